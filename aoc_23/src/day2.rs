@@ -1,3 +1,5 @@
+use crate::util;
+
 #[derive(Default, Debug)]
 struct Cubes {
     blue: u32,
@@ -26,11 +28,7 @@ fn parse_round(round_str: &str) -> Cubes {
     round
 }
 
-fn parse_game(line: &str) -> Option<Game> {
-    if line.is_empty() {
-        return None;
-    }
-
+fn parse_game(line: &str) -> Game {
     let (game_str, rounds_str) = line.split_once(":").unwrap();
     let game_id = game_str
         .strip_prefix("Game ")
@@ -41,16 +39,15 @@ fn parse_game(line: &str) -> Option<Game> {
         .split(';')
         .map(|round_str| parse_round(round_str))
         .collect();
-    Some(Game {
+    Game {
         id: game_id,
         rounds,
-    })
+    }
 }
 
 pub fn problem_a(contents: String) -> u32 {
-    let games: Vec<Game> = contents
-        .split('\n')
-        .filter_map(|line| parse_game(line))
+    let games: Vec<Game> = util::lines(&contents)
+        .map(|line| parse_game(line))
         .collect();
     let bag_cubes = Cubes {
         blue: 14,
@@ -78,9 +75,8 @@ fn calculate_cube_power(game: Game) -> u32 {
 }
 
 pub fn problem_b(contents: String) -> u32 {
-    contents
-        .split('\n')
-        .filter_map(|line| parse_game(line))
+    util::lines(&contents)
+        .map(|line| parse_game(line))
         .map(|game| calculate_cube_power(game))
         .sum()
 }
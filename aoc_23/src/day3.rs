@@ -1,5 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
+use crate::util;
+
 #[derive(Default, Debug, Eq, PartialEq, Hash)]
 struct Point {
     row: i32,
@@ -22,49 +24,55 @@ fn parse_schematic(contents: String) -> (HashSet<Point>, Vec<Part>, Vec<Point>)
     let mut gear_points = vec![];
     let mut part_numbers = vec![];
 
-    contents.split("\n").enumerate().for_each(|(u_row, line)| {
-        let row = i32::try_from(u_row).unwrap();
-        let mut cur_number = String::new();
-        line.chars().enumerate().for_each(|(u_column, char)| {
-            let column = i32::try_from(u_column).unwrap();
-
-            if char.is_ascii_digit() {
-                cur_number.push(char);
-            } else if !cur_number.is_empty() {
-                let point = Point {
+    util::grid(&contents)
+        .iter()
+        .enumerate()
+        .for_each(|(u_row, line)| {
+            let row = i32::try_from(u_row).unwrap();
+            let mut cur_number = String::new();
+            line.iter()
+                .copied()
+                .enumerate()
+                .for_each(|(u_column, char)| {
+                    let column = i32::try_from(u_column).unwrap();
+
+                    if char.is_ascii_digit() {
+                        cur_number.push(char);
+                    } else if !cur_number.is_empty() {
+                        let point = Point {
+                            row,
+                            column: column - 1,
+                        };
+                        let part = Part {
+                            end_point: point,
+                            length: i32::try_from(cur_number.len()).unwrap(),
+                            value: cur_number.parse().unwrap(),
+                        };
+                        part_numbers.push(part);
+                        cur_number = String::new();
+                    }
+                    if is_symbol(char) {
+                        if char == '*' {
+                            gear_points.push(Point { row, column })
+                        }
+                        point_to_is_symbol.insert(Point { row, column });
+                    }
+                });
+
+            if !cur_number.is_empty() {
+                let end_point = Point {
                     row,
-                    column: column - 1,
+                    column: i32::try_from(line.len()).unwrap() - 1,
                 };
                 let part = Part {
-                    end_point: point,
+                    end_point,
                     length: i32::try_from(cur_number.len()).unwrap(),
                     value: cur_number.parse().unwrap(),
                 };
                 part_numbers.push(part);
-                cur_number = String::new();
-            }
-            if is_symbol(char) {
-                if char == '*' {
-                    gear_points.push(Point { row, column })
-                }
-                point_to_is_symbol.insert(Point { row, column });
             }
         });
 
-        if !cur_number.is_empty() {
-            let end_point = Point {
-                row,
-                column: i32::try_from(line.len()).unwrap() - 1,
-            };
-            let part = Part {
-                end_point,
-                length: i32::try_from(cur_number.len()).unwrap(),
-                value: cur_number.parse().unwrap(),
-            };
-            part_numbers.push(part);
-        }
-    });
-
     (point_to_is_symbol, part_numbers, gear_points)
 }
 
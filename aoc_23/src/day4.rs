@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use crate::util;
+
 struct Card {
     id: u32,
     winning_numbers: Vec<u32>,
@@ -7,14 +9,6 @@ struct Card {
     num_matching: u32,
 }
 
-fn parse_numbers(numbers_str: &str) -> Vec<u32> {
-    numbers_str
-        .split(" ")
-        .filter(|str| !str.is_empty())
-        .map(|number_str| number_str.trim().parse().unwrap())
-        .collect()
-}
-
 fn parse_card(line: &str) -> Card {
     let (card_str, numbers_str) = line.split_once(":").unwrap();
     let card_id = card_str
@@ -24,8 +18,8 @@ fn parse_card(line: &str) -> Card {
         .parse::<u32>()
         .unwrap();
     let (card_number_str, winning_numbers_str) = numbers_str.split_once("|").unwrap();
-    let winning_numbers = parse_numbers(winning_numbers_str);
-    let card_numbers = parse_numbers(card_number_str);
+    let winning_numbers = util::numbers(winning_numbers_str);
+    let card_numbers = util::numbers(card_number_str);
     let usize_matching = card_numbers
         .iter()
         .filter(|number| winning_numbers.contains(&number))
@@ -48,19 +42,14 @@ fn calculate_card_score(card: Card) -> u32 {
 }
 
 pub fn problem_a(contents: String) -> u32 {
-    contents
-        .split('\n')
-        .filter(|line| !line.is_empty())
+    util::lines(&contents)
         .map(|line| parse_card(line))
         .map(|card| calculate_card_score(card))
         .sum()
 }
 
 pub fn problem_b(contents: String) -> u32 {
-    let cards = contents
-        .split('\n')
-        .filter(|line| !line.is_empty())
-        .map(|line| parse_card(line));
+    let cards = util::lines(&contents).map(|line| parse_card(line));
 
     let card_num_to_card: HashMap<u32, Card> =
         cards.into_iter().map(|card| (card.id, card)).collect();
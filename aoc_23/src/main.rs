@@ -5,11 +5,14 @@ mod day1;
 mod day2;
 mod day3;
 mod day4;
+mod submit;
+mod util;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let problem = &args[1];
     let file_path = &args[2];
+    let should_submit = args[3..].iter().any(|arg| arg == "--submit");
     let contents = fs::read_to_string(file_path).expect("Should have been able to read the file");
     let result = match problem.as_str() {
         "1a" => day1::problem_a(contents),
@@ -23,4 +26,8 @@ fn main() {
         &_ => panic!("Day not recognized"),
     };
     println!("{}", result);
+    if should_submit {
+        let (day, level) = submit::parse_problem(problem);
+        println!("{}", submit::submit_answer(day, level, &result.to_string()));
+    }
 }
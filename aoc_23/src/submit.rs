@@ -0,0 +1,66 @@
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const YEAR: u32 = 2023;
+
+/// Posts the answer to adventofcode.com and returns what it said about it.
+/// Shells out to curl to keep this crate free of dependencies.
+pub fn submit_answer(day: u32, level: u32, answer: &str) -> String {
+    let session = env::var("AOC_SESSION").expect("AOC_SESSION should be set to submit answers");
+    // The cookie goes in a curl config on stdin, on the command line any
+    // local user could read it with ps
+    let mut curl = Command::new("curl")
+        .args([
+            "--silent",
+            "--fail",
+            "--config",
+            "-",
+            "--data-urlencode",
+            &format!("level={}", level),
+            "--data-urlencode",
+            &format!("answer={}", answer),
+            &format!("https://adventofcode.com/{}/day/{}/answer", YEAR, day),
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Should have been able to run curl");
+    curl.stdin
+        .take()
+        .expect("curl's stdin is piped")
+        .write_all(format!("cookie = \"session={}\"\n", session).as_bytes())
+        .expect("Should have been able to pass the cookie to curl");
+    let output = curl
+        .wait_with_output()
+        .expect("Should have been able to run curl");
+    if !output.status.success() {
+        panic!("Submitting answer failed: {}", output.status);
+    }
+    describe_response(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn describe_response(body: &str) -> String {
+    if body.contains("That's the right answer") {
+        "Correct!".to_string()
+    } else if body.contains("That's not the right answer") {
+        "Incorrect".to_string()
+    } else if body.contains("You gave an answer too recently") {
+        "Answered too recently, wait before trying again".to_string()
+    } else if body.contains("You don't seem to be solving the right level") {
+        "Already solved (or not unlocked yet)".to_string()
+    } else {
+        format!("Unrecognized response:\n{}", body)
+    }
+}
+
+/// Splits a problem like "3b" into its day and level (a = 1, b = 2)
+pub fn parse_problem(problem: &str) -> (u32, u32) {
+    let (day_str, part) = problem.split_at(problem.len() - 1);
+    let level = match part {
+        "a" => 1,
+        "b" => 2,
+        _ => panic!("Part not recognized"),
+    };
+    (day_str.parse().expect("Day not recognized"), level)
+}
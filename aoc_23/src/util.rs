@@ -0,0 +1,35 @@
+/// Non-empty lines of the input
+pub fn lines(contents: &str) -> impl Iterator<Item = &str> {
+    contents.split('\n').filter(|line| !line.is_empty())
+}
+
+/// Every unsigned number in the string, in order. Anything that isn't a
+/// digit is treated as a separator.
+pub fn numbers(string: &str) -> Vec<u32> {
+    string
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|number_str| !number_str.is_empty())
+        .map(|number_str| number_str.parse().unwrap())
+        .collect()
+}
+
+/// Sections of the input separated by blank lines
+#[allow(dead_code)]
+pub fn sections(contents: &str) -> Vec<&str> {
+    contents
+        .split("\n\n")
+        .map(|section| section.trim_matches('\n'))
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+/// Input as a grid of chars, indexed by [row][column]. Blank rows are kept
+/// so row indexes match the input, only the trailing newline is dropped.
+pub fn grid(contents: &str) -> Vec<Vec<char>> {
+    contents
+        .strip_suffix('\n')
+        .unwrap_or(contents)
+        .split('\n')
+        .map(|line| line.chars().collect())
+        .collect()
+}
@@ -1,33 +1,80 @@
-use anyhow::anyhow;
-use log::info;
+use anyhow::Context as _;
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::fs;
 use std::io;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Instant;
+use std::time::SystemTime;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
+use toml_edit::DocumentMut;
 use url::Url;
 
-use crate::config::get_repo_config;
-use crate::config::resolve_secret;
-use crate::config::ChatProvider;
-use crate::config::Config;
-use crate::config::WebChatProvider;
-use crate::git;
-use crate::git::determine_repo_root_dir;
-use crate::llm;
-use crate::llm::anthropic;
-use crate::llm::perplexity;
-use crate::llm::vertex_ai;
-use crate::llm::LlmProvider;
-use crate::notes::format_note_path;
-use crate::notes::note_template;
-use crate::notes::DailyNoteSpecifier;
-use crate::notes::NoteSpecifier;
+use wkfl_core::config;
+use wkfl_core::config::get_repo_config;
+use wkfl_core::config::ChatProvider;
+use wkfl_core::config::Config;
+use wkfl_core::config::WebChatProvider;
+use wkfl_core::errors::ConfigError;
+use wkfl_core::git;
+use wkfl_core::git::determine_repo_root_dir;
+use wkfl_core::http::HttpClient;
+use wkfl_core::llm;
+use wkfl_core::llm::anthropic;
+use wkfl_core::llm::cache::CachedChat;
+use wkfl_core::llm::cache::CachedGroundedChat;
+use wkfl_core::llm::cache::LlmCache;
+use wkfl_core::llm::history::Conversation;
+use wkfl_core::llm::perplexity;
+use wkfl_core::llm::templates;
+use wkfl_core::llm::vertex_ai;
+use wkfl_core::llm::LlmProvider;
+use wkfl_core::migrations;
+use wkfl_core::note_index::NoteIndex;
+use wkfl_core::note_links;
+use wkfl_core::note_links::LinkIndex;
+use wkfl_core::notes;
+use wkfl_core::notes::format_note_path;
+use wkfl_core::notes::DailyNoteSpecifier;
+use wkfl_core::notes::NoteSpecifier;
+use wkfl_core::snippets;
+use wkfl_core::snippets::Snippet;
+use wkfl_core::utils;
+use wkfl_core::utils::run_commands;
+
+use crate::llm_args::Attachments;
+use crate::llm_args::ModelTypeArg;
+use crate::outdated;
+use crate::outdated::Language;
+use crate::pairs;
+use crate::progress;
+use crate::progress::ProgressBar;
 use crate::prompts::basic_prompt;
 use crate::prompts::boolean_prompt;
+use crate::prompts::editable_prompt;
+use crate::prompts::get_query;
+use crate::prompts::multi_select_prompt_with_layout;
+use crate::prompts::multiline_prompt;
+use crate::prompts::private_prompt;
+use crate::prompts::secret_prompt;
 use crate::prompts::select_prompt;
+use crate::prompts::select_prompt_with_layout;
+use crate::prompts::select_prompt_with_preview;
 use crate::prompts::Link;
+use crate::prompts::SelectLayout;
 use crate::repositories::get_repositories_in_directory;
+use crate::review;
 use crate::shell_actions::ShellAction;
-use crate::utils;
-use crate::utils::run_commands;
+use crate::suggestions;
+use crate::suggestions::Suggestion;
 use crate::Context;
 
 pub fn start_workflow(context: &mut Context) -> anyhow::Result<()> {
@@ -65,12 +112,14 @@ pub fn start_workflow(context: &mut Context) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn end_workflow() -> anyhow::Result<()> {
+pub fn end_workflow(maybe_branch: Option<String>) -> anyhow::Result<()> {
     let repo = git::get_repository()?;
     let repo_config = get_repo_config(determine_repo_root_dir(&repo))?;
     run_commands(&repo_config.pre_end_commands)?;
     if repo.is_worktree() {
         anyhow::bail!("For worktree based repos call stop from base of repo with name of worktree");
+    } else if let Some(name) = maybe_branch {
+        git::remove_branch_or_worktree(&repo, &name)?;
     } else if repo.is_bare() {
         let worktrees = git::get_worktrees(&repo)?;
         let workspace_name = select_prompt("Worktree Name:", &worktrees)?;
@@ -85,6 +134,96 @@ pub fn end_workflow() -> anyhow::Result<()> {
     Ok(())
 }
 
+pub fn commit_with_pairs(
+    mut pairs: Vec<String>,
+    pick_recent: bool,
+    git_args: Vec<String>,
+    config: Config,
+) -> anyhow::Result<()> {
+    if pick_recent {
+        let recent = pairs::recent_pairs()?;
+        if recent.is_empty() {
+            anyhow::bail!("No recent pairs, use --pair to pick someone");
+        }
+        pairs.push(select_prompt("Pair:", &recent)?.to_string());
+    }
+    let notes_dir = config.notes_directory_path()?;
+    let trailers = pairs
+        .iter()
+        .map(|who| pairs::co_author_trailer(&notes_dir, who))
+        .collect::<anyhow::Result<Vec<String>>>()?;
+
+    let mut command = Command::new("git");
+    command.arg("commit");
+    for trailer in &trailers {
+        command.arg("--trailer").arg(trailer);
+    }
+    let status = command.args(git_args).status()?;
+    if !status.success() {
+        anyhow::bail!("git commit failed with {}", status);
+    }
+    pairs::remember_pairs(&pairs)?;
+    Ok(())
+}
+
+enum GroupStatus {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+/// Runs the group and everything it depends on, stopping at the first
+/// failing command
+pub fn run_command_group(name: &str) -> anyhow::Result<()> {
+    let repo = git::get_repository()?;
+    let repo_root = determine_repo_root_dir(&repo);
+    let repo_config = get_repo_config(repo_root)?;
+    let order = repo_config.command_group_order(name)?;
+
+    let mut results = vec![];
+    let mut failed = false;
+    for group_name in &order {
+        if failed {
+            results.push((group_name, GroupStatus::Skipped, None));
+            continue;
+        }
+        info!("Running {group_name}");
+        let started = Instant::now();
+        let mut status = GroupStatus::Succeeded;
+        for command in &repo_config.commands[*group_name].commands {
+            let exit_status = Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .current_dir(repo_root)
+                .status()?;
+            if !exit_status.success() {
+                info!("`{command}` failed with {exit_status}");
+                status = GroupStatus::Failed;
+                failed = true;
+                break;
+            }
+        }
+        results.push((group_name, status, Some(started.elapsed())));
+    }
+
+    println!("\n{:<24} {:>10} {:>9}", "group", "status", "time (s)");
+    for (group_name, status, maybe_elapsed) in &results {
+        let status_str = match status {
+            GroupStatus::Succeeded => "ok",
+            GroupStatus::Failed => "failed",
+            GroupStatus::Skipped => "skipped",
+        };
+        let elapsed_str = maybe_elapsed
+            .map(|elapsed| format!("{:.1}", elapsed.as_secs_f32()))
+            .unwrap_or("-".to_string());
+        println!("{:<24} {:>10} {:>9}", group_name, status_str, elapsed_str);
+    }
+    if failed {
+        anyhow::bail!("Command group {} failed", name);
+    }
+    Ok(())
+}
+
 pub fn list_repositories(config: Config) -> anyhow::Result<()> {
     let base_repo_path = config.repositories_directory_path()?;
     let repo_paths = get_repositories_in_directory(&base_repo_path)?;
@@ -95,8 +234,85 @@ pub fn list_repositories(config: Config) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn switch_repo(context: &mut Context) -> anyhow::Result<()> {
+pub fn repos_outdated(
+    languages: Vec<Language>,
+    maybe_repo: Option<String>,
+    config: Config,
+) -> anyhow::Result<()> {
+    let base_repo_path = config.repositories_directory_path()?;
+    let repo_paths = match maybe_repo {
+        Some(name) => {
+            let repo_path = base_repo_path.join(&name);
+            if !repo_path.join(".git").exists() {
+                anyhow::bail!("'{}' isn't a repo in {}", name, base_repo_path.display());
+            }
+            vec![repo_path]
+        }
+        None => get_repositories_in_directory(&base_repo_path)?,
+    };
+    let http = HttpClient::from_config(&config.http)?;
+
+    let mut rows = vec![];
+    let mut skipped = 0;
+    let mut progress = ProgressBar::new("Checking", repo_paths.len());
+    for repo_path in &repo_paths {
+        let repo_name = repo_path
+            .strip_prefix(&base_repo_path)?
+            .display()
+            .to_string();
+        progress.next(&repo_name);
+        for language in Language::detect(repo_path) {
+            if !languages.is_empty() && !languages.contains(&language) {
+                continue;
+            }
+            debug!("Checking {:?} dependencies in {}", language, repo_name);
+            match outdated::find_outdated(repo_path, language, &http) {
+                Ok(dependencies) => rows.extend(
+                    dependencies
+                        .into_iter()
+                        .map(|dependency| (repo_name.clone(), language, dependency)),
+                ),
+                Err(err) => {
+                    warn!("Skipping {:?} in {}: {}", language, repo_name, err);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+    drop(progress);
+
+    if rows.is_empty() {
+        if skipped == 0 {
+            println!("Everything is up to date");
+        }
+        return Ok(());
+    }
+    println!(
+        "{:<24} {:<10} {:<24} {:>12} {:>12}",
+        "repo", "language", "name", "current", "latest"
+    );
+    for (repo_name, language, dependency) in rows {
+        let language_str = format!("{:?}", language).to_lowercase();
+        println!(
+            "{:<24} {:<10} {:<24} {:>12} {:>12}",
+            repo_name, language_str, dependency.name, dependency.current, dependency.latest
+        );
+    }
+    Ok(())
+}
+
+pub fn switch_repo(maybe_name: Option<String>, context: &mut Context) -> anyhow::Result<()> {
     let base_repo_path = context.config.repositories_directory_path()?;
+    if let Some(name) = maybe_name {
+        let repo_path = base_repo_path.join(&name);
+        if !repo_path.join(".git").exists() {
+            anyhow::bail!("'{}' isn't a repo in {}", name, base_repo_path.display());
+        }
+        context
+            .shell_actions
+            .push(ShellAction::Cd { path: repo_path });
+        return Ok(());
+    }
     let repo_paths = get_repositories_in_directory(&base_repo_path)?;
     let repo_paths_strs: Vec<String> = repo_paths
         .iter()
@@ -168,24 +384,89 @@ pub fn print_repo_debug_info() -> anyhow::Result<()> {
         info!("has_changes: n/a");
     }
     info!("worktrees: {:?}", git::get_worktrees(&repo)?);
+    let branch = git::get_current_branch_name(&repo).ok();
+    info!("branch: {:?}", branch);
+    info!(
+        "ticket: {:?}",
+        branch
+            .as_deref()
+            .and_then(utils::extract_ticket_from_branch)
+    );
+    Ok(())
+}
+
+/// Prints a prompt's answer for a script to read, as a JSON string with
+/// `json`
+fn print_answer(answer: &str, json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string(answer)?);
+    } else {
+        println!("{}", answer);
+    }
     Ok(())
 }
 
-pub fn confirm(prompt: &str, default: bool) -> anyhow::Result<()> {
-    if !boolean_prompt(prompt, default)? {
+/// The answer is the exit code, or printed as true or false with `json`
+pub fn confirm(prompt: &str, default: bool, json: bool) -> anyhow::Result<()> {
+    let confirmed = boolean_prompt(prompt, default)?;
+    if json {
+        println!("{}", confirmed);
+    } else if !confirmed {
         std::process::exit(1);
     }
     Ok(())
 }
 
-pub fn select(prompt: &str) -> anyhow::Result<()> {
+pub fn input(prompt: &str, initial: Option<String>, json: bool) -> anyhow::Result<()> {
+    let answer = editable_prompt(prompt, initial.as_deref().unwrap_or(""))?;
+    print_answer(&answer, json)
+}
+
+pub fn secret(prompt: &str, json: bool) -> anyhow::Result<()> {
+    print_answer(&secret_prompt(prompt)?, json)
+}
+
+/// Options to pick from, one per line on stdin
+fn read_options() -> anyhow::Result<Vec<String>> {
     let options: Vec<String> = io::stdin()
         .lines()
         .map_while(Result::ok)
         .filter(|s| !s.is_empty())
         .collect();
-    let result = select_prompt(prompt, &options)?;
-    println!("{}", result);
+    if options.is_empty() {
+        anyhow::bail!("No options to pick from, pass them on stdin one per line");
+    }
+    Ok(options)
+}
+
+/// Selection from stdin's lines, with the layout from the config unless
+/// one is given
+pub fn select(prompt: &str, maybe_layout: Option<SelectLayout>, json: bool) -> anyhow::Result<()> {
+    let options = read_options()?;
+    let result = match maybe_layout {
+        Some(layout) => select_prompt_with_layout(prompt, &options, layout)?,
+        None => select_prompt(prompt, &options)?,
+    };
+    print_answer(result, json)
+}
+
+/// Like `select`, but any number of lines can be picked. They're printed
+/// one per line, or as a JSON array with `json`.
+pub fn multi_select(
+    prompt: &str,
+    maybe_layout: Option<SelectLayout>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let options = read_options()?;
+    let layout = maybe_layout.unwrap_or(SelectLayout::configured(options.len()));
+    let selected = multi_select_prompt_with_layout(prompt, &options, layout)?;
+    if json {
+        println!("{}", serde_json::to_string(&selected)?);
+    } else {
+        for option in selected {
+            println!("{}", option);
+        }
+    }
     Ok(())
 }
 
@@ -193,6 +474,11 @@ pub fn open_daily_note(
     daily_note_to_open: DailyNoteSpecifier,
     context: &mut Context,
 ) -> anyhow::Result<()> {
+    if matches!(daily_note_to_open, DailyNoteSpecifier::Today)
+        && context.config.notes_rollover_on_open
+    {
+        rollover_tasks(&context.config)?;
+    }
     open_note(
         NoteSpecifier::Daily {
             day: daily_note_to_open,
@@ -217,16 +503,86 @@ pub fn open_person_note(maybe_who: Option<String>, context: &mut Context) -> any
     open_note(NoteSpecifier::Person { who }, context)
 }
 
+/// Opens today's note for a meeting. A new meeting is also added to the
+/// Meetings section of each attendee's person note.
+pub fn open_meeting_note(
+    maybe_title: Option<String>,
+    with: Vec<String>,
+    context: &mut Context,
+) -> anyhow::Result<()> {
+    let title = match maybe_title {
+        Some(title) => title,
+        None => basic_prompt("Meeting:")?,
+    };
+    let with: Vec<String> = with
+        .iter()
+        .map(|who| who.trim().to_string())
+        .filter(|who| !who.is_empty())
+        .collect();
+    let backlink = notes::meeting_backlink(&title);
+    let meeting = NoteSpecifier::Meeting {
+        title,
+        with: with.clone(),
+    };
+    let notes_dir = context.config.notes_directory_path()?;
+    let is_new = !notes_dir.join(format_note_path(&meeting)).exists();
+    open_note(meeting, context)?;
+    if is_new {
+        for who in with {
+            let person = NoteSpecifier::Person { who };
+            notes::append_to_note(
+                &notes_dir,
+                &person,
+                "Meetings",
+                &backlink,
+                &note_template_vars(&person),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Values available to note templates. Repo context is only filled in
+/// when run from inside a repo.
+fn note_template_vars(note_specifier: &NoteSpecifier) -> Vec<(&'static str, Option<String>)> {
+    let mut vars = notes::note_template_vars(note_specifier);
+    let maybe_repo = git::get_repository().ok();
+    let repo_name = maybe_repo.as_ref().and_then(|repo| {
+        git::determine_repo_root_dir(repo)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    });
+    let branch = maybe_repo
+        .as_ref()
+        .and_then(|repo| git::get_current_branch_name(repo).ok());
+    let ticket = branch
+        .as_deref()
+        .and_then(utils::extract_ticket_from_branch);
+    vars.extend([("repo", repo_name), ("branch", branch), ("ticket", ticket)]);
+    vars
+}
+
 fn open_note(note_to_open: NoteSpecifier, context: &mut Context) -> anyhow::Result<()> {
     let notes_subpath = format_note_path(&note_to_open);
-    let mut notes_file = context.config.notes_directory_path()?;
-    notes_file.push(notes_subpath);
+    let notes_dir = context.config.notes_directory_path()?;
+    let notes_file = notes_dir.join(notes_subpath);
     fs::create_dir_all(notes_file.parent().unwrap())?;
 
     if !notes_file.exists() {
-        let template = note_template(&note_to_open);
+        let template = notes::new_note_contents(
+            &notes_dir,
+            &note_to_open,
+            &note_template_vars(&note_to_open),
+        );
         fs::write(&notes_file, template)?;
     }
+    if let Err(err) = update_linked_from(&notes_dir, &notes_file) {
+        warn!(
+            "Couldn't update the links to {}: {}",
+            notes_file.display(),
+            err
+        );
+    }
 
     context
         .shell_actions
@@ -234,12 +590,485 @@ fn open_note(note_to_open: NoteSpecifier, context: &mut Context) -> anyhow::Resu
     Ok(())
 }
 
+/// Prints the notes, most recently modified first, optionally only ones
+/// of a kind (see `notes::note_kind`)
+pub fn list_notes(
+    maybe_kind: Option<&str>,
+    maybe_limit: Option<usize>,
+    config: Config,
+) -> anyhow::Result<()> {
+    let notes_dir = config.notes_directory_path()?;
+    let note_paths = notes::find_notes_by_recency(&notes_dir, false)?
+        .into_iter()
+        .filter(|note_path| {
+            maybe_kind.is_none_or(|kind| {
+                note_path
+                    .strip_prefix(&notes_dir)
+                    .is_ok_and(|relative_path| notes::note_kind(relative_path) == kind)
+            })
+        })
+        .take(maybe_limit.unwrap_or(usize::MAX));
+    for note_path in note_paths {
+        println!("{}", note_label(&notes_dir, &note_path));
+    }
+    Ok(())
+}
+
+/// Adds a `- HH:MM text` bullet to the Log section of today's daily note,
+/// without opening it. Times are UTC, the same as the daily note's date.
+pub fn log_to_daily_note(text: &str, config: Config) -> anyhow::Result<()> {
+    let notes_dir = config.notes_directory_path()?;
+    let today = NoteSpecifier::Daily {
+        day: DailyNoteSpecifier::Today,
+    };
+    let cur_time: OffsetDateTime = SystemTime::now().into();
+    let time = cur_time.format(format_description!("[hour]:[minute]"))?;
+    // Later lines are indented so they stay part of the bullet
+    let entry = format!("- {} {}", time, text.trim().replace('\n', "\n  "));
+    let note_path = notes::append_to_daily(&notes_dir, "Log", &entry, &note_template_vars(&today))?;
+    info!("Logged to {}", note_path.display());
+    Ok(())
+}
+
+pub fn rollover_tasks(config: &Config) -> anyhow::Result<()> {
+    let notes_dir = config.notes_directory_path()?;
+    let today = NoteSpecifier::Daily {
+        day: DailyNoteSpecifier::Today,
+    };
+    match notes::rollover_tasks(&notes_dir, &note_template_vars(&today))? {
+        Some((previous_note, 0)) => info!("No unfinished tasks in {}", previous_note.display()),
+        Some((previous_note, count)) => info!(
+            "Carried over {} task(s) from {}",
+            count,
+            previous_note.display()
+        ),
+        None => info!("No earlier daily note to carry tasks over from"),
+    }
+    Ok(())
+}
+
+/// Compresses archive/<year>/ into archive/<year>.tar.zst. If the year was
+/// already compressed the existing notes are extracted first so they end up
+/// in the new tarball as well.
+fn compress_archive_year(archive_dir: &Path, year: &str) -> anyhow::Result<()> {
+    let tarball = format!("{}.tar.zst", year);
+    let run_tar = |args: &[&str]| -> anyhow::Result<()> {
+        let output = Command::new("tar")
+            .arg("--zstd")
+            .args(args)
+            .current_dir(archive_dir)
+            .output()
+            .context("Failed to run tar")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "tar failed, output: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    };
+
+    if archive_dir.join(&tarball).exists() {
+        run_tar(&["-xf", &tarball])?;
+    }
+    run_tar(&["-cf", &tarball, year])?;
+    fs::remove_dir_all(archive_dir.join(year))?;
+    Ok(())
+}
+
+/// Keeps the note's `## Linked from` section in step with the notes that
+/// have `[[wiki links]]` to it
+fn update_linked_from(notes_dir: &Path, note_path: &Path) -> anyhow::Result<()> {
+    let index = LinkIndex::update(notes_dir)?;
+    let mut sources: Vec<&Path> = index
+        .backlinks(note_path.strip_prefix(notes_dir)?)
+        .into_iter()
+        .map(|(source, _)| source)
+        .collect();
+    sources.dedup();
+    let linked_from: Vec<String> = sources
+        .into_iter()
+        .map(|source| {
+            let link = note_links::link_name(source);
+            match notes::note_title(&notes_dir.join(source)) {
+                Some(title) => format!("- [[{}|{}]]", link, title),
+                None => format!("- [[{}]]", link),
+            }
+        })
+        .collect();
+    let linked_from = linked_from.join("\n");
+    let contents = fs::read_to_string(note_path)?;
+    // Rewriting the note when nothing changed would bump its modified time
+    // and re-indexing for every open
+    let current = notes::section_text(&contents, notes::LINKED_FROM_SECTION).unwrap_or_default();
+    if current != linked_from {
+        let updated = notes::replace_section(&contents, notes::LINKED_FROM_SECTION, &linked_from);
+        fs::write(note_path, updated)?;
+    }
+    Ok(())
+}
+
+/// Prints every `[[wiki link]]` to the note, which can be named by its
+/// path, file name or title
+pub fn show_backlinks(note: &str, config: Config) -> anyhow::Result<()> {
+    let notes_dir = config.notes_directory_path()?;
+    let index = LinkIndex::update(&notes_dir)?;
+    let note_path = index
+        .resolve(note)
+        .ok_or(anyhow::anyhow!("No note called {}", note))?;
+    for (source, line_number) in index.backlinks(note_path) {
+        let contents = fs::read_to_string(notes_dir.join(source))?;
+        let line = contents.lines().nth(line_number - 1).unwrap_or("");
+        println!("{}:{}: {}", source.display(), line_number, line.trim());
+    }
+    Ok(())
+}
+
+pub fn list_tags(config: Config) -> anyhow::Result<()> {
+    let index = LinkIndex::update(&config.notes_directory_path()?)?;
+    for (tag, count) in index.tag_counts() {
+        println!("{} {}", count, tag);
+    }
+    Ok(())
+}
+
+/// Lists the notes with the tag, or picks one of them to open
+pub fn tagged_notes(tag: &str, open: bool, context: &mut Context) -> anyhow::Result<()> {
+    let notes_dir = context.config.notes_directory_path()?;
+    let index = LinkIndex::update(&notes_dir)?;
+    let note_paths: Vec<PathBuf> = index
+        .tagged(tag)
+        .into_iter()
+        .map(|relative_path| notes_dir.join(relative_path))
+        .collect();
+    if open {
+        return pick_note_to_open(&notes_dir, note_paths, context);
+    }
+    for note_path in note_paths {
+        println!("{}", note_label(&notes_dir, &note_path));
+    }
+    Ok(())
+}
+
+/// `[kind] title (path)` for showing a note in a list
+fn note_label(notes_dir: &Path, note_path: &Path) -> String {
+    let relative_path = note_path
+        .strip_prefix(notes_dir)
+        .expect("Notes are all in the notes directory");
+    let kind = notes::note_kind(relative_path);
+    match notes::note_title(note_path) {
+        Some(title) => format!("[{}] {} ({})", kind, title, relative_path.display()),
+        None => format!("[{}] {}", kind, relative_path.display()),
+    }
+}
+
+pub fn open_any_note(context: &mut Context) -> anyhow::Result<()> {
+    let notes_dir = context.config.notes_directory_path()?;
+    let note_paths = notes::find_notes(&notes_dir, false)?;
+    pick_note_to_open(&notes_dir, note_paths, context)
+}
+
+pub fn open_recent_note(limit: usize, context: &mut Context) -> anyhow::Result<()> {
+    let notes_dir = context.config.notes_directory_path()?;
+    let mut note_paths = notes::find_notes_by_recency(&notes_dir, false)?;
+    note_paths.truncate(limit);
+    pick_note_to_open(&notes_dir, note_paths, context)
+}
+
+fn pick_note_to_open(
+    notes_dir: &Path,
+    note_paths: Vec<PathBuf>,
+    context: &mut Context,
+) -> anyhow::Result<()> {
+    if note_paths.is_empty() {
+        anyhow::bail!("No notes found in {}", notes_dir.display());
+    }
+    let options: Vec<String> = note_paths
+        .iter()
+        .map(|note_path| note_label(notes_dir, note_path))
+        .collect();
+    let preview = |selected: &str| {
+        options
+            .iter()
+            .position(|option| option == selected)
+            .and_then(|index| fs::read_to_string(&note_paths[index]).ok())
+            .unwrap_or_default()
+    };
+    let selected = select_prompt_with_preview("Note:", &options, &preview)?;
+    let index = options
+        .iter()
+        .position(|option| option == selected)
+        .expect("Selected option comes from the options");
+    context.shell_actions.push(ShellAction::EditFile {
+        path: note_paths[index].clone(),
+    });
+    Ok(())
+}
+
+pub fn add_snippet(name: String, tags: Vec<String>, context: &mut Context) -> anyhow::Result<()> {
+    let notes_dir = context.config.notes_directory_path()?;
+    let path = snippets::snippet_path(&notes_dir, &name)?;
+    fs::create_dir_all(path.parent().expect("Snippets are in a directory"))?;
+
+    let mut stdin = io::stdin();
+    if stdin.is_terminal() {
+        if !path.exists() {
+            let snippet = Snippet {
+                name,
+                tags,
+                body: String::new(),
+            };
+            fs::write(&path, snippet.to_markdown())?;
+        }
+        context.shell_actions.push(ShellAction::EditFile { path });
+        return Ok(());
+    }
+
+    if path.exists() {
+        anyhow::bail!("Snippet {} already exists at {}", name, path.display());
+    }
+    let mut body = String::new();
+    stdin.read_to_string(&mut body)?;
+    let snippet = Snippet { name, tags, body };
+    fs::write(&path, snippet.to_markdown())?;
+    info!("Added {}", path.display());
+    Ok(())
+}
+
+pub fn list_snippets(maybe_tag: Option<String>, config: Config) -> anyhow::Result<()> {
+    let snippets = snippets::load_snippets(&config.notes_directory_path()?)?;
+    for snippet in snippets {
+        if let Some(tag) = &maybe_tag {
+            if !snippet.tags.contains(tag) {
+                continue;
+            }
+        }
+        let line = format!("{:<24} {}", snippet.name, snippet.tags.join(", "));
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+/// Finds the snippet by name, or lets the user pick one, and fills in its
+/// placeholders by prompting for them
+fn get_rendered_snippet(maybe_name: Option<String>, config: Config) -> anyhow::Result<String> {
+    let notes_dir = config.notes_directory_path()?;
+    let snippet = match maybe_name {
+        Some(name) => {
+            let path = snippets::snippet_path(&notes_dir, &name)?;
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("No snippet named {} at {}", name, path.display()))?;
+            Snippet::parse(&name, &contents)
+        }
+        None => {
+            let mut all_snippets = snippets::load_snippets(&notes_dir)?;
+            if all_snippets.is_empty() {
+                anyhow::bail!("No snippets found, add one with `wkfl snippets add`");
+            }
+            let options: Vec<String> = all_snippets
+                .iter()
+                .map(|snippet| match snippet.tags.is_empty() {
+                    true => snippet.name.clone(),
+                    false => format!("{} [{}]", snippet.name, snippet.tags.join(", ")),
+                })
+                .collect();
+            let selected = select_prompt("Snippet:", &options)?;
+            let index = options
+                .iter()
+                .position(|option| option == selected)
+                .expect("Selected option comes from the options");
+            all_snippets.swap_remove(index)
+        }
+    };
+
+    let placeholders = snippet.placeholders();
+    let mut vars = vec![];
+    for placeholder in &placeholders {
+        let value = private_prompt(&format!("{}:", placeholder))?;
+        vars.push((placeholder.as_str(), Some(value)));
+    }
+    Ok(notes::render_template(&snippet.body, &vars))
+}
+
+pub fn copy_snippet(maybe_name: Option<String>, config: Config) -> anyhow::Result<()> {
+    let rendered = get_rendered_snippet(maybe_name, config)?;
+    eprint!("{}", snippets::osc52_copy(&rendered));
+    info!("Copied to clipboard");
+    Ok(())
+}
+
+pub fn run_snippet(maybe_name: Option<String>, config: Config) -> anyhow::Result<()> {
+    let rendered = get_rendered_snippet(maybe_name, config)?;
+    let status = Command::new("sh").arg("-c").arg(&rendered).status()?;
+    if !status.success() {
+        anyhow::bail!("Snippet failed with {}", status);
+    }
+    Ok(())
+}
+
+pub fn archive_notes(
+    compress: bool,
+    maybe_older_than_days: Option<i64>,
+    config: Config,
+) -> anyhow::Result<()> {
+    let notes_dir = config.notes_directory_path()?;
+    let cur_time: OffsetDateTime = SystemTime::now().into();
+    let older_than_days = maybe_older_than_days.unwrap_or(config.notes_archive_after_days());
+    let cutoff = cur_time
+        .date()
+        .checked_sub(notes::checked_days(older_than_days)?)
+        .ok_or(anyhow::anyhow!(
+            "Can't archive notes older than {} days",
+            older_than_days
+        ))?;
+
+    let mut years_archived = BTreeSet::new();
+    let mut moves = vec![];
+    let mut failed = 0;
+    for (note_path, date) in notes::find_daily_notes(&notes_dir)? {
+        if date >= cutoff {
+            continue;
+        }
+        // Keep going on failure, so one bad note doesn't stop the rest
+        // being archived or the indexes learning about the ones that were
+        match archive_daily_note(&notes_dir, &note_path, date) {
+            Ok(archive_path) => {
+                info!("Archived {}", note_path.display());
+                years_archived.insert(date.year().to_string());
+                moves.push((
+                    note_path.strip_prefix(&notes_dir)?.to_path_buf(),
+                    archive_path.strip_prefix(&notes_dir)?.to_path_buf(),
+                ));
+            }
+            Err(err) => {
+                warn!("Failed to archive {}: {:#}", note_path.display(), err);
+                failed += 1;
+            }
+        }
+    }
+    info!("Archived notes from {} year(s)", years_archived.len());
+    // Compressed notes can't be read, so they drop out of the indexes when
+    // they're next updated instead
+    if !moves.is_empty() && !compress {
+        NoteIndex::move_notes(&notes_dir, &moves)?;
+        LinkIndex::move_notes(&notes_dir, &moves)?;
+    }
+
+    if compress {
+        let archive_dir = notes_dir.join(notes::ARCHIVE_DIR);
+        for year in years_archived {
+            compress_archive_year(&archive_dir, &year)?;
+            info!("Compressed archive for {}", year);
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("Failed to archive {} note(s)", failed);
+    }
+    Ok(())
+}
+
+/// Moves a daily note into the archive, returning where it was moved to
+fn archive_daily_note(notes_dir: &Path, note_path: &Path, date: Date) -> anyhow::Result<PathBuf> {
+    let archive_path = notes::archived_daily_note_path(notes_dir, note_path, date);
+    fs::create_dir_all(archive_path.parent().expect("Archive path has a parent"))?;
+    if archive_path.exists() {
+        anyhow::bail!("{} is already archived", archive_path.display());
+    }
+    fs::rename(note_path, &archive_path)?;
+    // Clean up the week directory once it is empty, remove_dir fails if it
+    // isn't which is fine
+    if let Some(week_dir) = note_path.parent() {
+        let _ = fs::remove_dir(week_dir);
+    }
+    Ok(archive_path)
+}
+
+pub fn search_notes(query: &str, include_archived: bool, config: Config) -> anyhow::Result<()> {
+    let notes_dir = config.notes_directory_path()?;
+    let query_lower = query.to_lowercase();
+    for note_path in notes::find_notes(&notes_dir, include_archived)? {
+        let contents = fs::read_to_string(&note_path)?;
+        let relative_path = note_path.strip_prefix(&notes_dir)?;
+        for (i, line) in contents.lines().enumerate() {
+            if line.to_lowercase().contains(&query_lower) {
+                println!("{}:{}: {}", relative_path.display(), i + 1, line.trim());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn semantic_search_notes(
+    query: &str,
+    include_archived: bool,
+    limit: usize,
+    config: Config,
+) -> anyhow::Result<()> {
+    let notes_dir = config.notes_directory_path()?;
+    let provider = config
+        .embeddings
+        .as_ref()
+        .ok_or(ConfigError::MissingKey("embeddings".to_string()))?
+        .provider
+        .clone();
+    let client = provider.create_client(config)?;
+    let index = NoteIndex::update(&notes_dir, client.as_ref())?;
+    let query_embedding = client
+        .embed(&[query.to_string()])?
+        .into_iter()
+        .next()
+        .ok_or(anyhow::anyhow!("No embedding returned for the query"))?;
+    for (score, entry) in index.search(&query_embedding, include_archived, limit) {
+        let title = notes::note_title(&notes_dir.join(&entry.path)).unwrap_or_default();
+        println!(
+            "{:.2} {}:{}: {}",
+            score,
+            entry.path.display(),
+            entry.line,
+            title
+        );
+    }
+    Ok(())
+}
+
 pub fn print_config(config: Config) {
     info!("config: {:?}", config);
 }
 
-pub fn run_perplexity_query(maybe_query: Option<String>, config: Config) -> anyhow::Result<()> {
-    let query = llm::get_query(maybe_query)?;
+pub fn migrate_config(dry_run: bool) -> anyhow::Result<()> {
+    let path = config::config_file_path()?;
+    if !path.exists() {
+        info!("No config at {}, nothing to migrate", path.display());
+        return Ok(());
+    }
+    let mut document: DocumentMut = fs::read_to_string(&path)?.parse()?;
+    let version = migrations::config_version(&document);
+    if !migrations::migrate(&mut document)? {
+        info!("Config is already at version {}", version);
+        return Ok(());
+    }
+    if dry_run {
+        print!("{}", document);
+        return Ok(());
+    }
+    let backup_path = path.with_file_name(format!("config.toml.v{}.bak", version));
+    fs::copy(&path, &backup_path)?;
+    fs::write(&path, document.to_string())?;
+    info!(
+        "Migrated config from version {} to {}, the old one is at {}",
+        version,
+        migrations::CURRENT_CONFIG_VERSION,
+        backup_path.display()
+    );
+    Ok(())
+}
+
+pub fn run_perplexity_query(
+    maybe_query: Option<String>,
+    attachments: Attachments,
+    config: Config,
+) -> anyhow::Result<()> {
+    let query = get_query(maybe_query, &attachments)?;
     let client = perplexity::PerplexityClient::from_config(config)?;
     let result = client.create_chat_completion(perplexity::PerplexityRequest {
         messages: vec![llm::Message {
@@ -264,31 +1093,61 @@ pub fn run_perplexity_query(maybe_query: Option<String>, config: Config) -> anyh
     Ok(())
 }
 
-pub fn run_anthropic_query(maybe_query: Option<String>, config: Config) -> anyhow::Result<()> {
-    let query = llm::get_query(maybe_query)?;
-    let api_key_raw = config
-        .anthropic_api_key
-        .ok_or(anyhow!("Missing anthropic_api_key in config"))?;
-    let api_key = resolve_secret(&api_key_raw)?;
-    let client = anthropic::AnthropicClient::new(api_key);
+pub fn run_anthropic_query(
+    maybe_query: Option<String>,
+    attachments: Attachments,
+    config: Config,
+) -> anyhow::Result<()> {
+    let query = get_query(maybe_query, &attachments)?;
+    let max_tokens = config.anthropic.max_tokens;
+    let temperature = config.anthropic.temperature;
+    let client = anthropic::AnthropicClient::from_config(config)?;
     let result = client.create_chat_completion(anthropic::AnthropicRequest {
         messages: vec![llm::Message {
             role: llm::Role::User,
             content: query,
-        }],
-        max_tokens: 1024,
+        }
+        .into()],
+        max_tokens,
+        temperature,
         ..anthropic::AnthropicRequest::default()
     })?;
     println!("{}", result.content[0].text);
     Ok(())
 }
 
+pub fn count_vertex_ai_tokens(
+    maybe_query: Option<String>,
+    attachments: Attachments,
+    config: Config,
+) -> anyhow::Result<()> {
+    let query = get_query(maybe_query, &attachments)?;
+    let client = vertex_ai::VertexAiClient::from_config(config)?;
+    let request = vertex_ai::VertexAiRequest {
+        contents: vec![vertex_ai::Content {
+            role: Some(vertex_ai::Role::User),
+            parts: vec![vertex_ai::Part { text: query }],
+        }],
+        ..vertex_ai::VertexAiRequest::default()
+    };
+    let model = vertex_ai::VertexAiModel::default();
+    let count = client.count_tokens(&request, &model)?;
+    println!(
+        "{} tokens ({} limit for {})",
+        count.total_tokens,
+        model.input_token_limit(),
+        model
+    );
+    Ok(())
+}
+
 pub fn run_vertex_ai_query(
     maybe_query: Option<String>,
     enable_search: bool,
+    attachments: Attachments,
     config: Config,
 ) -> anyhow::Result<()> {
-    let query = llm::get_query(maybe_query)?;
+    let query = get_query(maybe_query, &attachments)?;
     let client = vertex_ai::VertexAiClient::from_config(config)?;
     let mut request = vertex_ai::VertexAiRequest {
         contents: vec![vertex_ai::Content {
@@ -319,80 +1178,632 @@ pub fn run_vertex_ai_query(
     Ok(())
 }
 
-fn number_to_superscript(number: &u8) -> String {
-    const SUPERSCRIPT_DIGITS: [&str; 10] = ["⁰", "¹", "²", "³", "⁴", "⁵", "⁶", "⁷", "⁸", "⁹"];
-    number
-        .to_string()
-        .chars()
-        .map(|c| SUPERSCRIPT_DIGITS[c.to_digit(10).unwrap() as usize])
-        .collect()
-}
-
-fn format_citation_indices(indices: &[u8]) -> String {
-    indices
-        .iter()
-        .map(number_to_superscript)
-        .collect::<Vec<String>>()
-        .join("˒")
+/// How `wkfl web-chat` should search and where the answer goes
+pub struct WebChatOptions {
+    pub model_type: llm::ModelType,
+    pub model: Option<String>,
+    pub model_provider: Option<WebChatProvider>,
+    /// How to mark citations, the default depends on where they are going
+    pub citations: Option<llm::CitationStyle>,
+    /// Markdown file to also write the answer to
+    pub out: Option<PathBuf>,
+    pub attachments: Attachments,
 }
 
 pub fn run_web_chat(
     maybe_query: Option<String>,
-    model_type: llm::ModelType,
-    model_provider: Option<WebChatProvider>,
+    options: WebChatOptions,
     config: Config,
 ) -> anyhow::Result<()> {
-    let query = llm::get_query(maybe_query)?;
+    let WebChatOptions {
+        model_type,
+        model,
+        model_provider,
+        citations: maybe_citations,
+        out: maybe_out,
+        attachments,
+    } = options;
+    let query = get_query(maybe_query, &attachments)?;
     let client_provider = match model_provider {
         Some(provider) => provider,
         None => config
             .get_web_chat_provider()
-            .expect("No provider configured that supports web chat"),
+            .ok_or(ConfigError::NoProvider("web chat".to_string()))?,
     };
-    let client = client_provider.create_client(config)?;
-    let result =
-        client.create_grounded_chat_completion(llm::GroundedChatRequest { query, model_type })?;
-
-    let mut last_end = 0;
-    for support in result.citations.supports.iter() {
-        let str_to_print = result.message.content[last_end..support.end_index].to_string();
-        print!(
-            "{}{}",
-            str_to_print,
-            format_citation_indices(&support.source_indices)
-        );
-        last_end = support.end_index;
+    let cache = LlmCache::from_config(&config)?;
+    let mut client = client_provider.create_client(config)?;
+    if let Some(cache) = cache {
+        client = Box::new(CachedGroundedChat::new(
+            client,
+            cache,
+            format!("{:?}", client_provider),
+        ));
     }
-    if last_end != result.message.content.len() {
-        let str_to_print = result.message.content[last_end..].to_string();
-        print!("{}", str_to_print);
+    let result = progress::with_spinner("Searching the web", || {
+        client.create_grounded_chat_completion(llm::GroundedChatRequest {
+            query,
+            model_type,
+            model,
+        })
+    })?;
+
+    match maybe_citations.unwrap_or_default() {
+        llm::CitationStyle::Inline => {
+            println!("{}\n", result.annotated_answer(llm::CitationStyle::Inline));
+            // Hyperlinked titles read better in the terminal than markdown
+            for (i, source) in result.citations.sources.iter().enumerate() {
+                print!(
+                    " {} {}",
+                    llm::superscript_number(i + 1),
+                    Link::new(&source.title, &source.uri)
+                );
+            }
+            println!();
+        }
+        style => print!("{}", result.render(style)),
     }
-    println!("\n");
 
-    for citation in result.citations.sources.iter() {
-        print!(" {:}", Link::new(&citation.title, &citation.uri));
+    if let Some(out) = maybe_out {
+        // Footnotes unless asked otherwise, since the file is likely
+        // headed for notes
+        let style = maybe_citations.unwrap_or(llm::CitationStyle::Footnotes);
+        fs::write(&out, result.render(style))?;
+        info!("Wrote answer to {}", out.display());
     }
-    println!();
 
     Ok(())
 }
 
-pub fn run_chat(
-    maybe_query: Option<String>,
-    model_type: llm::ModelType,
+/// Chat client for the provider (or the configured default), wrapped in
+/// the cache if it is enabled
+fn create_chat_client(
     model_provider: Option<ChatProvider>,
     config: Config,
-) -> anyhow::Result<()> {
-    let query = llm::get_query(maybe_query)?;
+) -> anyhow::Result<Box<dyn llm::Chat>> {
     let client_provider = match model_provider {
         Some(provider) => provider,
         None => config
             .get_chat_provider()
-            .expect("No provider configured that supports web chat"),
+            .ok_or(ConfigError::NoProvider("chat".to_string()))?,
+    };
+    let cache = LlmCache::from_config(&config)?;
+    let mut client = client_provider.create_client(config)?;
+    if let Some(cache) = cache {
+        client = Box::new(CachedChat::new(
+            client,
+            cache,
+            format!("{:?}", client_provider),
+        ));
+    }
+    Ok(client)
+}
+
+/// Picks up a saved conversation. An empty id means the latest one.
+fn get_conversation(
+    maybe_continue_id: Option<String>,
+    config: &Config,
+) -> anyhow::Result<Conversation> {
+    match maybe_continue_id.as_deref() {
+        None => Ok(Conversation::new(config.prompts.system.clone())),
+        Some("") => Conversation::latest()?.ok_or(anyhow::anyhow!("No saved chats to continue")),
+        Some(id) => Conversation::load(id),
+    }
+}
+
+/// How `wkfl chat` should ask and what to do with the answer
+pub struct ChatOptions {
+    pub model_type: llm::ModelType,
+    pub model: Option<String>,
+    pub model_provider: Option<ChatProvider>,
+    /// Saved chat to continue, empty for the latest one
+    pub continue_id: Option<String>,
+    /// Prompt template to render the query into
+    pub template: Option<String>,
+    /// Also append the answer to today's daily note
+    pub save_note: bool,
+    /// File with a JSON schema the answer has to match
+    pub json_schema: Option<PathBuf>,
+    pub attachments: Attachments,
+}
+
+pub fn run_chat(
+    maybe_query: Option<String>,
+    options: ChatOptions,
+    config: Config,
+) -> anyhow::Result<()> {
+    let ChatOptions {
+        model_type,
+        model,
+        model_provider,
+        continue_id: maybe_continue_id,
+        template: maybe_template,
+        save_note,
+        json_schema: maybe_json_schema,
+        attachments,
+    } = options;
+    let response_schema = maybe_json_schema
+        .map(|path| -> anyhow::Result<serde_json::Value> {
+            let schema = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&schema)
+                .with_context(|| format!("{} isn't valid JSON", path.display()))
+        })
+        .transpose()?;
+    let query = match maybe_template {
+        Some(name) => {
+            let template = templates::load_template(&name, &config.prompts)?;
+            attachments.add_to(templates::render_template(
+                &template,
+                maybe_query.as_deref(),
+            )?)?
+        }
+        None => get_query(maybe_query, &attachments)?,
     };
-    let client = client_provider.create_client(config)?;
-    let result = client.create_message(llm::ChatRequest { query, model_type })?;
+    let mut conversation = get_conversation(maybe_continue_id, &config)?;
+    let maybe_notes_dir = if save_note {
+        Some(config.notes_directory_path()?)
+    } else {
+        None
+    };
+    let client = create_chat_client(model_provider, config)?;
+    let result = progress::with_spinner("Waiting for an answer", || {
+        client.create_message(llm::ChatRequest {
+            query: query.clone(),
+            model_type,
+            model,
+            history: conversation.messages.clone(),
+            system: conversation.system.clone(),
+            // A continued chat is likely to be continued again
+            cache_prompt: !conversation.messages.is_empty(),
+            response_schema: response_schema.clone(),
+        })
+    })?;
+
+    match &response_schema {
+        Some(schema) => {
+            let answer = llm::parse_structured(schema, &result.message.content)?;
+            println!("{}", serde_json::to_string_pretty(&answer)?);
+        }
+        None => println!("{}", result.message.content),
+    }
+    let answer = result.message.content.clone();
+    conversation.push_turn(query, result.message);
+    conversation.save()?;
+    if let Some(notes_dir) = maybe_notes_dir {
+        let today = NoteSpecifier::Daily {
+            day: DailyNoteSpecifier::Today,
+        };
+        let entry = format!(
+            "### {}\n\n{}\n\nContinue with `wkfl chat --continue={}`",
+            conversation.title,
+            answer.trim(),
+            conversation.id
+        );
+        let note_path =
+            notes::append_to_daily(&notes_dir, "Chats", &entry, &note_template_vars(&today))?;
+        info!("Saved the answer to {}", note_path.display());
+    }
+    Ok(())
+}
+
+const COMMIT_MESSAGE_PROMPT: &str = "Write a git commit message for the diff below. \
+Use a short imperative subject line under 72 characters, then a blank line and a body \
+explaining what changed and why, wrapped at 72 characters. Leave out the body for small, \
+obvious changes. Reply with only the commit message, no code fences or commentary.";
+const DIFF_TOKEN_BUDGET: u64 = 50_000;
+
+/// Suggests a commit message for the staged changes, then lets you commit
+/// with it, edit it first or give up
+pub fn suggest_commit_message(
+    model_type: llm::ModelType,
+    model: Option<String>,
+    model_provider: Option<ChatProvider>,
+    print_only: bool,
+    config: Config,
+) -> anyhow::Result<()> {
+    let repo = git::get_repository()?;
+    let mut diff = git::staged_diff(&repo)?;
+    if diff.is_empty() {
+        anyhow::bail!("Nothing is staged, `git add` some changes first");
+    }
+    if llm::estimate_tokens(&diff) > DIFF_TOKEN_BUDGET {
+        info!("Diff is too big to send whole, truncating it");
+        diff = llm::truncate_to_tokens(&diff, DIFF_TOKEN_BUDGET);
+    }
+
+    let client = create_chat_client(model_provider, config)?;
+    let result = progress::with_spinner("Writing a commit message", || {
+        client.create_message(llm::ChatRequest {
+            query: format!("```diff\n{}```", diff),
+            model_type,
+            model,
+            history: vec![],
+            system: Some(COMMIT_MESSAGE_PROMPT.to_string()),
+            cache_prompt: false,
+            response_schema: None,
+        })
+    })?;
+    let mut message = strip_code_fence(&result.message.content);
+    if print_only {
+        println!("{}", message);
+        return Ok(());
+    }
+
+    let options = [
+        "Commit",
+        "Edit subject",
+        "Edit message",
+        "Edit in $EDITOR and commit",
+        "Cancel",
+    ]
+    .map(String::from);
+    loop {
+        eprintln!("\n{}\n", message);
+        let mut command = Command::new("git");
+        command.arg("commit").arg("--message").arg(&message);
+        match select_prompt("Use this message?", &options)? {
+            "Commit" => {}
+            "Edit subject" => {
+                let (subject, body) = message.split_once('\n').unwrap_or((&message, ""));
+                let subject = editable_prompt("Subject:", subject)?;
+                message = format!("{}\n{}", subject.trim(), body)
+                    .trim_end()
+                    .to_string();
+                continue;
+            }
+            "Edit message" => {
+                message = multiline_prompt("Message:", &message)?.trim().to_string();
+                continue;
+            }
+            "Edit in $EDITOR and commit" => {
+                command.arg("--edit");
+            }
+            _ => return Ok(()),
+        }
+        let status = command.status()?;
+        if !status.success() {
+            anyhow::bail!("git commit failed with {}", status);
+        }
+        return Ok(());
+    }
+}
+
+const PR_DESCRIPTION_PROMPT: &str = "Write a pull request title and description for the \
+branch below. Reply with the title on the first line, under 72 characters, then a blank line \
+and a markdown body with a short summary of what changed and why, followed by anything \
+reviewers should look at closely. Refer to the ticket if one is given. Reply with only the \
+title and body, no code fences or commentary.";
+
+/// Suggests a PR title and body from the branch's commits and diff
+/// against the default branch, printed as markdown
+pub fn suggest_pr_description(
+    model_type: llm::ModelType,
+    model: Option<String>,
+    model_provider: Option<ChatProvider>,
+    config: Config,
+) -> anyhow::Result<()> {
+    let repo = git::get_repository()?;
+    let branch = git::get_current_branch_name(&repo)?;
+    let changes = git::branch_changes(&repo)?;
+    if changes.commit_messages.is_empty() {
+        anyhow::bail!(
+            "{} has no commits that aren't on the default branch",
+            branch
+        );
+    }
+    let mut diff = changes.diff;
+    if llm::estimate_tokens(&diff) > DIFF_TOKEN_BUDGET {
+        info!("Diff is too big to send whole, truncating it");
+        diff = llm::truncate_to_tokens(&diff, DIFF_TOKEN_BUDGET);
+    }
+
+    let mut query = format!("Branch: {}\n", branch);
+    if let Some(ticket) = utils::extract_ticket_from_branch(&branch) {
+        query.push_str(&format!("Ticket: {}\n", ticket));
+    }
+    query.push_str("\nCommits:\n");
+    for message in &changes.commit_messages {
+        query.push_str(&format!("- {}\n", message.trim().replace('\n', "\n  ")));
+    }
+    query.push_str(&format!("\n```diff\n{}```", diff));
+
+    let client = create_chat_client(model_provider, config)?;
+    let result = progress::with_spinner("Writing a PR description", || {
+        client.create_message(llm::ChatRequest {
+            query,
+            model_type,
+            model,
+            history: vec![],
+            system: Some(PR_DESCRIPTION_PROMPT.to_string()),
+            cache_prompt: false,
+            response_schema: None,
+        })
+    })?;
+    let description = strip_code_fence(&result.message.content);
+    let (title, body) = description.split_once('\n').unwrap_or((&description, ""));
+    println!(
+        "# {}\n\n{}",
+        title.trim_start_matches('#').trim(),
+        body.trim()
+    );
+    Ok(())
+}
+
+const REVIEW_PROMPT: &str = "Review this diff like a careful senior engineer. Each line \
+is numbered with its line in the new file. Point out bugs, risky changes and confusing code, \
+not style nits. Reply with one finding per line formatted as `path:line: comment`, using the \
+numbered lines, or `No issues` if there's nothing worth raising.";
+const REVIEW_CHUNK_TOKEN_BUDGET: u64 = 20_000;
+
+/// Reviews the branch's diff against the default branch in chunks of
+/// whole files, printing findings grouped by file
+pub fn review_branch(
+    model_type: llm::ModelType,
+    model: Option<String>,
+    model_provider: Option<ChatProvider>,
+    config: Config,
+) -> anyhow::Result<()> {
+    let repo = git::get_repository()?;
+    let changes = git::branch_changes(&repo)?;
+    if changes.diff.is_empty() {
+        anyhow::bail!("No changes against the default branch to review");
+    }
+    let files = review::split_by_file(&changes.diff)
+        .iter()
+        .map(|file| review::number_lines(file))
+        .collect();
+    let chunks = review::chunk_files(files, REVIEW_CHUNK_TOKEN_BUDGET);
+
+    let client = create_chat_client(model_provider, config)?;
+    let mut findings = BTreeMap::new();
+    let mut progress = ProgressBar::new("Reviewing", chunks.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        progress.next(&format!("chunk {}", i + 1));
+        let result = client.create_message(llm::ChatRequest {
+            query: format!("```\n{}```", chunk),
+            model_type: model_type.clone(),
+            model: model.clone(),
+            history: vec![],
+            system: Some(REVIEW_PROMPT.to_string()),
+            cache_prompt: false,
+            response_schema: None,
+        })?;
+        review::parse_findings(&result.message.content, &mut findings);
+    }
+    drop(progress);
+
+    if findings.is_empty() {
+        println!("No issues found");
+        return Ok(());
+    }
+    for (path, file_findings) in findings {
+        println!("{}", path);
+        for (line_number, comment) in file_findings {
+            println!("  {:>5}: {}", line_number, comment);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Models sometimes wrap the whole answer in a code fence despite being
+/// asked not to
+fn strip_code_fence(text: &str) -> String {
+    let text = text.trim();
+    let Some(inner) = text.strip_prefix("```") else {
+        return text.to_string();
+    };
+    let inner = inner.split_once('\n').map_or("", |(_, rest)| rest);
+    inner.trim_end().trim_end_matches("```").trim().to_string()
+}
+
+/// Applies a change suggested by an LLM, read from a file, stdin or else
+/// the last answer in the latest chat. It's checked against the working
+/// tree and previewed before anything is written.
+pub fn apply_suggestion(maybe_path: Option<PathBuf>, skip_confirm: bool) -> anyhow::Result<()> {
+    let mut stdin = io::stdin();
+    let text = match maybe_path {
+        Some(path) => fs::read_to_string(path)?,
+        None if !stdin.is_terminal() => {
+            let mut text = String::new();
+            stdin.read_to_string(&mut text)?;
+            text
+        }
+        None => Conversation::latest()?
+            .and_then(|conversation| {
+                conversation
+                    .messages
+                    .into_iter()
+                    .rev()
+                    .find(|message| matches!(message.role, llm::Role::Assistant))
+            })
+            .map(|message| message.content)
+            .ok_or(anyhow::anyhow!("No saved chats to take a suggestion from"))?,
+    };
+
+    let repo = git::get_repository()?;
+    let root = repo
+        .workdir()
+        .ok_or(anyhow::anyhow!("Can't apply suggestions in a bare repo"))?;
+    match suggestions::parse_suggestion(&text)? {
+        Suggestion::Patch(patch) => {
+            git_apply(root, &patch, true)?;
+            eprint!("{}", suggestions::colorize_diff(&patch));
+            if !skip_confirm && !boolean_prompt("Apply?", true)? {
+                return Ok(());
+            }
+            git_apply(root, &patch, false)?;
+        }
+        Suggestion::Edits(edits) => {
+            let changes = suggestions::preview_edits(&edits, root)?;
+            for change in &changes {
+                let diff = git::diff_contents(&change.path, &change.old, &change.new)?;
+                eprint!("{}", suggestions::colorize_diff(&diff));
+            }
+            if !skip_confirm && !boolean_prompt("Apply?", true)? {
+                return Ok(());
+            }
+            for change in changes {
+                let path = root.join(&change.path);
+                fs::create_dir_all(path.parent().expect("Files are in a directory"))?;
+                fs::write(path, change.new)?;
+            }
+        }
+    }
+    info!("Applied the suggestion");
+    Ok(())
+}
+
+fn git_apply(root: &Path, patch: &str, check_only: bool) -> anyhow::Result<()> {
+    let mut command = Command::new("git");
+    command.arg("apply");
+    if check_only {
+        command.arg("--check");
+    }
+    let mut child = command
+        .current_dir(root)
+        .stdin(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(patch.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Suggested patch doesn't apply: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+const CHAT_REPL_HELP: &str = "Commands:
+  /reset            Start a new conversation
+  /model <type|id>  Switch model type (small, large, thinking) or use a model ID
+  /system [prompt]  Set the system prompt, or clear it if empty
+  /save <file>      Save the conversation as markdown
+  /edit             Write the next message over multiple lines
+  /quit             Exit";
+
+/// Multi-turn chat, sending the whole conversation with each message
+pub fn run_interactive_chat(
+    mut model_type: llm::ModelType,
+    mut model: Option<String>,
+    model_provider: Option<ChatProvider>,
+    maybe_continue_id: Option<String>,
+    config: Config,
+) -> anyhow::Result<()> {
+    let mut conversation = get_conversation(maybe_continue_id, &config)?;
+    let client = create_chat_client(model_provider, config)?;
+    if !conversation.messages.is_empty() {
+        eprintln!(
+            "Continuing \"{}\" ({})",
+            conversation.title, conversation.id
+        );
+    }
+    eprintln!("Type /help for commands");
+    loop {
+        let mut input = private_prompt(">")?;
+        if input.trim() == "/edit" {
+            input = multiline_prompt("Message:", "")?;
+        }
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
+        if let Some(command) = input.strip_prefix('/') {
+            let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+            let arg = arg.trim();
+            match name {
+                "reset" => {
+                    conversation = Conversation::new(conversation.system.take());
+                    eprintln!("Started a new conversation");
+                }
+                "model" if !arg.is_empty() => match ModelTypeArg::from_str(arg, true) {
+                    Ok(new_model_type) => {
+                        model_type = new_model_type.into();
+                        model = None;
+                        eprintln!("Using {:?} model", model_type);
+                    }
+                    Err(_) => {
+                        model = Some(arg.to_string());
+                        eprintln!("Using {}", arg);
+                    }
+                },
+                "system" => {
+                    conversation.system = (!arg.is_empty()).then(|| arg.to_string());
+                    eprintln!(
+                        "System prompt {}",
+                        if arg.is_empty() { "cleared" } else { "set" }
+                    );
+                }
+                "save" if !arg.is_empty() => match fs::write(arg, conversation.to_markdown()) {
+                    Ok(()) => eprintln!("Saved conversation to {}", arg),
+                    Err(err) => eprintln!("Failed to save conversation to {}: {}", arg, err),
+                },
+                "quit" | "exit" => break,
+                _ => eprintln!("{}", CHAT_REPL_HELP),
+            }
+            continue;
+        }
+
+        let response = progress::with_spinner("Waiting for an answer", || {
+            client.create_message(llm::ChatRequest {
+                query: input.to_string(),
+                model_type: model_type.clone(),
+                model: model.clone(),
+                history: conversation.messages.clone(),
+                system: conversation.system.clone(),
+                cache_prompt: true,
+                response_schema: None,
+            })
+        });
+        // The question isn't added to the conversation, so it can just be
+        // asked again
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                eprintln!("Failed to get an answer: {}", err);
+                continue;
+            }
+        };
+        println!("{}\n", response.message.content);
+        conversation.push_turn(input.to_string(), response.message);
+        if let Err(err) = conversation.save() {
+            eprintln!("Failed to save the chat: {}", err);
+        }
+    }
+    Ok(())
+}
+
+pub fn list_chat_history() -> anyhow::Result<()> {
+    for conversation in Conversation::list()? {
+        let updated = OffsetDateTime::from_unix_timestamp(conversation.updated_at)?;
+        println!(
+            "{}  {}  {:>3}  {}",
+            conversation.id,
+            updated.date(),
+            conversation.messages.len() / 2,
+            conversation.title
+        );
+    }
+    Ok(())
+}
+
+pub fn show_chat_history(maybe_id: Option<String>) -> anyhow::Result<()> {
+    let conversation = match maybe_id {
+        Some(id) => Conversation::load(&id)?,
+        None => Conversation::latest()?.ok_or(anyhow::anyhow!("No saved chats"))?,
+    };
+    print!("{}", conversation.to_markdown());
+    Ok(())
+}
 
-    println!("{}", result.message.content);
+pub fn delete_chat_history(id: &str) -> anyhow::Result<()> {
+    Conversation::delete(id)?;
+    info!("Deleted chat {}", id);
     Ok(())
 }
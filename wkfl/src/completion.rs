@@ -0,0 +1,167 @@
+use std::io::Write;
+
+use clap::ValueEnum;
+use clap_complete::Shell;
+
+use wkfl_core::config::{get_repo_config, Config};
+use wkfl_core::git;
+use wkfl_core::notes;
+
+use crate::repositories::get_repositories_in_directory;
+
+#[derive(Clone, Debug, ValueEnum)]
+pub enum CompletionKind {
+    Repos,
+    Branches,
+    Topics,
+    People,
+    CommandGroups,
+    Snippets,
+}
+
+/// Subcommand paths whose positional argument is completed dynamically
+const DYNAMIC_ARGS: &[(&[&str], CompletionKind)] = &[
+    (&["repo"], CompletionKind::Repos),
+    (&["end"], CompletionKind::Branches),
+    (&["run"], CompletionKind::CommandGroups),
+    (&["notes", "topic"], CompletionKind::Topics),
+    (&["notes", "person"], CompletionKind::People),
+    (&["snippets", "copy"], CompletionKind::Snippets),
+    (&["snippets", "run"], CompletionKind::Snippets),
+];
+
+pub fn get_candidates(kind: &CompletionKind, config: &Config) -> anyhow::Result<Vec<String>> {
+    match kind {
+        CompletionKind::Repos => {
+            let base_repo_path = config.repositories_directory_path()?;
+            let repo_paths = get_repositories_in_directory(&base_repo_path)?;
+            Ok(repo_paths
+                .iter()
+                .filter_map(|path| path.strip_prefix(&base_repo_path).ok())
+                .map(|path| path.to_string_lossy().to_string())
+                .collect())
+        }
+        CompletionKind::Branches => {
+            let repo = git::get_repository()?;
+            git::get_local_branches(&repo)
+        }
+        CompletionKind::CommandGroups => {
+            let repo = git::get_repository()?;
+            let repo_config = get_repo_config(git::determine_repo_root_dir(&repo))?;
+            Ok(repo_config.commands.into_keys().collect())
+        }
+        CompletionKind::Topics => {
+            notes::list_note_names(&config.notes_directory_path()?, notes::TOPICS_DIR)
+        }
+        CompletionKind::People => {
+            notes::list_note_names(&config.notes_directory_path()?, notes::PEOPLE_DIR)
+        }
+        CompletionKind::Snippets => {
+            notes::list_note_names(&config.notes_directory_path()?, notes::SNIPPETS_DIR)
+        }
+    }
+}
+
+fn kind_name(kind: &CompletionKind) -> String {
+    kind.to_possible_value()
+        .expect("No variants are skipped")
+        .get_name()
+        .to_string()
+}
+
+/// Case arms shared by bash and zsh mapping the words typed so far to the
+/// kind of completion needed
+fn case_arms() -> String {
+    DYNAMIC_ARGS
+        .iter()
+        .map(|(path, kind)| {
+            format!(
+                "        \"{}\") kind=\"{}\" ;;\n",
+                path.join(" "),
+                kind_name(kind)
+            )
+        })
+        .collect()
+}
+
+fn bash_dynamic_completions(bin_name: &str) -> String {
+    let cases = case_arms();
+    format!(
+        r#"
+_{bin}_dynamic() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local kind=""
+    case "${{COMP_WORDS[*]:1:COMP_CWORD-1}}" in
+{cases}    esac
+    if [[ -n "${{kind}}" && "${{cur}}" != -* ]]; then
+        COMPREPLY=( $(compgen -W "$(command {bin} __complete "${{kind}}" 2>/dev/null)" -- "${{cur}}") )
+        return 0
+    fi
+    _{bin} "$@"
+}}
+
+complete -F _{bin}_dynamic -o bashdefault -o default {bin}
+"#,
+        bin = bin_name,
+        cases = cases,
+    )
+}
+
+fn zsh_dynamic_completions(bin_name: &str) -> String {
+    let cases = case_arms();
+    format!(
+        r#"
+_{bin}_dynamic() {{
+    local kind=""
+    case "${{words[2,CURRENT-1]}}" in
+{cases}    esac
+    if [[ -n "${{kind}}" && "${{words[CURRENT]}}" != -* ]]; then
+        compadd -- ${{(f)"$(command {bin} __complete "${{kind}}" 2>/dev/null)"}}
+        return
+    fi
+    _{bin} "$@"
+}}
+
+compdef _{bin}_dynamic {bin}
+"#,
+        bin = bin_name,
+        cases = cases,
+    )
+}
+
+fn fish_dynamic_completions(bin_name: &str) -> String {
+    DYNAMIC_ARGS
+        .iter()
+        .map(|(path, kind)| {
+            let (first, rest) = path.split_first().expect("Paths are never empty");
+            let mut condition = format!("__fish_{}_using_subcommand {}", bin_name, first);
+            for subcommand in rest {
+                condition.push_str(&format!("; and __fish_seen_subcommand_from {}", subcommand));
+            }
+            format!(
+                "complete -c {bin} -n \"{condition}\" -f -a \"(command {bin} __complete {kind} 2>/dev/null)\"\n",
+                bin = bin_name,
+                condition = condition,
+                kind = kind_name(kind),
+            )
+        })
+        .collect()
+}
+
+/// Writes the shell specific glue that calls `__complete` for arguments
+/// that can't be completed statically. Needs to be written after the
+/// script generated by clap_complete since it wraps/extends it.
+pub fn write_dynamic_completions(
+    shell: Shell,
+    bin_name: &str,
+    out: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let script = match shell {
+        Shell::Bash => bash_dynamic_completions(bin_name),
+        Shell::Zsh => zsh_dynamic_completions(bin_name),
+        Shell::Fish => fish_dynamic_completions(bin_name),
+        _ => return Ok(()),
+    };
+    out.write_all(script.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,391 @@
+use std::io::Write;
+
+use wkfl_core::config::Config;
+
+/// Config an example needs to be useful, examples for features that
+/// aren't set up are hidden
+enum Requires {
+    Nothing,
+    ChatProvider,
+    WebChatProvider,
+    Anthropic,
+    Perplexity,
+    VertexAi,
+    Embeddings,
+}
+
+impl Requires {
+    fn is_met(&self, config: &Config) -> bool {
+        match self {
+            Requires::Nothing => true,
+            Requires::ChatProvider => config.get_chat_provider().is_some(),
+            Requires::WebChatProvider => config.get_web_chat_provider().is_some(),
+            Requires::Anthropic => config.anthropic_api_key.is_some(),
+            Requires::Perplexity => config.perplexity_api_key.is_some(),
+            Requires::VertexAi => config.vertex_ai.is_some(),
+            Requires::Embeddings => config.embeddings.is_some(),
+        }
+    }
+}
+
+struct Example {
+    /// Subcommand path, eg. "notes topic"
+    command: &'static str,
+    description: &'static str,
+    /// Args after `wkfl`
+    args: &'static str,
+    requires: Requires,
+}
+
+const EXAMPLES: &[Example] = &[
+    Example {
+        command: "start",
+        description: "Start work on a new branch (or worktree), prompting for the name and ticket",
+        args: "start",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "end",
+        description: "Finish with a branch and switch back to the default one",
+        args: "end agent/ABC-12_feature",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "commit",
+        description: "Commit crediting the people you paired with",
+        args: "commit --pair alex --pair sam -- -m \"Fix flaky test\"",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "commit",
+        description: "Pick who to credit from recent pairs",
+        args: "commit --recent",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "run",
+        description: "Run the repo's test command group, after the groups it depends on",
+        args: "run test",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "repo",
+        description: "Pick a repo to cd into",
+        args: "repo",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "prompt select",
+        description: "Pick from a long list with a fullscreen picker",
+        args: "prompt select \"Branch?\" --fullscreen",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "prompt multi-select",
+        description: "Pick several lines from stdin, as a JSON array",
+        args: "prompt multi-select \"Files to stage:\" --json",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "prompt input",
+        description: "Ask for a line of text, starting from a suggestion",
+        args: "prompt input \"Branch name:\" --initial agent/",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "prompt secret",
+        description: "Ask for a token in a script without echoing it",
+        args: "prompt secret \"GitHub token:\"",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "repos outdated",
+        description: "List outdated Rust dependencies in one repo",
+        args: "repos outdated --language rust --repo wkfl",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes today",
+        description: "Open today's daily note",
+        args: "notes today",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes topic",
+        description: "Open (or create) a topic note",
+        args: "notes topic release-process",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes meeting",
+        description: "Start a meeting note linked to the attendees' person notes",
+        args: "notes meeting \"Sprint planning\" --with alice,bob",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes open",
+        description: "Open last Friday's daily note",
+        args: "notes open fri",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes list",
+        description: "List the 10 most recently changed topic notes",
+        args: "notes list --topic --recent 10",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes recent",
+        description: "Pick a recently changed note to open",
+        args: "notes recent",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes backlinks",
+        description: "Show the notes with a [[wiki link]] to a topic",
+        args: "notes backlinks release-process",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes tagged",
+        description: "Pick a note tagged #project-x to open",
+        args: "notes tagged project-x --open",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes log",
+        description: "Log what you did to today's note without opening it",
+        args: "notes log \"deployed v1.2 to staging\"",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes rollover",
+        description: "Carry unchecked tasks from the last daily note over to today's",
+        args: "notes rollover",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes search",
+        description: "Search notes, including archived ones",
+        args: "notes search \"on call\" --archived",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "notes search",
+        description: "Find the notes most related to a question, using embeddings",
+        args: "notes search --semantic \"how did we decide on the retry policy\"",
+        requires: Requires::Embeddings,
+    },
+    Example {
+        command: "notes archive",
+        description: "Move daily notes older than 12 weeks into yearly archives and compress them",
+        args: "notes archive --older-than 12w --compress",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "snippets add",
+        description: "Save a snippet from stdin with tags",
+        args: "snippets add prune-branches --tag git",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "snippets run",
+        description: "Run a snippet, prompting for its placeholders",
+        args: "snippets run prune-branches",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "chat",
+        description: "Ask a question and save the chat",
+        args: "chat \"How do I undo a rebase?\"",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "chat",
+        description: "Review a diff using a prompt template",
+        args: "chat --stdin --template review",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "chat",
+        description: "Ask about files",
+        args: "chat -f src/main.rs -f src/actions.rs \"Where are errors reported?\"",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "chat",
+        description: "Ask a question and keep the answer in today's daily note",
+        args: "chat --save-note \"Why is the build cache invalidated?\"",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "chat",
+        description: "Continue the latest chat interactively",
+        args: "chat --interactive --continue",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "chat",
+        description: "Try a model without adding it to [models] in the config",
+        args: "chat -p anthropic --model claude-3-7-sonnet-latest \"Explain this error\"",
+        requires: Requires::Anthropic,
+    },
+    Example {
+        command: "chat",
+        description: "Get an answer as JSON matching a schema, for scripts",
+        args: "chat --json-schema todos.schema.json --file notes.md \"List the todos\"",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "chat history show",
+        description: "Print the latest chat as markdown",
+        args: "chat history show",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "ai commit-message",
+        description: "Suggest a commit message for the staged changes and commit with it",
+        args: "ai commit-message",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "ai pr-description",
+        description: "Draft a PR description for the current branch",
+        args: "ai pr-description --model-type large",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "ai review",
+        description: "Review the current branch before opening a PR",
+        args: "ai review --model-type thinking",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "apply-suggestion",
+        description: "Preview and apply the change suggested in the latest chat",
+        args: "apply-suggestion",
+        requires: Requires::ChatProvider,
+    },
+    Example {
+        command: "apply-suggestion",
+        description: "Apply a saved patch without asking",
+        args: "apply-suggestion fix.patch --yes",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "web-chat",
+        description: "Search the web and save the answer with citations",
+        args: "web-chat \"Latest stable Rust version\" --out answer.md",
+        requires: Requires::WebChatProvider,
+    },
+    Example {
+        command: "llm anthropic",
+        description: "Send a one off query straight to Anthropic",
+        args: "llm anthropic \"Explain lifetimes in one paragraph\"",
+        requires: Requires::Anthropic,
+    },
+    Example {
+        command: "llm perplexity",
+        description: "Send a one off query straight to Perplexity",
+        args: "llm perplexity \"What changed in Rust 2024?\"",
+        requires: Requires::Perplexity,
+    },
+    Example {
+        command: "llm vertex-ai count-tokens",
+        description: "Check a file fits in Gemini's context",
+        args: "llm vertex-ai count-tokens --file notes.md",
+        requires: Requires::VertexAi,
+    },
+    Example {
+        command: "llm usage",
+        description: "Show tokens used and their estimated cost per day this week",
+        args: "llm usage --days 7 --daily",
+        requires: Requires::Nothing,
+    },
+    Example {
+        command: "completion",
+        description: "Install zsh completions",
+        args: "completion zsh > ~/.zfunc/_wkfl",
+        requires: Requires::Nothing,
+    },
+];
+
+/// Prints examples for the command and its subcommands, or every command
+/// if `command` is empty
+pub fn print_examples(
+    command: &[String],
+    config: &Config,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let command = command.join(" ");
+    let matching: Vec<&Example> = EXAMPLES
+        .iter()
+        .filter(|example| {
+            command.is_empty()
+                || example.command == command
+                || example.command.starts_with(&format!("{} ", command))
+        })
+        .collect();
+    let examples: Vec<&Example> = matching
+        .iter()
+        .copied()
+        .filter(|example| example.requires.is_met(config))
+        .collect();
+    if matching.is_empty() {
+        writeln!(out, "No examples for `wkfl {}`", command)?;
+        return Ok(());
+    }
+    if examples.is_empty() {
+        writeln!(
+            out,
+            "`wkfl {}` needs config that isn't set up, see `wkfl {} --help`",
+            command, command
+        )?;
+        return Ok(());
+    }
+    for example in examples {
+        writeln!(out, "# {}\nwkfl {}\n", example.description, example.args)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::EXAMPLES;
+    use crate::Cli;
+
+    /// Splits on spaces, keeping double quoted strings together
+    fn split_args(args: &str) -> Vec<String> {
+        let mut words = vec![];
+        let mut word = String::new();
+        let mut quoted = false;
+        for c in args.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                ' ' if !quoted => words.push(std::mem::take(&mut word)),
+                _ => word.push(c),
+            }
+        }
+        words.push(word);
+        words
+    }
+
+    #[test]
+    fn test_examples_parse() {
+        for example in EXAMPLES {
+            let args = example.args.split(" > ").next().unwrap();
+            let words = split_args(args);
+            assert!(
+                args.starts_with(example.command),
+                "`{}` isn't a `{}` example",
+                example.args,
+                example.command
+            );
+            if let Err(err) = Cli::try_parse_from(["wkfl".to_string()].into_iter().chain(words)) {
+                panic!("`wkfl {}` doesn't parse: {}", example.args, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    fs,
+    io::{self, IsTerminal, Read},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Args, ValueEnum, ValueHint};
+
+use wkfl_core::config::{ChatProvider, WebChatProvider};
+use wkfl_core::llm::{self, CitationStyle, ModelType};
+
+// The clap side of wkfl-core's LLM types, which don't depend on clap
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum ModelTypeArg {
+    #[default]
+    Small,
+    Large,
+    Thinking,
+}
+
+impl From<ModelTypeArg> for ModelType {
+    fn from(arg: ModelTypeArg) -> Self {
+        match arg {
+            ModelTypeArg::Small => ModelType::Small,
+            ModelTypeArg::Large => ModelType::Large,
+            ModelTypeArg::Thinking => ModelType::Thinking,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ChatProviderArg {
+    VertexAI,
+    Anthropic,
+}
+
+impl From<ChatProviderArg> for ChatProvider {
+    fn from(arg: ChatProviderArg) -> Self {
+        match arg {
+            ChatProviderArg::VertexAI => ChatProvider::VertexAI,
+            ChatProviderArg::Anthropic => ChatProvider::Anthropic,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum WebChatProviderArg {
+    VertexAI,
+    Perplexity,
+}
+
+impl From<WebChatProviderArg> for WebChatProvider {
+    fn from(arg: WebChatProviderArg) -> Self {
+        match arg {
+            WebChatProviderArg::VertexAI => WebChatProvider::VertexAI,
+            WebChatProviderArg::Perplexity => WebChatProvider::Perplexity,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CitationStyleArg {
+    /// Markdown footnotes (`[^1]`), for pasting into notes
+    Footnotes,
+    /// Superscript numbers (`¹˒²`), for reading in the terminal
+    Inline,
+    /// Just the answer, without sources
+    None,
+}
+
+impl From<CitationStyleArg> for CitationStyle {
+    fn from(arg: CitationStyleArg) -> Self {
+        match arg {
+            CitationStyleArg::Footnotes => CitationStyle::Footnotes,
+            CitationStyleArg::Inline => CitationStyle::Inline,
+            CitationStyleArg::None => CitationStyle::None,
+        }
+    }
+}
+
+// Piped input and files to add to the prompt. Not a doc comment, clap
+// would use it as the help for every command this is flattened into.
+#[derive(Args, Debug, Default)]
+pub struct Attachments {
+    /// Add piped input to the prompt, eg. `git diff | wkfl chat --stdin "review this"`
+    #[arg(long)]
+    pub stdin: bool,
+    /// Add a file to the prompt, can be repeated
+    #[arg(short = 'f', long = "file", value_hint = ValueHint::FilePath)]
+    pub files: Vec<PathBuf>,
+}
+
+impl Attachments {
+    /// Appends each attachment to the query in a fenced block
+    pub fn add_to(&self, query: String) -> anyhow::Result<String> {
+        let mut attachments = vec![];
+        if self.stdin {
+            let mut stdin = io::stdin();
+            if stdin.is_terminal() {
+                anyhow::bail!("--stdin was given but nothing was piped in");
+            }
+            let mut input = String::new();
+            stdin.read_to_string(&mut input)?;
+            attachments.push(("stdin".to_string(), input));
+        }
+        for path in &self.files {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            attachments.push((path.display().to_string(), contents));
+        }
+        Ok(llm::add_attachments(query, attachments))
+    }
+}
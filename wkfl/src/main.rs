@@ -1,29 +1,50 @@
-use std::{env, error::Error, io, path::PathBuf};
+use std::{
+    env,
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+    time::Instant,
+};
 
-use clap::{CommandFactory, Parser, Subcommand, ValueHint};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueHint};
 use clap_complete::{generate, Shell};
-use config::{ChatProvider, WebChatProvider};
-use llm::ModelType;
-use notes::DailyNoteSpecifier;
+use completion::CompletionKind;
+use llm_args::{Attachments, ChatProviderArg, CitationStyleArg, ModelTypeArg, WebChatProviderArg};
+use wkfl_core::config;
+use wkfl_core::errors;
+use wkfl_core::llm;
+use wkfl_core::notes::{self, DailyNoteSpecifier};
 
 mod actions;
-mod config;
-mod git;
-mod llm;
-mod notes;
+mod completion;
+mod examples;
+mod llm_args;
+mod outdated;
+mod pairs;
+mod progress;
+mod prompt_history;
 mod prompts;
 mod repositories;
+mod review;
 mod shell_actions;
-mod utils;
+mod stats;
+mod suggestions;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
+#[command(after_help = "Add --examples to any command to see how it's used")]
 struct Cli {
     #[arg(short, long)]
     verbose: bool,
     #[arg(long, value_hint = ValueHint::FilePath)]
     shell_actions_file: Option<PathBuf>,
+    /// Don't use cached LLM responses even if the cache is enabled
+    #[arg(long, global = true, visible_alias = "no-cache")]
+    no_llm_cache: bool,
+    /// Show example invocations of the command instead of running it
+    #[arg(long, global = true)]
+    examples: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -31,21 +52,60 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     Start,
-    End,
+    End {
+        #[arg(value_hint = ValueHint::Other)]
+        branch: Option<String>,
+    },
+    /// Run `git commit` with Co-authored-by trailers for the people you
+    /// paired with. Extra args after `--` are passed to git.
+    Commit {
+        /// Person (note name) to credit, can be repeated
+        #[arg(short, long = "pair", value_hint = ValueHint::Other)]
+        pairs: Vec<String>,
+        /// Pick someone to credit from the people recently paired with
+        #[arg(short, long)]
+        recent: bool,
+        #[arg(last = true)]
+        git_args: Vec<String>,
+    },
+    /// Run a command group from the repo config, after its dependencies
+    Run {
+        #[arg(value_hint = ValueHint::Other)]
+        name: String,
+    },
     RepoDebug,
-    Repos,
-    Repo,
+    /// List repos in the repositories directory
+    Repos {
+        #[command(subcommand)]
+        command: Option<ReposCommands>,
+    },
+    Repo {
+        #[arg(value_hint = ValueHint::Other)]
+        name: Option<String>,
+    },
     Config,
     Clone,
+    /// Prompts for shell scripts, drawn on stderr with the answer printed
+    /// to stdout
+    Prompt {
+        #[command(subcommand)]
+        command: PromptCommands,
+    },
+    /// Same as `wkfl prompt confirm`, kept for existing scripts
+    #[command(hide = true)]
     Confirm {
         #[arg(value_hint = ValueHint::Other)]
         prompt: Option<String>,
         #[arg(short = 't', long)]
         default_true: bool,
     },
+    /// Same as `wkfl prompt select`, kept for existing scripts
+    #[command(hide = true)]
     Select {
         #[arg(value_hint = ValueHint::Other)]
         prompt: Option<String>,
+        #[command(flatten)]
+        layout: SelectLayoutArgs,
     },
     Notes {
         #[command(subcommand)]
@@ -55,24 +115,123 @@ enum Commands {
         #[command(subcommand)]
         command: LlmCommands,
     },
+    /// Features built on the configured chat provider
+    Ai {
+        #[command(subcommand)]
+        command: AiCommands,
+    },
+    Snippets {
+        #[command(subcommand)]
+        command: SnippetsCommands,
+    },
     Completion {
         language: Option<Shell>,
     },
+    /// Summarize the locally recorded command stats
+    Stats,
+    /// Apply a change an LLM suggested, as a unified diff or SEARCH/REPLACE
+    /// blocks. Read from the file, stdin or the latest chat's last answer.
+    ApplySuggestion {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: Option<PathBuf>,
+        /// Apply without asking after the preview
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Update the config file to the current format, keeping a backup
+    MigrateConfig {
+        /// Print the migrated config instead of writing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show example invocations of a command, skipping features that
+    /// aren't configured. `wkfl <command> --examples` does the same.
+    HelpExamples {
+        #[arg(value_hint = ValueHint::Other)]
+        command: Vec<String>,
+    },
+    /// Ask a question answered from a web search, with citations
     WebChat {
         #[arg(value_hint = ValueHint::Other)]
         query: Option<String>,
         #[arg(short = 'p', long, value_enum)]
-        model_provider: Option<WebChatProvider>,
+        model_provider: Option<WebChatProviderArg>,
         #[arg(short, long, value_enum, default_value_t)]
-        model_type: ModelType,
+        model_type: ModelTypeArg,
+        /// Model ID to use instead of the one for the model type
+        #[arg(long, value_hint = ValueHint::Other)]
+        model: Option<String>,
+        /// How to mark citations. Defaults to inline when printing and to
+        /// footnotes in the --out file.
+        #[arg(long, value_enum)]
+        citations: Option<CitationStyleArg>,
+        /// Also write the answer to a markdown file
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
+        #[command(flatten)]
+        attachments: Attachments,
     },
+    /// Ask the chat provider a question, or chat back and forth
+    #[command(args_conflicts_with_subcommands = true)]
     Chat {
+        #[command(subcommand)]
+        command: Option<ChatCommands>,
         #[arg(value_hint = ValueHint::Other)]
         query: Option<String>,
         #[arg(short = 'p', long, value_enum)]
-        model_provider: Option<ChatProvider>,
+        model_provider: Option<ChatProviderArg>,
         #[arg(short, long, value_enum, default_value_t)]
-        model_type: ModelType,
+        model_type: ModelTypeArg,
+        /// Model ID to use instead of the one for the model type
+        #[arg(long, value_hint = ValueHint::Other)]
+        model: Option<String>,
+        /// Keep chatting, with the conversation sent as context each turn
+        #[arg(short, long, conflicts_with_all = ["query", "stdin", "files"])]
+        interactive: bool,
+        /// Continue a saved chat, the latest one if no id is given
+        #[arg(
+            short,
+            long = "continue",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        continue_id: Option<String>,
+        /// Prompt template from ~/.config/wkfl/prompts/ or the config. The
+        /// query fills in its {query} placeholder.
+        #[arg(short, long, conflicts_with = "interactive", value_hint = ValueHint::Other)]
+        template: Option<String>,
+        /// Also add the answer to today's daily note
+        #[arg(long, conflicts_with = "interactive")]
+        save_note: bool,
+        /// Ask for an answer matching this JSON schema, and print it as
+        /// JSON once it's checked against the schema
+        #[arg(long, conflicts_with = "interactive", value_hint = ValueHint::FilePath)]
+        json_schema: Option<PathBuf>,
+        #[command(flatten)]
+        attachments: Attachments,
+    },
+}
+
+/// Hidden entrypoint used by the completion scripts to get candidates for
+/// arguments. Kept out of `Cli` so it isn't offered by the completions.
+#[derive(Parser, Debug)]
+#[command(name = "__complete")]
+struct CompleteCli {
+    #[arg(value_enum)]
+    what: CompletionKind,
+}
+
+#[derive(Subcommand, Debug)]
+enum ReposCommands {
+    /// Report outdated direct dependencies across repos
+    Outdated {
+        /// Only check these languages, can be repeated
+        #[arg(short, long = "language", value_enum)]
+        languages: Vec<outdated::Language>,
+        /// Only check this repo
+        #[arg(long, value_hint = ValueHint::Other)]
+        repo: Option<String>,
     },
 }
 
@@ -89,23 +248,300 @@ enum NotesCommands {
         #[arg(value_hint = ValueHint::Other)]
         who: Option<String>,
     },
+    /// Open today's note for a meeting, linked to each attendee's person note
+    Meeting {
+        #[arg(value_hint = ValueHint::Other)]
+        title: Option<String>,
+        /// People at the meeting, eg. alice,bob
+        #[arg(long, value_delimiter = ',', value_hint = ValueHint::Other)]
+        with: Vec<String>,
+    },
+    /// Pick any note to open, or open the daily note for a day
+    Open {
+        /// A date (2024-05-01), an offset (-3d, +1w) or a weekday (fri),
+        /// which is the latest one up to today
+        #[arg(allow_hyphen_values = true, value_hint = ValueHint::Other)]
+        day: Option<DailyNoteSpecifier>,
+    },
+    /// List notes, most recently modified first
+    List {
+        #[arg(long, group = "kind")]
+        person: bool,
+        #[arg(long, group = "kind")]
+        topic: bool,
+        #[arg(long, group = "kind")]
+        daily: bool,
+        /// Only list this many notes
+        #[arg(long, value_name = "N")]
+        recent: Option<usize>,
+    },
+    /// Pick one of the most recently modified notes to open
+    Recent {
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show the notes with a `[[wiki link]]` to a note. Opening a note also
+    /// lists them in its `## Linked from` section.
+    Backlinks {
+        /// The note's path, file name or title
+        #[arg(value_hint = ValueHint::Other)]
+        note: String,
+    },
+    /// Add a timestamped bullet to today's daily note without opening it
+    Log {
+        #[arg(value_hint = ValueHint::Other)]
+        text: String,
+    },
+    /// List the `#tags` and front matter tags in notes, most used first
+    Tags,
+    /// List the notes with a tag
+    Tagged {
+        #[arg(value_hint = ValueHint::Other)]
+        tag: String,
+        /// Pick one of them to open instead
+        #[arg(long)]
+        open: bool,
+    },
+    /// Copy unchecked tasks from the last daily note into today's, marking
+    /// them as migrated (`- [>]`) in the old note
+    Rollover,
+    /// Move old daily notes into archive/<year>/
+    Archive {
+        /// Compress each archived year into a tar.zst
+        #[arg(long)]
+        compress: bool,
+        /// Archive notes older than this, eg. 90d or 12w, instead of the
+        /// notes_archive_after_days config
+        #[arg(long, value_parser = notes::parse_days, value_hint = ValueHint::Other)]
+        older_than: Option<i64>,
+    },
+    Search {
+        #[arg(value_hint = ValueHint::Other)]
+        query: String,
+        /// Also search archived notes. Compressed archives aren't searched.
+        #[arg(long)]
+        archived: bool,
+        /// Find the notes closest in meaning to the query, using the
+        /// `[embeddings]` provider, instead of matching text
+        #[arg(long)]
+        semantic: bool,
+        /// How many notes to show for a semantic search
+        #[arg(short = 'n', long, default_value_t = 10, requires = "semantic")]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ChatCommands {
+    /// Saved chats
+    History {
+        #[command(subcommand)]
+        command: ChatHistoryCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ChatHistoryCommands {
+    List,
+    /// Print a chat, the latest one if no id is given
+    Show {
+        #[arg(value_hint = ValueHint::Other)]
+        id: Option<String>,
+    },
+    Delete {
+        #[arg(value_hint = ValueHint::Other)]
+        id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PromptCommands {
+    /// Ask a yes or no question, the answer is the exit code
+    Confirm {
+        #[arg(value_hint = ValueHint::Other)]
+        prompt: Option<String>,
+        #[arg(short = 't', long)]
+        default_true: bool,
+        /// Print true or false and always exit 0
+        #[arg(long)]
+        json: bool,
+    },
+    /// Read a line of text
+    Input {
+        #[arg(value_hint = ValueHint::Other)]
+        prompt: Option<String>,
+        /// Text to start with, eg. a suggestion to edit
+        #[arg(long, value_hint = ValueHint::Other)]
+        initial: Option<String>,
+        /// Print the answer as a JSON string
+        #[arg(long)]
+        json: bool,
+    },
+    /// Read a password or token without echoing it
+    Secret {
+        #[arg(value_hint = ValueHint::Other)]
+        prompt: Option<String>,
+        /// Print the answer as a JSON string
+        #[arg(long)]
+        json: bool,
+    },
+    /// Pick one of the lines on stdin
+    Select {
+        #[arg(value_hint = ValueHint::Other)]
+        prompt: Option<String>,
+        #[command(flatten)]
+        layout: SelectLayoutArgs,
+        /// Print the answer as a JSON string
+        #[arg(long)]
+        json: bool,
+    },
+    /// Pick any number of the lines on stdin, tab marks each one. They're
+    /// printed one per line.
+    MultiSelect {
+        #[arg(value_hint = ValueHint::Other)]
+        prompt: Option<String>,
+        #[command(flatten)]
+        layout: SelectLayoutArgs,
+        /// Print the answers as a JSON array
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+struct SelectLayoutArgs {
+    /// Options shown at once below the prompt, instead of the config's
+    /// `[select] height`
+    #[arg(long)]
+    height: Option<usize>,
+    /// Pick from a fullscreen list instead of below the prompt
+    #[arg(long, conflicts_with = "height")]
+    fullscreen: bool,
+}
+
+impl SelectLayoutArgs {
+    /// The layout asked for, None to use the config's
+    fn layout(&self) -> Option<prompts::SelectLayout> {
+        match (self.height, self.fullscreen) {
+            (_, true) => Some(prompts::SelectLayout::Fullscreen),
+            (Some(height), false) => Some(prompts::SelectLayout::Inline { height }),
+            (None, false) => None,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum SnippetsCommands {
+    /// Add a snippet. Read from stdin if piped, otherwise opened in your editor
+    Add {
+        #[arg(value_hint = ValueHint::Other)]
+        name: String,
+        /// Can be repeated
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+    },
+    List {
+        /// Only list snippets with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
+    /// Copy a snippet to the clipboard, filling in its placeholders
+    Copy {
+        #[arg(value_hint = ValueHint::Other)]
+        name: Option<String>,
+    },
+    /// Run a snippet with sh, filling in its placeholders
+    Run {
+        #[arg(value_hint = ValueHint::Other)]
+        name: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 enum LlmCommands {
+    /// Send a query straight to Anthropic
     Anthropic {
         #[arg(value_hint = ValueHint::Other)]
         query: Option<String>,
+        #[command(flatten)]
+        attachments: Attachments,
     },
+    /// Send a query straight to Perplexity
     Perplexity {
         #[arg(value_hint = ValueHint::Other)]
         query: Option<String>,
+        #[command(flatten)]
+        attachments: Attachments,
     },
+    /// Send a query straight to Vertex AI
+    #[command(args_conflicts_with_subcommands = true)]
     VertexAi {
+        #[command(subcommand)]
+        command: Option<VertexAiCommands>,
         #[arg(value_hint = ValueHint::Other)]
         query: Option<String>,
         #[arg(short, long)]
         enable_search: bool,
+        #[command(flatten)]
+        attachments: Attachments,
+    },
+    /// Tokens used and estimated cost per provider and model
+    Usage {
+        /// How many days back to include
+        #[arg(long, default_value_t = 30)]
+        days: u64,
+        /// Break the usage down by day
+        #[arg(long)]
+        daily: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AiCommands {
+    /// Suggest a commit message for the staged changes, then commit with it
+    CommitMessage {
+        #[arg(short = 'p', long, value_enum)]
+        model_provider: Option<ChatProviderArg>,
+        #[arg(short, long, value_enum, default_value_t)]
+        model_type: ModelTypeArg,
+        /// Model ID to use instead of the one for the model type
+        #[arg(long, value_hint = ValueHint::Other)]
+        model: Option<String>,
+        /// Only print the suggestion, eg. for `git commit -m "$(wkfl ai commit-message --print)"`
+        #[arg(long)]
+        print: bool,
+    },
+    /// Suggest a PR title and description for the current branch, printed
+    /// as markdown
+    PrDescription {
+        #[arg(short = 'p', long, value_enum)]
+        model_provider: Option<ChatProviderArg>,
+        #[arg(short, long, value_enum, default_value_t)]
+        model_type: ModelTypeArg,
+        /// Model ID to use instead of the one for the model type
+        #[arg(long, value_hint = ValueHint::Other)]
+        model: Option<String>,
+    },
+    /// Review the current branch's changes against the default branch
+    Review {
+        #[arg(short = 'p', long, value_enum)]
+        model_provider: Option<ChatProviderArg>,
+        #[arg(short, long, value_enum, default_value_t)]
+        model_type: ModelTypeArg,
+        /// Model ID to use instead of the one for the model type
+        #[arg(long, value_hint = ValueHint::Other)]
+        model: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum VertexAiCommands {
+    /// Count the tokens in a prompt without sending it
+    CountTokens {
+        #[arg(value_hint = ValueHint::Other)]
+        query: Option<String>,
+        #[command(flatten)]
+        attachments: Attachments,
     },
 }
 
@@ -123,39 +559,177 @@ fn setup_logging(verbose: bool) {
         if env::var("RUST_LOG").is_err() {
             log_builder.filter(None, log::LevelFilter::Info);
         }
-        log_builder.format_timestamp(None);
+        log_builder.format(|buf, record| {
+            // Logged above a spinner or progress bar, which is redrawn below
+            if progress::is_drawn() {
+                write!(buf, "\r\x1b[2K")?;
+            }
+            let level_style = buf.default_level_style(record.level());
+            writeln!(
+                buf,
+                "[{level_style}{:<5}{level_style:#} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            )
+        });
     }
     log_builder.init();
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let cli = Cli::parse();
+fn print_completion_candidates() -> anyhow::Result<()> {
+    let complete_cli = CompleteCli::parse_from(env::args().skip(1));
+    let config = config::get_config()?;
+    // Completion shouldn't print errors into the user's terminal
+    for candidate in completion::get_candidates(&complete_cli.what, &config).unwrap_or_default() {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
+
+/// The command `--examples` was given for. It's looked for in a lenient
+/// parse first, so the command's required args don't need to be given.
+fn examples_command_path() -> Option<Vec<String>> {
+    let matches = Cli::command().ignore_errors(true).try_get_matches().ok()?;
+    let mut command_path = vec![];
+    let mut matches = &matches;
+    while let Some((name, subcommand_matches)) = matches.subcommand() {
+        command_path.push(name.to_string());
+        matches = subcommand_matches;
+    }
+    matches
+        .try_get_one::<bool>("examples")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+        .then_some(command_path)
+}
+
+fn print_examples_for(command_path: &[String]) -> anyhow::Result<()> {
+    examples::print_examples(command_path, &config::get_config()?, &mut io::stdout())
+}
+
+/// Records how long the command took, if enabled. Failing to record
+/// shouldn't fail the command, so errors are only logged.
+fn record_stats(command_name: &str, start: Instant, success: bool) {
+    let record_stats = config::get_config().is_ok_and(|config| config.record_stats);
+    if !record_stats {
+        return;
+    }
+    if let Err(err) = stats::record(command_name, start.elapsed(), success) {
+        log::warn!("Failed to record stats: {}", err);
+    }
+}
+
+fn main() -> ExitCode {
+    let result = if env::args().nth(1).as_deref() == Some("__complete") {
+        print_completion_candidates()
+    } else if let Some(command_path) = examples_command_path() {
+        print_examples_for(&command_path)
+    } else {
+        let matches = Cli::command().get_matches();
+        let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        let command_name = stats::command_name(&matches);
+        let start = Instant::now();
+        let result = run(cli);
+        record_stats(&command_name, start, result.is_ok());
+        // Printed even if the command failed, the tokens were still spent
+        llm::usage::print_session_cost();
+        result
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => errors::report(&err),
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
     setup_logging(cli.verbose);
+    prompts::install_terminal_restore_handlers()?;
 
     let mut context = Context {
         config: config::get_config()?,
         shell_actions: vec![],
     };
+    if cli.no_llm_cache {
+        context.config.llm_cache.enabled = false;
+    }
+    prompts::configure_select(context.config.select.clone());
+    if !matches!(cli.command, Commands::MigrateConfig { .. }) && config::config_needs_migration()? {
+        log::warn!("Config is in an old format, run `wkfl migrate-config` to update it");
+    }
     match cli.command {
         Commands::Start => actions::start_workflow(&mut context)?,
-        Commands::End => actions::end_workflow()?,
+        Commands::End { branch } => actions::end_workflow(branch)?,
+        Commands::Commit {
+            pairs,
+            recent,
+            git_args,
+        } => actions::commit_with_pairs(pairs, recent, git_args, context.config)?,
+        Commands::Run { name } => actions::run_command_group(&name)?,
         Commands::RepoDebug => actions::print_repo_debug_info()?,
-        Commands::Repos => actions::list_repositories(context.config)?,
-        Commands::Repo => actions::switch_repo(&mut context)?,
+        Commands::Repos { command: None } => actions::list_repositories(context.config)?,
+        Commands::Repos {
+            command: Some(ReposCommands::Outdated { languages, repo }),
+        } => actions::repos_outdated(languages, repo, context.config)?,
+        Commands::Repo { name } => actions::switch_repo(name, &mut context)?,
         Commands::Clone => actions::clone_repo(&mut context)?,
         Commands::Config => actions::print_config(context.config),
+        Commands::Prompt { command } => match command {
+            PromptCommands::Confirm {
+                prompt: user_prompt,
+                default_true: default,
+                json,
+            } => {
+                let prompt = user_prompt.unwrap_or("Confirm?".to_string());
+                actions::confirm(&prompt, default, json)?
+            }
+            PromptCommands::Input {
+                prompt: user_prompt,
+                initial,
+                json,
+            } => {
+                let prompt = user_prompt.unwrap_or(">".to_string());
+                actions::input(&prompt, initial, json)?
+            }
+            PromptCommands::Secret {
+                prompt: user_prompt,
+                json,
+            } => {
+                let prompt = user_prompt.unwrap_or("Secret:".to_string());
+                actions::secret(&prompt, json)?
+            }
+            PromptCommands::Select {
+                prompt: user_prompt,
+                layout,
+                json,
+            } => {
+                let prompt = user_prompt.unwrap_or("?".to_string());
+                actions::select(&prompt, layout.layout(), json)?
+            }
+            PromptCommands::MultiSelect {
+                prompt: user_prompt,
+                layout,
+                json,
+            } => {
+                let prompt = user_prompt.unwrap_or("?".to_string());
+                actions::multi_select(&prompt, layout.layout(), json)?
+            }
+        },
         Commands::Confirm {
             prompt: user_prompt,
             default_true: default,
         } => {
             let prompt = user_prompt.unwrap_or("Confirm?".to_string());
-            actions::confirm(&prompt, default)?
+            actions::confirm(&prompt, default, false)?
         }
         Commands::Select {
             prompt: user_prompt,
+            layout,
         } => {
             let prompt = user_prompt.unwrap_or("?".to_string());
-            actions::select(&prompt)?
+            actions::select(&prompt, layout.layout(), false)?
         }
         Commands::Notes {
             command: notes_command,
@@ -171,37 +745,199 @@ fn main() -> Result<(), Box<dyn Error>> {
             }
             NotesCommands::Topic { name } => actions::open_topic_note(name, &mut context)?,
             NotesCommands::Person { who } => actions::open_person_note(who, &mut context)?,
+            NotesCommands::Meeting { title, with } => {
+                actions::open_meeting_note(title, with, &mut context)?
+            }
+            NotesCommands::Open { day: Some(day) } => actions::open_daily_note(day, &mut context)?,
+            NotesCommands::Open { day: None } => actions::open_any_note(&mut context)?,
+            NotesCommands::List {
+                person,
+                topic,
+                daily,
+                recent,
+            } => {
+                let kind = [(person, "person"), (topic, "topic"), (daily, "daily")]
+                    .into_iter()
+                    .find_map(|(selected, kind)| selected.then_some(kind));
+                actions::list_notes(kind, recent, context.config)?
+            }
+            NotesCommands::Recent { limit } => actions::open_recent_note(limit, &mut context)?,
+            NotesCommands::Backlinks { note } => actions::show_backlinks(&note, context.config)?,
+            NotesCommands::Log { text } => actions::log_to_daily_note(&text, context.config)?,
+            NotesCommands::Tags => actions::list_tags(context.config)?,
+            NotesCommands::Tagged { tag, open } => actions::tagged_notes(&tag, open, &mut context)?,
+            NotesCommands::Rollover => actions::rollover_tasks(&context.config)?,
+            NotesCommands::Archive {
+                compress,
+                older_than,
+            } => actions::archive_notes(compress, older_than, context.config)?,
+            NotesCommands::Search {
+                query,
+                archived,
+                semantic: false,
+                ..
+            } => actions::search_notes(&query, archived, context.config)?,
+            NotesCommands::Search {
+                query,
+                archived,
+                semantic: true,
+                limit,
+            } => actions::semantic_search_notes(&query, archived, limit, context.config)?,
         },
         Commands::Llm {
             command: llm_command,
         } => match llm_command {
-            LlmCommands::Perplexity { query } => {
-                actions::run_perplexity_query(query, context.config)?
+            LlmCommands::Perplexity { query, attachments } => {
+                actions::run_perplexity_query(query, attachments, context.config)?
             }
-            LlmCommands::Anthropic { query } => {
-                actions::run_anthropic_query(query, context.config)?
+            LlmCommands::Anthropic { query, attachments } => {
+                actions::run_anthropic_query(query, attachments, context.config)?
             }
             LlmCommands::VertexAi {
+                command: Some(VertexAiCommands::CountTokens { query, attachments }),
+                ..
+            } => actions::count_vertex_ai_tokens(query, attachments, context.config)?,
+            LlmCommands::VertexAi {
+                command: None,
                 query,
                 enable_search,
-            } => actions::run_vertex_ai_query(query, enable_search, context.config)?,
+                attachments,
+            } => actions::run_vertex_ai_query(query, enable_search, attachments, context.config)?,
+            LlmCommands::Usage { days, daily } => {
+                llm::usage::print_usage(days, daily, &context.config.llm_prices)?
+            }
+        },
+        Commands::Ai {
+            command:
+                AiCommands::CommitMessage {
+                    model_provider,
+                    model_type,
+                    model,
+                    print,
+                },
+        } => actions::suggest_commit_message(
+            model_type.into(),
+            model,
+            model_provider.map(Into::into),
+            print,
+            context.config,
+        )?,
+        Commands::Ai {
+            command:
+                AiCommands::PrDescription {
+                    model_provider,
+                    model_type,
+                    model,
+                },
+        } => actions::suggest_pr_description(
+            model_type.into(),
+            model,
+            model_provider.map(Into::into),
+            context.config,
+        )?,
+        Commands::Ai {
+            command:
+                AiCommands::Review {
+                    model_provider,
+                    model_type,
+                    model,
+                },
+        } => actions::review_branch(
+            model_type.into(),
+            model,
+            model_provider.map(Into::into),
+            context.config,
+        )?,
+        Commands::Snippets {
+            command: snippets_command,
+        } => match snippets_command {
+            SnippetsCommands::Add { name, tags } => actions::add_snippet(name, tags, &mut context)?,
+            SnippetsCommands::List { tag } => actions::list_snippets(tag, context.config)?,
+            SnippetsCommands::Copy { name } => actions::copy_snippet(name, context.config)?,
+            SnippetsCommands::Run { name } => actions::run_snippet(name, context.config)?,
         },
         Commands::Completion { language } => {
             let mut cmd = Cli::command();
             let bin_name = cmd.get_name().to_string();
             let shell = language.unwrap_or(Shell::from_env().unwrap_or(Shell::Bash));
-            generate(shell, &mut cmd, bin_name, &mut io::stdout());
+            generate(shell, &mut cmd, &bin_name, &mut io::stdout());
+            completion::write_dynamic_completions(shell, &bin_name, &mut io::stdout())?;
+        }
+        Commands::Stats => stats::print_stats()?,
+        Commands::ApplySuggestion { file, yes } => actions::apply_suggestion(file, yes)?,
+        Commands::MigrateConfig { dry_run } => actions::migrate_config(dry_run)?,
+        Commands::HelpExamples { command } => {
+            examples::print_examples(&command, &context.config, &mut io::stdout())?
         }
         Commands::WebChat {
             query,
             model_type,
+            model,
             model_provider,
-        } => actions::run_web_chat(query, model_type, model_provider, context.config)?,
+            citations,
+            out,
+            attachments,
+        } => actions::run_web_chat(
+            query,
+            actions::WebChatOptions {
+                model_type: model_type.into(),
+                model,
+                model_provider: model_provider.map(Into::into),
+                citations: citations.map(Into::into),
+                out,
+                attachments,
+            },
+            context.config,
+        )?,
+        Commands::Chat {
+            command: Some(ChatCommands::History { command }),
+            ..
+        } => match command {
+            ChatHistoryCommands::List => actions::list_chat_history()?,
+            ChatHistoryCommands::Show { id } => actions::show_chat_history(id)?,
+            ChatHistoryCommands::Delete { id } => actions::delete_chat_history(&id)?,
+        },
         Commands::Chat {
+            command: None,
+            model_type,
+            model,
+            model_provider,
+            interactive: true,
+            continue_id,
+            ..
+        } => actions::run_interactive_chat(
+            model_type.into(),
+            model,
+            model_provider.map(Into::into),
+            continue_id,
+            context.config,
+        )?,
+        Commands::Chat {
+            command: None,
             query,
             model_type,
+            model,
             model_provider,
-        } => actions::run_chat(query, model_type, model_provider, context.config)?,
+            interactive: false,
+            continue_id,
+            template,
+            save_note,
+            json_schema,
+            attachments,
+        } => actions::run_chat(
+            query,
+            actions::ChatOptions {
+                model_type: model_type.into(),
+                model,
+                model_provider: model_provider.map(Into::into),
+                continue_id,
+                template,
+                save_note,
+                json_schema,
+                attachments,
+            },
+            context.config,
+        )?,
     };
 
     if let Some(shell_actions_file) = cli.shell_actions_file {
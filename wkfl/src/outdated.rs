@@ -0,0 +1,249 @@
+use std::{collections::BTreeMap, fs, io, path::Path, process::Command};
+
+use clap::ValueEnum;
+use log::warn;
+use serde::Deserialize;
+
+use wkfl_core::http::HttpClient;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Language {
+    Rust,
+    Javascript,
+    Python,
+}
+
+impl Language {
+    fn manifest(&self) -> &'static str {
+        match self {
+            Language::Rust => "Cargo.toml",
+            Language::Javascript => "package.json",
+            Language::Python => "requirements.txt",
+        }
+    }
+
+    /// Languages used in the repo, based on the manifests in its root
+    pub fn detect(repo_path: &Path) -> Vec<Language> {
+        Language::value_variants()
+            .iter()
+            .filter(|language| repo_path.join(language.manifest()).exists())
+            .copied()
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OutdatedDependency {
+    pub name: String,
+    pub current: String,
+    pub latest: String,
+}
+
+/// Finds outdated direct dependencies. Rust and JavaScript use the native
+/// tools (cargo-outdated and npm), Python checks pinned requirements
+/// against PyPI.
+pub fn find_outdated(
+    repo_path: &Path,
+    language: Language,
+    http: &HttpClient,
+) -> anyhow::Result<Vec<OutdatedDependency>> {
+    match language {
+        Language::Rust => {
+            let output = run_tool(
+                repo_path,
+                "cargo",
+                &["outdated", "--root-deps-only", "--format", "json"],
+            )?;
+            parse_cargo_outdated(&output)
+        }
+        Language::Javascript => {
+            let output = run_tool(repo_path, "npm", &["outdated", "--json"])?;
+            parse_npm_outdated(&output)
+        }
+        Language::Python => {
+            let requirements = fs::read_to_string(repo_path.join(language.manifest()))?;
+            let mut outdated = vec![];
+            for (name, current) in parse_pinned_requirements(&requirements) {
+                // One missing or unreachable package shouldn't hide the rest
+                let latest = match latest_pypi_version(&name, http) {
+                    Ok(latest) => latest,
+                    Err(err) => {
+                        warn!("Skipping {}, couldn't check it on PyPI: {}", name, err);
+                        continue;
+                    }
+                };
+                if latest != current {
+                    outdated.push(OutdatedDependency {
+                        name,
+                        current,
+                        latest,
+                    });
+                }
+            }
+            Ok(outdated)
+        }
+    }
+}
+
+/// Runs the tool in the repo and returns its stdout. The exit status is
+/// ignored since `npm outdated` fails when anything is outdated.
+fn run_tool(repo_path: &Path, program: &str, args: &[&str]) -> anyhow::Result<String> {
+    let output = match Command::new(program)
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            anyhow::bail!("{} isn't installed", program)
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if output.stdout.is_empty() && !output.status.success() {
+        anyhow::bail!(
+            "`{} {}` failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[derive(Deserialize)]
+struct CargoOutdatedCrate {
+    dependencies: Vec<CargoOutdatedDependency>,
+}
+
+#[derive(Deserialize)]
+struct CargoOutdatedDependency {
+    name: String,
+    project: String,
+    latest: String,
+}
+
+fn parse_cargo_outdated(output: &str) -> anyhow::Result<Vec<OutdatedDependency>> {
+    let mut outdated = vec![];
+    // One JSON object per line, one line per workspace member
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let crate_outdated: CargoOutdatedCrate = serde_json::from_str(line)?;
+        for dependency in crate_outdated.dependencies {
+            if dependency.project != dependency.latest {
+                outdated.push(OutdatedDependency {
+                    name: dependency.name,
+                    current: dependency.project,
+                    latest: dependency.latest,
+                });
+            }
+        }
+    }
+    Ok(outdated)
+}
+
+#[derive(Deserialize)]
+struct NpmOutdatedPackage {
+    current: Option<String>,
+    latest: String,
+}
+
+fn parse_npm_outdated(output: &str) -> anyhow::Result<Vec<OutdatedDependency>> {
+    if output.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let packages: BTreeMap<String, NpmOutdatedPackage> = serde_json::from_str(output)?;
+    Ok(packages
+        .into_iter()
+        .map(|(name, package)| OutdatedDependency {
+            name,
+            // Not installed yet
+            current: package.current.unwrap_or("-".to_string()),
+            latest: package.latest,
+        })
+        .collect())
+}
+
+/// Name and version of each `name==version` requirement. Ranges can't be
+/// outdated in the same way, so they're skipped.
+fn parse_pinned_requirements(requirements: &str) -> Vec<(String, String)> {
+    requirements
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.starts_with('-'))
+        .filter_map(|line| {
+            let (name, version) = line.split_once("==")?;
+            // Drop extras and environment markers, eg. name[extra]==1.0; python_version<"3.10"
+            let name = name.split('[').next().unwrap_or_default().trim();
+            let version = version.split(';').next().unwrap_or_default().trim();
+            (!name.is_empty() && !version.is_empty())
+                .then(|| (name.to_string(), version.to_string()))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct PypiProject {
+    info: PypiProjectInfo,
+}
+
+#[derive(Deserialize)]
+struct PypiProjectInfo {
+    version: String,
+}
+
+fn latest_pypi_version(name: &str, http: &HttpClient) -> anyhow::Result<String> {
+    let request = http.get(&format!("https://pypi.org/pypi/{}/json", name));
+    let project: PypiProject = http.call(request)?.into_json()?;
+    Ok(project.info.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_cargo_outdated, parse_npm_outdated, parse_pinned_requirements, OutdatedDependency,
+    };
+
+    fn dependency(name: &str, current: &str, latest: &str) -> OutdatedDependency {
+        OutdatedDependency {
+            name: name.to_string(),
+            current: current.to_string(),
+            latest: latest.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_cargo_outdated() {
+        let output = r#"{"crate_name":"wkfl","dependencies":[{"name":"anyhow","project":"1.0.95","compat":"1.0.98","latest":"1.0.98","kind":"Normal","platform":null},{"name":"log","project":"0.4.22","compat":"0.4.22","latest":"0.4.22","kind":"Normal","platform":null}]}"#;
+        assert_eq!(
+            parse_cargo_outdated(output).unwrap(),
+            vec![dependency("anyhow", "1.0.95", "1.0.98")]
+        );
+    }
+
+    #[test]
+    fn test_parse_npm_outdated() {
+        let output = r#"{
+            "lodash": {"current": "4.17.20", "wanted": "4.17.21", "latest": "4.17.21", "location": "node_modules/lodash"},
+            "react": {"wanted": "18.3.1", "latest": "19.0.0", "location": "node_modules/react"}
+        }"#;
+        assert_eq!(
+            parse_npm_outdated(output).unwrap(),
+            vec![
+                dependency("lodash", "4.17.20", "4.17.21"),
+                dependency("react", "-", "19.0.0"),
+            ]
+        );
+        assert!(parse_npm_outdated("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_pinned_requirements() {
+        let requirements = "# deps\nrequests==2.31.0\nflask>=2.0\n-r dev.txt\nuvicorn[standard]==0.29.0 ; python_version >= \"3.8\"\n";
+        assert_eq!(
+            parse_pinned_requirements(requirements),
+            vec![
+                ("requests".to_string(), "2.31.0".to_string()),
+                ("uvicorn".to_string(), "0.29.0".to_string()),
+            ]
+        );
+    }
+}
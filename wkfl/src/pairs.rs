@@ -0,0 +1,53 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use wkfl_core::config::wkfl_state_dir;
+use wkfl_core::notes;
+
+const RECENT_PAIRS_LIMIT: usize = 10;
+
+fn recent_pairs_file() -> anyhow::Result<PathBuf> {
+    Ok(wkfl_state_dir()?.join("recent_pairs.json"))
+}
+
+/// People recently paired with, most recent first
+pub fn recent_pairs() -> anyhow::Result<Vec<String>> {
+    let path = recent_pairs_file()?;
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn remember_pairs(pairs: &[String]) -> anyhow::Result<()> {
+    let mut recent = recent_pairs()?;
+    recent.retain(|who| !pairs.contains(who));
+    recent.splice(0..0, pairs.iter().cloned());
+    recent.truncate(RECENT_PAIRS_LIMIT);
+
+    let path = recent_pairs_file()?;
+    fs::create_dir_all(path.parent().expect("Recent pairs file is in a directory"))?;
+    fs::write(path, serde_json::to_string(&recent)?)?;
+    Ok(())
+}
+
+/// Builds the Co-authored-by trailer for someone from their person note.
+/// Name comes from the note's title and the email from an `email:` line.
+pub fn co_author_trailer(notes_dir: &Path, who: &str) -> anyhow::Result<String> {
+    let note_path = notes_dir.join(notes::format_note_path(&notes::NoteSpecifier::Person {
+        who: who.to_string(),
+    }));
+    let contents = fs::read_to_string(&note_path)
+        .map_err(|_| anyhow::anyhow!("No person note for {} at {}", who, note_path.display()))?;
+    let email = notes::note_metadata(&contents, "email").ok_or_else(|| {
+        anyhow::anyhow!(
+            "Add an `email: ...` line to {} to pair with {}",
+            note_path.display(),
+            who
+        )
+    })?;
+    let name = notes::note_title(&note_path).unwrap_or(who.to_string());
+    Ok(format!("Co-authored-by: {} <{}>", name, email))
+}
@@ -0,0 +1,181 @@
+//! Spinners and progress bars for long running commands, drawn on stderr.
+//! When stderr isn't a terminal, or with --verbose where they'd be mixed up
+//! with the debug logs, progress is logged instead. Spinners only log at
+//! debug level so scripts don't get a line for every API call.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor,
+    style::Print,
+    terminal::{Clear, ClearType},
+    QueueableCommand,
+};
+use log::{debug, info};
+
+use crate::prompts;
+
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(80);
+const BAR_WIDTH: usize = 20;
+
+/// Whether a spinner or bar is on the current line, so log lines know to
+/// clear it first
+static DRAWN: AtomicBool = AtomicBool::new(false);
+
+pub fn is_drawn() -> bool {
+    DRAWN.load(Ordering::SeqCst)
+}
+
+fn should_draw() -> bool {
+    io::stderr().is_terminal() && !log::log_enabled!(log::Level::Debug)
+}
+
+/// Replaces the current line, cut to the terminal width so it can't wrap
+fn draw_line(text: &str) {
+    let text = prompts::fit_width(text, prompts::terminal_width().saturating_sub(1));
+    let mut stderr = io::stderr();
+    // Errors are ignored, progress isn't worth failing the command over
+    let _ = stderr
+        .queue(cursor::MoveToColumn(0))
+        .and_then(|stderr| stderr.queue(Clear(ClearType::CurrentLine)))
+        .and_then(|stderr| stderr.queue(Print(text)))
+        .and_then(|stderr| stderr.flush());
+    DRAWN.store(true, Ordering::SeqCst);
+}
+
+fn clear_line() {
+    let mut stderr = io::stderr();
+    let _ = stderr
+        .queue(cursor::MoveToColumn(0))
+        .and_then(|stderr| stderr.queue(Clear(ClearType::CurrentLine)))
+        .and_then(|stderr| stderr.flush());
+    DRAWN.store(false, Ordering::SeqCst);
+}
+
+/// Spins until dropped, for waiting on something with no measurable
+/// progress like an API call
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: &str) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        if !should_draw() {
+            debug!("{}", message);
+            return Self { stop, thread: None };
+        }
+        let thread = {
+            let stop = stop.clone();
+            let message = message.to_string();
+            thread::spawn(move || {
+                for frame in SPINNER_FRAMES.iter().cycle() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    draw_line(&format!("{} {}", frame, message));
+                    thread::sleep(SPINNER_INTERVAL);
+                }
+            })
+        };
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+            clear_line();
+        }
+    }
+}
+
+/// Runs `f` with a spinner showing
+pub fn with_spinner<T>(message: &str, f: impl FnOnce() -> T) -> T {
+    let _spinner = Spinner::start(message);
+    f()
+}
+
+/// Progress through a known number of items, eg. repos, cleared when
+/// dropped
+pub struct ProgressBar {
+    message: String,
+    total: usize,
+    started: usize,
+    draw: bool,
+}
+
+fn render_bar(done: usize, total: usize, width: usize) -> String {
+    let filled = (done * width)
+        .checked_div(total)
+        .unwrap_or(width)
+        .min(width);
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(width - filled))
+}
+
+impl ProgressBar {
+    pub fn new(message: &str, total: usize) -> Self {
+        Self {
+            message: message.to_string(),
+            total,
+            started: 0,
+            draw: should_draw(),
+        }
+    }
+
+    /// Moves on to the next item, `item` says what it is
+    pub fn next(&mut self, item: &str) {
+        self.started += 1;
+        if self.draw {
+            draw_line(&format!(
+                "{} {} {}/{} {}",
+                self.message,
+                render_bar(self.started - 1, self.total, BAR_WIDTH),
+                self.started,
+                self.total,
+                item
+            ));
+        } else {
+            info!(
+                "{} ({}/{}) {}",
+                self.message, self.started, self.total, item
+            );
+        }
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        if self.draw && self.started > 0 {
+            clear_line();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_bar;
+
+    #[test]
+    fn test_render_bar() {
+        assert_eq!(render_bar(0, 4, 8), "[--------]");
+        assert_eq!(render_bar(1, 4, 8), "[##------]");
+        assert_eq!(render_bar(4, 4, 8), "[########]");
+        assert_eq!(render_bar(0, 0, 4), "[####]");
+    }
+}
@@ -0,0 +1,66 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, process};
+
+use wkfl_core::config::wkfl_state_dir;
+
+const HISTORY_LIMIT: usize = 100;
+
+/// Answers to each prompt, keyed by the prompt text, most recent first
+type History = BTreeMap<String, Vec<String>>;
+
+fn history_file() -> anyhow::Result<PathBuf> {
+    Ok(wkfl_state_dir()?.join("prompt_history.json"))
+}
+
+fn read_history() -> anyhow::Result<History> {
+    let path = history_file()?;
+    if !path.exists() {
+        return Ok(History::new());
+    }
+    // Starting over beats never saving history again
+    match serde_json::from_str(&fs::read_to_string(path)?) {
+        Ok(history) => Ok(history),
+        Err(err) => {
+            log::warn!("Prompt history is corrupt, starting a new one: {}", err);
+            Ok(History::new())
+        }
+    }
+}
+
+/// Previous answers to `prompt`, most recent first
+pub fn load(prompt: &str) -> Vec<String> {
+    match read_history() {
+        Ok(mut history) => history.remove(prompt).unwrap_or_default(),
+        Err(err) => {
+            log::warn!("Failed to read prompt history: {}", err);
+            vec![]
+        }
+    }
+}
+
+fn try_remember(prompt: &str, answer: &str) -> anyhow::Result<()> {
+    let mut history = read_history()?;
+    let answers = history.entry(prompt.to_string()).or_default();
+    answers.retain(|previous| previous != answer);
+    answers.insert(0, answer.to_string());
+    answers.truncate(HISTORY_LIMIT);
+
+    // Written next to it then renamed over it, so a crash or another
+    // prompt saving at the same time can't leave half a file
+    let path = history_file()?;
+    fs::create_dir_all(path.parent().expect("History file is in a directory"))?;
+    let temp_path = path.with_extension(format!("json.{}", process::id()));
+    fs::write(&temp_path, serde_json::to_string(&history)?)?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+/// Saves an answer to `prompt`. Failing only warns, the answer itself is
+/// still good
+pub fn remember(prompt: &str, answer: &str) {
+    if answer.trim().is_empty() {
+        return;
+    }
+    if let Err(err) = try_remember(prompt, answer) {
+        log::warn!("Failed to save prompt history: {}", err);
+    }
+}
@@ -1,16 +1,122 @@
-use std::io::{self, Stderr, Write};
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    env, fs,
+    io::{self, IsTerminal, Read, Stderr, Write},
+    panic,
+    path::PathBuf,
+    process::{self, Command},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::bail;
 use crossterm::{
     self, cursor,
     event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     style::{self, Attribute, Color, PrintStyledContent, Stylize},
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    terminal::{
+        self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
     ExecutableCommand, QueueableCommand,
 };
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use wkfl_core::config::SelectConfig;
+use wkfl_core::errors::UserAbort;
+
+use crate::llm_args::Attachments;
+use crate::prompt_history;
+
+const KILL_RING_SIZE: usize = 10;
+
+/// Whether a prompt switched to the alternate screen, so restoring the
+/// terminal knows to switch back
+static IN_ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+static SELECT_CONFIG: OnceLock<SelectConfig> = OnceLock::new();
+
+/// Puts the terminal back into a usable state. Safe to call even if nothing
+/// changed the terminal, so it can be used from the panic and signal handlers.
+pub fn restore_terminal() {
+    let mut stderr = io::stderr();
+    // Errors are ignored since there isn't anything else we could do to
+    // restore the terminal
+    if IN_ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+        let _ = stderr.execute(LeaveAlternateScreen);
+    }
+    let _ = disable_raw_mode();
+    let _ = stderr
+        .queue(cursor::Show)
+        .and_then(|stderr| stderr.queue(cursor::SetCursorStyle::DefaultUserShape))
+        .and_then(|stderr| stderr.queue(style::ResetColor))
+        .and_then(|stderr| stderr.flush());
+}
 
-const MAX_OPTIONS_SHOWN: usize = 10;
+/// Makes sure the terminal gets restored if wkfl panics or is interrupted.
+/// In raw mode ctrl-c is a key event, which the prompts handle themselves,
+/// but the signal can still arrive while not in a prompt (eg. waiting on a
+/// request) or from outside the terminal.
+pub fn install_terminal_restore_handlers() -> anyhow::Result<()> {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+    ctrlc::set_handler(|| {
+        restore_terminal();
+        eprintln!();
+        // 128 + SIGINT, which is what shells use for ctrl-c
+        process::exit(130);
+    })?;
+    Ok(())
+}
+
+/// Enables raw mode and restores the terminal when dropped, so the terminal
+/// is usable again no matter how the prompt exits
+struct RawModeGuard;
+
+impl RawModeGuard {
+    fn new() -> anyhow::Result<Self> {
+        enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Byte index of the grapheme before `index`, cursors move by grapheme so
+/// accented letters and emoji are edited as one character
+fn previous_grapheme(text: &str, index: usize) -> usize {
+    text[..index]
+        .grapheme_indices(true)
+        .next_back()
+        .map_or(0, |(start, _)| start)
+}
+
+/// Byte index after the grapheme starting at `index`
+fn next_grapheme(text: &str, index: usize) -> usize {
+    text[index..]
+        .graphemes(true)
+        .next()
+        .map_or(index, |grapheme| index + grapheme.len())
+}
+
+fn last_grapheme_start(text: &str) -> usize {
+    previous_grapheme(text, text.len())
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(char::is_alphanumeric)
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum OpAdjust {
@@ -38,6 +144,28 @@ struct PromptState {
     input_row: u16,
     line: String,
     mode: PromptMode,
+    /// Text removed by the readline style kill commands, most recent last
+    kill_ring: Vec<String>,
+    /// Vim's unnamed register, text removed by `x`, `d` and `c` for `p`
+    register: String,
+    /// Line and cursor before each change, most recent last
+    undo_stack: Vec<(String, usize)>,
+    redo_stack: Vec<(String, usize)>,
+    /// Whether the current insert has been saved to the undo stack, so
+    /// everything typed in one go is undone together
+    insert_saved: bool,
+    /// Keys of the change being made, until back in normal mode
+    recording: Option<Vec<(KeyCode, KeyModifiers)>>,
+    /// Keys of the last complete change, replayed by `.`
+    last_change: Vec<(KeyCode, KeyModifiers)>,
+    /// Previous answers to the prompt, most recent first
+    history: Vec<String>,
+    /// Entry of `history` being shown, None when editing a new answer
+    history_index: Option<usize>,
+    /// What was typed before going back through the history
+    draft: String,
+    /// Query of an in progress Ctrl-R search
+    search: Option<String>,
 }
 
 impl PromptState {
@@ -48,14 +176,25 @@ impl PromptState {
             input_row,
             line: String::new(),
             mode: PromptMode::Insert,
+            kill_ring: vec![],
+            register: String::new(),
+            undo_stack: vec![],
+            redo_stack: vec![],
+            insert_saved: false,
+            recording: None,
+            last_change: vec![],
+            history: vec![],
+            history_index: None,
+            draft: String::new(),
+            search: None,
         }
     }
 
     fn max_cursor(&self) -> usize {
         match self.mode {
             PromptMode::Insert => self.line.len(),
-            // -1 so you can only go to the last character and not past
-            PromptMode::Normal | PromptMode::OperatorPending(_) => self.line.len() - 1,
+            // You can only go to the last character and not past
+            PromptMode::Normal | PromptMode::OperatorPending(_) => last_grapheme_start(&self.line),
         }
     }
 
@@ -64,7 +203,8 @@ impl PromptState {
     }
 
     fn normal_mode(&mut self) {
-        self.mode = PromptMode::Normal
+        self.mode = PromptMode::Normal;
+        self.insert_saved = false;
     }
 
     fn operator_pending_mode(&mut self, op: Operation) {
@@ -80,15 +220,12 @@ impl PromptState {
     }
 
     fn move_left(&mut self) {
-        if self.cursor > 0 {
-            self.cursor -= 1
-        }
+        self.cursor = previous_grapheme(&self.line, self.cursor);
     }
 
     fn move_right(&mut self) {
-        let max_cursor = self.max_cursor();
-        if self.cursor < max_cursor {
-            self.cursor += 1
+        if self.cursor < self.max_cursor() {
+            self.cursor = next_grapheme(&self.line, self.cursor);
         }
     }
 
@@ -110,61 +247,62 @@ impl PromptState {
         }
     }
 
+    /// Index into `graphemes` of the one the cursor is on
+    fn cursor_grapheme(&self, graphemes: &[(usize, &str)]) -> usize {
+        graphemes
+            .iter()
+            .position(|(index, _)| *index >= self.cursor)
+            .unwrap_or(graphemes.len())
+    }
+
     fn get_current_word_end(&self) -> usize {
-        fn predicate(item: &(usize, char)) -> bool {
-            !item.1.is_alphanumeric()
-        }
-        let item = self
-            .line
-            .char_indices()
-            .skip(self.cursor + 1)
+        let graphemes: Vec<(usize, &str)> = self.line.grapheme_indices(true).collect();
+        let item = graphemes
+            .iter()
+            .enumerate()
+            .skip(self.cursor_grapheme(&graphemes) + 1)
             // Get into a word
-            .skip_while(predicate)
+            .skip_while(|(_, (_, grapheme))| !is_word(grapheme))
             // Find when we are back out of the word
-            .find(predicate);
-        if let Some((index, _)) = item {
-            index - 1
+            .find(|(_, (_, grapheme))| !is_word(grapheme));
+        if let Some((position, _)) = item {
+            graphemes[position - 1].0
         } else {
-            self.line.len() - 1
+            last_grapheme_start(&self.line)
         }
     }
 
     fn get_current_word_start(&self) -> usize {
-        fn predicate(item: &(usize, char)) -> bool {
-            !item.1.is_alphanumeric()
-        }
-        let item = self
-            .line
-            .char_indices()
+        let graphemes: Vec<(usize, &str)> = self.line.grapheme_indices(true).collect();
+        let item = graphemes
+            .iter()
+            .enumerate()
+            .take(self.cursor_grapheme(&graphemes))
             .rev()
-            .skip(self.line.len() - self.cursor)
             // Get into a word
-            .skip_while(predicate)
+            .skip_while(|(_, (_, grapheme))| !is_word(grapheme))
             // Find when we are back out of the word
-            .find(predicate);
-        if let Some((index, _)) = item {
-            index + 1
+            .find(|(_, (_, grapheme))| !is_word(grapheme));
+        if let Some((position, _)) = item {
+            graphemes[position + 1].0
         } else {
             0
         }
     }
 
     fn get_next_word_start(&self) -> usize {
-        fn predicate(item: &(usize, char)) -> bool {
-            item.1.is_alphanumeric()
-        }
         let item = self
             .line
-            .char_indices()
-            .skip(self.cursor)
+            .grapheme_indices(true)
+            .skip_while(|(index, _)| *index < self.cursor)
             // Get out of a word
-            .skip_while(predicate)
+            .skip_while(|(_, grapheme)| is_word(grapheme))
             // Find when we are back in a word
-            .find(predicate);
+            .find(|(_, grapheme)| is_word(grapheme));
         if let Some((index, _)) = item {
             index
         } else {
-            self.line.len() - 1
+            last_grapheme_start(&self.line)
         }
     }
 
@@ -179,13 +317,13 @@ impl PromptState {
             self.get_current_word_end()
         } else {
             let word_start = self.get_next_word_start();
-            if word_start == self.line.len() - 1 {
+            if word_start == last_grapheme_start(&self.line) {
                 word_start
             } else {
-                word_start - 1
+                previous_grapheme(&self.line, word_start)
             }
         };
-        self.delete_range(start, end + 1);
+        self.cut_range(start, next_grapheme(&self.line, end));
     }
 
     fn delete_range(&mut self, start: usize, end: usize) {
@@ -193,21 +331,204 @@ impl PromptState {
         self.cursor = start;
     }
 
+    /// Deletes the range into the register
+    fn cut_range(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.register = self.line[start..end].to_string();
+        self.delete_range(start, end);
+    }
+
     fn delete_current_char(&mut self) {
-        self.line.remove(self.cursor);
+        let end = next_grapheme(&self.line, self.cursor);
+        self.line.replace_range(self.cursor..end, "");
+    }
+
+    fn cut_current_char(&mut self) {
+        self.cut_range(self.cursor, next_grapheme(&self.line, self.cursor));
     }
 
     fn delete_all(&mut self) {
-        self.line = String::new();
+        self.register = std::mem::take(&mut self.line);
         self.cursor = 0;
     }
 
-    fn insert_char(&mut self, c: char) {
-        if self.cursor < self.max_cursor() {
-            self.line.insert(self.cursor, c);
+    /// Pastes the register after the cursor like `p`, or before it like
+    /// `P`, leaving the cursor on the last pasted character
+    fn paste(&mut self, after: bool) {
+        if self.register.is_empty() {
+            return;
+        }
+        let position = if after {
+            next_grapheme(&self.line, self.cursor)
         } else {
-            self.line.push(c);
+            self.cursor
+        };
+        self.line.insert_str(position, &self.register);
+        self.cursor = position + last_grapheme_start(&self.register);
+    }
+
+    /// Replaces the line, with the cursor at the end
+    fn set_line(&mut self, line: String) {
+        self.line = line;
+        self.cursor = self.max_cursor();
+    }
+
+    fn history_previous(&mut self) {
+        let index = match self.history_index {
+            None if !self.history.is_empty() => {
+                self.draft = self.line.clone();
+                0
+            }
+            Some(index) if index + 1 < self.history.len() => index + 1,
+            _ => return,
+        };
+        self.history_index = Some(index);
+        self.set_line(self.history[index].clone());
+    }
+
+    fn history_next(&mut self) {
+        match self.history_index {
+            None => {}
+            Some(0) => {
+                self.history_index = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.set_line(draft);
+            }
+            Some(index) => {
+                self.history_index = Some(index - 1);
+                self.set_line(self.history[index - 1].clone());
+            }
+        }
+    }
+
+    /// Shows the first entry from `start` matching the search, returns
+    /// whether there was one
+    fn search_history_from(&mut self, start: usize) -> bool {
+        let Some(query) = &self.search else {
+            return false;
+        };
+        let found = self
+            .history
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find_map(|(index, entry)| entry.find(query.as_str()).map(|at| (index, at)));
+        match found {
+            Some((index, at)) => {
+                self.history_index = Some(index);
+                self.line = self.history[index].clone();
+                self.cursor = at;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Handles a key during a Ctrl-R search, returns false if the key ended
+    /// the search and should be handled as normal
+    fn search_key(&mut self, key: KeyCode, modifiers: KeyModifiers) -> bool {
+        if self.search.is_none() {
+            return false;
+        }
+        match (key, modifiers) {
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                let start = self.history_index.map_or(0, |index| index + 1);
+                self.search_history_from(start);
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.search.get_or_insert_default().push(c);
+                let start = self.history_index.unwrap_or(0);
+                self.search_history_from(start);
+            }
+            (KeyCode::Backspace, _) => {
+                self.search.get_or_insert_default().pop();
+                self.search_history_from(0);
+            }
+            (KeyCode::Esc, _) => self.search = None,
+            _ => {
+                self.search = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    fn start_search(&mut self) {
+        if self.history_index.is_none() {
+            self.draft = self.line.clone();
+        }
+        self.search = Some(String::new());
+    }
+
+    fn save_undo(&mut self, line: String, cursor: usize) {
+        self.undo_stack.push((line, cursor));
+        self.redo_stack.clear();
+    }
+
+    fn restore(&mut self, line: String, cursor: usize) {
+        self.line = line;
+        self.cursor = cursor.min(last_grapheme_start(&self.line));
+    }
+
+    fn undo(&mut self) {
+        if let Some((line, cursor)) = self.undo_stack.pop() {
+            let current = std::mem::take(&mut self.line);
+            self.redo_stack.push((current, self.cursor));
+            self.restore(line, cursor);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some((line, cursor)) = self.redo_stack.pop() {
+            let current = std::mem::take(&mut self.line);
+            self.undo_stack.push((current, self.cursor));
+            self.restore(line, cursor);
+        }
+    }
+
+    fn kill_range(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.kill_ring.push(self.line[start..end].to_string());
+        if self.kill_ring.len() > KILL_RING_SIZE {
+            self.kill_ring.remove(0);
         }
+        self.delete_range(start, end);
+    }
+
+    /// Ctrl-W, kills back to the previous whitespace like readline does
+    fn kill_word_before_cursor(&mut self) {
+        let before_cursor = self.line[..self.cursor].trim_end();
+        let start = before_cursor
+            .rfind(|c: char| c.is_ascii_whitespace())
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        self.kill_range(start, self.cursor);
+    }
+
+    fn kill_to_start(&mut self) {
+        self.kill_range(0, self.cursor);
+    }
+
+    fn kill_to_end(&mut self) {
+        self.kill_range(self.cursor, self.line.len());
+    }
+
+    /// Inserts the most recently killed text at the cursor
+    fn yank(&mut self) {
+        if let Some(text) = self.kill_ring.last() {
+            self.line.insert_str(self.cursor, text);
+            self.cursor += text.len();
+        }
+    }
+
+    /// Inserts at the cursor and moves past it
+    fn insert_char(&mut self, c: char) {
+        self.line.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
     }
 }
 
@@ -218,38 +539,99 @@ fn determine_cursor_shape(state: &PromptState) -> cursor::SetCursorStyle {
     }
 }
 
+/// Normal mode keys that start a change `.` can repeat
+const CHANGE_KEYS: &str = "xXpPdc";
+
+/// Handles undo, redo and `.`, and keeps track of changes for them
 fn handle_key(
     state: &mut PromptState,
     key: KeyCode,
     modifiers: KeyModifiers,
 ) -> anyhow::Result<bool> {
+    if state.mode == PromptMode::Normal {
+        match (key, modifiers) {
+            (KeyCode::Char('u'), KeyModifiers::NONE) => {
+                state.undo();
+                return Ok(false);
+            }
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                state.redo();
+                return Ok(false);
+            }
+            (KeyCode::Char('.'), KeyModifiers::NONE) => {
+                for (key, modifiers) in state.last_change.clone() {
+                    handle_key(state, key, modifiers)?;
+                }
+                return Ok(false);
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT)
+                if CHANGE_KEYS.contains(c) =>
+            {
+                state.recording = Some(vec![]);
+            }
+            _ => {}
+        }
+    }
+    if let Some(recording) = &mut state.recording {
+        recording.push((key, modifiers));
+    }
+
+    let line = state.line.clone();
+    let cursor = state.cursor;
+    let was_inserting = state.mode == PromptMode::Insert;
+    let done = handle_edit_key(state, key, modifiers)?;
+    if state.line != line && !(was_inserting && state.insert_saved) {
+        state.save_undo(line, cursor);
+        state.insert_saved = state.mode == PromptMode::Insert;
+    }
+    if state.mode == PromptMode::Normal {
+        if let Some(recording) = state.recording.take() {
+            state.last_change = recording;
+        }
+    }
+    Ok(done)
+}
+
+fn handle_edit_key(
+    state: &mut PromptState,
+    key: KeyCode,
+    modifiers: KeyModifiers,
+) -> anyhow::Result<bool> {
+    if state.search_key(key, modifiers) {
+        return Ok(false);
+    }
     match (&state.mode, key, modifiers) {
         (_, KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-            bail!("ctrl-c sent");
+            return Err(UserAbort.into());
         }
+        (PromptMode::Insert, KeyCode::Char(c), KeyModifiers::CONTROL) => match c {
+            'r' => state.start_search(),
+            'a' => state.move_to_start(),
+            'e' => state.move_to_end(),
+            'w' => state.kill_word_before_cursor(),
+            'u' => state.kill_to_start(),
+            'k' => state.kill_to_end(),
+            'y' => state.yank(),
+            _ => {}
+        },
         (mode, keycode, KeyModifiers::NONE | KeyModifiers::SHIFT) => match (mode, keycode) {
             (_, KeyCode::Enter) => {
                 return Ok(true);
             }
+            (_, KeyCode::Up) => state.history_previous(),
+            (_, KeyCode::Down) => state.history_next(),
             (PromptMode::Insert, KeyCode::Esc) => {
                 state.normal_mode();
                 state.move_left();
             }
             (PromptMode::Normal, KeyCode::Backspace) => state.move_left(),
             (PromptMode::Insert, KeyCode::Backspace) => {
-                if state.cursor < state.max_cursor() {
-                    if state.cursor != 0 {
-                        state.move_left();
-                        state.delete_current_char()
-                    }
-                } else if state.line.pop().is_some() {
+                if state.cursor > 0 {
                     state.move_left();
+                    state.delete_current_char()
                 }
             }
-            (PromptMode::Insert, KeyCode::Char(c)) => {
-                state.insert_char(c);
-                state.move_right();
-            }
+            (PromptMode::Insert, KeyCode::Char(c)) => state.insert_char(c),
             (PromptMode::Normal, KeyCode::Char(c)) => match c {
                 'i' => state.insert_mode(),
                 'I' => {
@@ -264,11 +646,13 @@ fn handle_key(
                     state.insert_mode();
                     state.move_to_end();
                 }
-                'x' => state.delete_current_char(),
-                'X' => {
+                'x' => state.cut_current_char(),
+                'X' if state.cursor > 0 => {
                     state.move_left();
-                    state.delete_current_char();
+                    state.cut_current_char();
                 }
+                'p' => state.paste(true),
+                'P' => state.paste(false),
                 'h' => state.move_left(),
                 'l' => state.move_right(),
                 'c' => state.operator_pending_mode(Operation::Change(OpAdjust::Empty)),
@@ -300,23 +684,23 @@ fn handle_key(
                     state.normal_mode();
                 }
                 (Operation::Change(OpAdjust::Empty), 'e') => {
-                    let end = state.get_current_word_end();
-                    state.delete_range(state.cursor, end + 1);
+                    let end = next_grapheme(&state.line, state.get_current_word_end());
+                    state.cut_range(state.cursor, end);
                     state.insert_mode();
                 }
                 (Operation::Delete(OpAdjust::Empty), 'e') => {
-                    let end = state.get_current_word_end();
-                    state.delete_range(state.cursor, end + 1);
+                    let end = next_grapheme(&state.line, state.get_current_word_end());
+                    state.cut_range(state.cursor, end);
                     state.normal_mode();
                 }
                 (Operation::Change(OpAdjust::Empty), 'b') => {
                     let start = state.get_current_word_start();
-                    state.delete_range(start, state.cursor);
+                    state.cut_range(start, state.cursor);
                     state.insert_mode();
                 }
                 (Operation::Delete(OpAdjust::Empty), 'b') => {
                     let start = state.get_current_word_start();
-                    state.delete_range(start, state.cursor);
+                    state.cut_range(start, state.cursor);
                     state.normal_mode();
                 }
                 (Operation::Change(OpAdjust::Empty), 'c') => {
@@ -341,13 +725,18 @@ fn print_prompt_input(state: &PromptState, stderr: &mut dyn Write) -> anyhow::Re
         .queue(cursor::MoveTo(state.input_start, state.input_row))?
         .queue(Clear(ClearType::UntilNewLine))?
         .queue(style::Print(&state.line))?;
+    if let Some(query) = &state.search {
+        stderr.queue(PrintStyledContent(
+            format!("  (reverse-i-search: {})", query).attribute(Attribute::Dim),
+        ))?;
+    }
     Ok(())
 }
 
 fn update_cursor(state: &PromptState, stderr: &mut dyn Write) -> anyhow::Result<()> {
     stderr
         .queue(cursor::MoveTo(
-            state.input_start + u16::try_from(state.cursor)?,
+            state.input_start + u16::try_from(state.line[..state.cursor].width())?,
             state.input_row,
         ))?
         .queue(determine_cursor_shape(state))?;
@@ -355,33 +744,457 @@ fn update_cursor(state: &PromptState, stderr: &mut dyn Write) -> anyhow::Result<
 }
 
 pub fn basic_prompt(prompt: &str) -> anyhow::Result<String> {
+    editable_prompt(prompt, "")
+}
+
+/// Same as `basic_prompt` but the answer isn't saved to the prompt history,
+/// for answers that could be sensitive, like chat messages
+pub fn private_prompt(prompt: &str) -> anyhow::Result<String> {
+    prompt_with_history(prompt, "", false)
+}
+
+/// The query from the argument, piped input or else a prompt, with any
+/// attachments added
+pub fn get_query(maybe_query: Option<String>, attachments: &Attachments) -> anyhow::Result<String> {
+    let query = match maybe_query {
+        Some(query) => query,
+        // stdin is an attachment, only the argument can be the question
+        None if attachments.stdin => String::new(),
+        None => {
+            let mut stdin = io::stdin();
+            if stdin.is_terminal() {
+                basic_prompt("Query:")?
+            } else {
+                let mut query = String::new();
+                stdin.read_to_string(&mut query)?;
+                query
+            }
+        }
+    };
+    attachments.add_to(query)
+}
+
+/// Prompt with `initial` already filled in, for editing a suggestion
+pub fn editable_prompt(prompt: &str, initial: &str) -> anyhow::Result<String> {
+    prompt_with_history(prompt, initial, true)
+}
+
+fn prompt_with_history(prompt: &str, initial: &str, use_history: bool) -> anyhow::Result<String> {
     let mut stderr = io::stderr();
     eprint!("{} ", prompt);
     stderr.flush()?;
 
-    let input_start = u16::try_from(prompt.len() + 1)?;
+    let input_start = u16::try_from(prompt.width() + 1)?;
     let (_, input_row) = cursor::position()?;
     let mut state = PromptState::new(input_start, input_row);
+    state.line = initial.to_string();
+    state.cursor = state.line.len();
+    if use_history {
+        state.history = prompt_history::load(prompt);
+    }
 
-    enable_raw_mode()?;
+    let raw_mode = RawModeGuard::new()?;
     stderr.execute(cursor::SetCursorStyle::SteadyBar)?;
+    print_prompt_input(&state, &mut stderr)?;
+    update_cursor(&state, &mut stderr)?;
+    stderr.flush()?;
 
-    while let Event::Key(KeyEvent {
-        code, modifiers, ..
-    }) = event::read()?
-    {
-        if handle_key(&mut state, code, modifiers)? {
-            break;
+    let result = basic_prompt_inner(&mut state, &mut stderr);
+
+    drop(raw_mode);
+    // Always end on a new line, so an interrupted prompt doesn't leave
+    // partial input in front of whatever is printed next
+    eprintln!();
+
+    result?;
+    if use_history {
+        prompt_history::remember(prompt, &state.line);
+    }
+    Ok(state.line)
+}
+
+/// Input prompts react to
+enum PromptEvent {
+    Key(KeyCode, KeyModifiers),
+    Resize { rows: u16 },
+}
+
+/// Waits for a key press or the terminal being resized, other events like
+/// focus changes are skipped
+fn read_event() -> anyhow::Result<PromptEvent> {
+    loop {
+        match event::read()? {
+            Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) => return Ok(PromptEvent::Key(code, modifiers)),
+            Event::Resize(_, rows) => return Ok(PromptEvent::Resize { rows }),
+            _ => {}
         }
+    }
+}
 
-        print_prompt_input(&state, &mut stderr)?;
-        update_cursor(&state, &mut stderr)?;
+/// Columns in the terminal. Some terminals, eg. a fresh pty, report a
+/// width of 0
+pub fn terminal_width() -> usize {
+    match terminal::size() {
+        Ok((columns, _)) if columns > 0 => usize::from(columns),
+        _ => 80,
+    }
+}
+
+fn basic_prompt_inner(state: &mut PromptState, stderr: &mut Stderr) -> anyhow::Result<()> {
+    loop {
+        match read_event()? {
+            PromptEvent::Key(code, modifiers) => {
+                if handle_key(state, code, modifiers)? {
+                    break;
+                }
+            }
+            // The terminal may have rewrapped lines, moving the input
+            PromptEvent::Resize { .. } => state.input_row = cursor::position()?.1,
+        }
+
+        print_prompt_input(state, stderr)?;
+        update_cursor(state, stderr)?;
         stderr.flush()?;
     }
-    disable_raw_mode()?;
+    Ok(())
+}
+
+/// Prompt for an API token or password. Input isn't echoed, each character
+/// shows as a `*`.
+pub fn secret_prompt(prompt: &str) -> anyhow::Result<String> {
+    let mut stderr = io::stderr();
+    eprint!("{} ", prompt);
+    stderr.flush()?;
+    let (input_start, input_row) = cursor::position()?;
+    let mut secret = String::new();
+
+    let raw_mode = RawModeGuard::new()?;
+    stderr.execute(cursor::SetCursorStyle::SteadyBar)?;
+    let result = secret_prompt_inner(&mut secret, input_start, input_row, &mut stderr);
+    drop(raw_mode);
     eprintln!();
 
-    Ok(state.line)
+    result.map(|_| secret)
+}
+
+fn secret_prompt_inner(
+    secret: &mut String,
+    input_start: u16,
+    mut input_row: u16,
+    stderr: &mut Stderr,
+) -> anyhow::Result<()> {
+    loop {
+        let (code, modifiers) = match read_event()? {
+            PromptEvent::Key(code, modifiers) => (code, modifiers),
+            PromptEvent::Resize { .. } => {
+                input_row = cursor::position()?.1;
+                continue;
+            }
+        };
+        match (code, modifiers) {
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Err(UserAbort.into()),
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => secret.clear(),
+            (KeyCode::Enter, _) => break,
+            (KeyCode::Backspace, _) => {
+                secret.pop();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => secret.push(c),
+            _ => {}
+        }
+        stderr
+            .queue(cursor::MoveTo(input_start, input_row))?
+            .queue(Clear(ClearType::UntilNewLine))?
+            .queue(style::Print("*".repeat(secret.graphemes(true).count())))?
+            .flush()?;
+    }
+    Ok(())
+}
+
+/// Text being written in `multiline_prompt`. The cursor is a line and a
+/// byte offset into that line.
+struct MultilineState {
+    lines: Vec<String>,
+    row: usize,
+    column: usize,
+}
+
+enum MultilineExit {
+    Done,
+    /// Carry on writing in $EDITOR
+    Editor,
+}
+
+impl MultilineState {
+    fn new(initial: &str) -> Self {
+        let lines: Vec<String> = initial.split('\n').map(String::from).collect();
+        let row = lines.len() - 1;
+        let column = lines[row].len();
+        MultilineState { lines, row, column }
+    }
+
+    fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    fn line(&self) -> &str {
+        &self.lines[self.row]
+    }
+
+    fn insert_char(&mut self, c: char) {
+        let column = self.column;
+        self.lines[self.row].insert(column, c);
+        self.column += c.len_utf8();
+    }
+
+    fn new_line(&mut self) {
+        let column = self.column;
+        let rest = self.lines[self.row].split_off(column);
+        self.row += 1;
+        self.lines.insert(self.row, rest);
+        self.column = 0;
+    }
+
+    /// Deletes the character before the cursor, joining the line onto the
+    /// one above when at its start
+    fn backspace(&mut self) {
+        if self.column > 0 {
+            let end = self.column;
+            self.column = previous_grapheme(self.line(), end);
+            let column = self.column;
+            self.lines[self.row].replace_range(column..end, "");
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.column = self.line().len();
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    fn move_left(&mut self) {
+        if self.column > 0 {
+            self.column = previous_grapheme(self.line(), self.column);
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.column = self.line().len();
+        }
+    }
+
+    fn move_right(&mut self) {
+        if self.column < self.line().len() {
+            self.column = next_grapheme(self.line(), self.column);
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.column = 0;
+        }
+    }
+
+    /// Moves to another line, keeping the column if that line is long enough
+    fn move_to_row(&mut self, row: usize) {
+        let width_before = self.line()[..self.column].width();
+        self.row = row;
+        self.column = fit_width(self.line(), width_before).len();
+    }
+
+    fn move_up(&mut self) {
+        if self.row > 0 {
+            self.move_to_row(self.row - 1);
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.move_to_row(self.row + 1);
+        }
+    }
+
+    /// The cursor's column and its row relative to the first line, counting
+    /// the extra rows long lines wrap onto
+    fn cursor_offset(&self, width: usize) -> (usize, usize) {
+        let rows_before: usize = self.lines[..self.row]
+            .iter()
+            .map(|line| wrapped_rows(line, width))
+            .sum();
+        let column = self.line()[..self.column].width();
+        let wrapped_row = (column / width).min(wrapped_rows(self.line(), width) - 1);
+        let column = (column - wrapped_row * width).min(width - 1);
+        (column, rows_before + wrapped_row)
+    }
+
+    /// Terminal rows the text takes up, counting wrapped rows
+    fn total_rows(&self, width: usize) -> usize {
+        self.lines
+            .iter()
+            .map(|line| wrapped_rows(line, width))
+            .sum()
+    }
+}
+
+/// How many terminal rows a line takes up once it wraps
+fn wrapped_rows(line: &str, width: usize) -> usize {
+    line.width().div_ceil(width).max(1)
+}
+
+fn multiline_handle_key(
+    state: &mut MultilineState,
+    key: KeyCode,
+    modifiers: KeyModifiers,
+) -> anyhow::Result<Option<MultilineExit>> {
+    match (key, modifiers) {
+        (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Err(UserAbort.into()),
+        (KeyCode::Char('d'), KeyModifiers::CONTROL) => return Ok(Some(MultilineExit::Done)),
+        (KeyCode::Char('x'), KeyModifiers::CONTROL) => return Ok(Some(MultilineExit::Editor)),
+        (KeyCode::Char('a'), KeyModifiers::CONTROL) | (KeyCode::Home, _) => state.column = 0,
+        (KeyCode::Char('e'), KeyModifiers::CONTROL) | (KeyCode::End, _) => {
+            state.column = state.line().len()
+        }
+        (KeyCode::Enter, _) => state.new_line(),
+        (KeyCode::Backspace, _) => state.backspace(),
+        (KeyCode::Left, _) => state.move_left(),
+        (KeyCode::Right, _) => state.move_right(),
+        (KeyCode::Up, _) => state.move_up(),
+        (KeyCode::Down, _) => state.move_down(),
+        (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => state.insert_char(c),
+        _ => {}
+    }
+    Ok(None)
+}
+
+/// Redraws the text from `start_row` and puts the cursor back. Returns
+/// where the text starts now, which moves up if printing it scrolled the
+/// terminal.
+fn print_multiline(
+    state: &MultilineState,
+    start_row: u16,
+    stderr: &mut Stderr,
+) -> anyhow::Result<u16> {
+    stderr
+        .queue(cursor::MoveTo(0, start_row))?
+        .queue(Clear(ClearType::FromCursorDown))?
+        .queue(style::Print(state.lines.join("\r\n")))?
+        .flush()?;
+    let (_, last_row) = cursor::position()?;
+    let width = terminal_width();
+    let total_rows = state.total_rows(width);
+    let start_row = last_row.saturating_sub(u16::try_from(total_rows - 1)?);
+    let (column, row) = state.cursor_offset(width);
+    stderr.queue(cursor::MoveTo(
+        u16::try_from(column)?,
+        start_row + u16::try_from(row)?,
+    ))?;
+    Ok(start_row)
+}
+
+fn multiline_prompt_inner(
+    state: &mut MultilineState,
+    start_row: &mut u16,
+    stderr: &mut Stderr,
+) -> anyhow::Result<MultilineExit> {
+    *start_row = print_multiline(state, *start_row, stderr)?;
+    stderr.flush()?;
+    loop {
+        match read_event()? {
+            PromptEvent::Key(code, modifiers) => {
+                if let Some(exit) = multiline_handle_key(state, code, modifiers)? {
+                    return Ok(exit);
+                }
+            }
+            PromptEvent::Resize { .. } => {
+                let (_, cursor_row) = cursor::position()?;
+                let (_, row) = state.cursor_offset(terminal_width());
+                *start_row = cursor_row.saturating_sub(u16::try_from(row)?);
+            }
+        }
+        *start_row = print_multiline(state, *start_row, stderr)?;
+        stderr.flush()?;
+    }
+}
+
+/// Prompt for text that can span lines, eg. a PR body or an LLM prompt.
+/// Enter starts a new line and ctrl-d finishes. Ctrl-x carries on in
+/// $EDITOR for anything longer.
+pub fn multiline_prompt(prompt: &str, initial: &str) -> anyhow::Result<String> {
+    let mut stderr = io::stderr();
+    eprintln!(
+        "{} {}",
+        prompt,
+        "(ctrl-d to finish, ctrl-x for $EDITOR)".attribute(Attribute::Dim)
+    );
+    let mut state = MultilineState::new(initial);
+    let (_, mut start_row) = cursor::position()?;
+
+    let raw_mode = RawModeGuard::new()?;
+    stderr.execute(cursor::SetCursorStyle::SteadyBar)?;
+    let result = multiline_prompt_inner(&mut state, &mut start_row, &mut stderr);
+    let last_row = start_row + u16::try_from(state.total_rows(terminal_width()) - 1)?;
+    stderr.queue(cursor::MoveTo(0, last_row))?;
+    if let Ok(MultilineExit::Editor) = result {
+        // The editor's text is printed instead once it's done
+        stderr
+            .queue(cursor::MoveTo(0, start_row))?
+            .queue(Clear(ClearType::FromCursorDown))?;
+    }
+    stderr.flush()?;
+    drop(raw_mode);
+
+    match result? {
+        MultilineExit::Done => {
+            eprintln!();
+            Ok(state.text())
+        }
+        MultilineExit::Editor => {
+            let text = edit_in_editor(&state.text())?;
+            eprintln!("{}", text);
+            Ok(text)
+        }
+    }
+}
+
+/// Writes `text` to a new file only we can read. The name isn't reused, so
+/// another user can't swap in their own file or a symlink ahead of us.
+fn create_temp_file(text: &str) -> anyhow::Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos();
+    for attempt in 0..10 {
+        let path = env::temp_dir().join(format!("wkfl-{}-{}-{}.md", process::id(), nanos, attempt));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(text.as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    anyhow::bail!("Couldn't create a temporary file to edit")
+}
+
+/// Edits the text in $VISUAL or $EDITOR, falling back to vi, and returns
+/// what was saved
+pub fn edit_in_editor(text: &str) -> anyhow::Result<String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or("vi".to_string());
+    let path = create_temp_file(text)?;
+    // Run through the shell since editors are often set with arguments,
+    // eg. `code --wait`
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let status = status?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", editor, status);
+    }
+    Ok(edited?.trim_end().to_string())
 }
 
 struct SelectionState {
@@ -391,6 +1204,9 @@ struct SelectionState {
     max_index: u16,
     has_options: bool,
     prompt_state: PromptState,
+    /// Indexes of the options marked with tab in a multi-select, None for
+    /// picking one
+    marked: Option<BTreeSet<usize>>,
 }
 
 impl SelectionState {
@@ -402,6 +1218,15 @@ impl SelectionState {
             max_index,
             has_options: true,
             prompt_state: PromptState::new(input_start, input_row),
+            marked: None,
+        }
+    }
+
+    fn toggle_mark(&mut self, index: usize) {
+        if let Some(marked) = &mut self.marked {
+            if !marked.remove(&index) {
+                marked.insert(index);
+            }
         }
     }
 
@@ -426,7 +1251,7 @@ impl SelectionState {
         if self.selected < self.max_index {
             self.selected += 1;
             // - 2 is so the next item is shown and 0 based indexing
-            if self.first_item + self.items_shown - 2 < self.selected
+            if (self.first_item + self.items_shown).saturating_sub(2) < self.selected
                 && self.first_item < self.max_index
             {
                 self.first_item += 1
@@ -434,6 +1259,17 @@ impl SelectionState {
         }
     }
 
+    /// Shows a different number of options, keeping the selected one in
+    /// view
+    fn resize(&mut self, items_shown: u16) {
+        self.items_shown = items_shown;
+        if self.selected < self.first_item {
+            self.first_item = self.selected;
+        } else if self.selected >= self.first_item + items_shown {
+            self.first_item = self.selected + 1 - items_shown;
+        }
+    }
+
     fn previous_item(&mut self) {
         if self.selected > 0 {
             self.selected -= 1;
@@ -467,14 +1303,17 @@ fn select_handle_key(
 
 fn print_options(
     state: &SelectionState,
-    #[allow(clippy::ptr_arg)] options: &Vec<&String>,
+    options: &[(usize, &String)],
     stderr: &mut dyn Write,
 ) -> anyhow::Result<()> {
     stderr.queue(Clear(ClearType::FromCursorDown))?;
     let selected_usize = usize::from(state.selected);
     let first_item = usize::from(state.first_item);
     let items_shown = usize::from(state.items_shown);
-    for (i, option) in options
+    // Leave room for the "> " marker and don't write in the last column,
+    // so options never wrap onto the next row
+    let max_width = terminal_width().saturating_sub(3);
+    for (i, (index, option)) in options
         .iter()
         .skip(first_item)
         .take(items_shown)
@@ -497,7 +1336,17 @@ fn print_options(
                 .queue(style::SetForegroundColor(Color::Reset))?
                 .queue(style::SetAttribute(style::Attribute::Reset))?;
         }
-        stderr.queue(style::Print(&option))?;
+        let mut max_width = max_width;
+        if let Some(marked) = &state.marked {
+            let mark = if marked.contains(index) {
+                "[x] "
+            } else {
+                "[ ] "
+            };
+            stderr.queue(style::Print(mark))?;
+            max_width = max_width.saturating_sub(mark.len());
+        }
+        stderr.queue(style::Print(ellipsize(option, max_width)))?;
     }
     stderr
         .queue(style::SetForegroundColor(Color::Reset))?
@@ -505,6 +1354,77 @@ fn print_options(
     Ok(())
 }
 
+/// The start of `text` that fits in `max_width` columns
+pub fn fit_width(text: &str, max_width: usize) -> &str {
+    let mut width = 0;
+    for (index, grapheme) in text.grapheme_indices(true) {
+        width += grapheme.width();
+        if width > max_width {
+            return &text[..index];
+        }
+    }
+    text
+}
+
+/// Cuts `text` to `max_width` columns, ending with … if it was too long
+fn ellipsize(text: &str, max_width: usize) -> Cow<'_, str> {
+    if text.width() <= max_width {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(format!("{}…", fit_width(text, max_width.saturating_sub(1))))
+}
+
+/// Renders details of the highlighted option in `select_prompt_with_preview`
+pub type Preview<'a> = &'a dyn Fn(&str) -> String;
+
+/// Lines of preview shown below the options, plus one for the divider
+const PREVIEW_HEIGHT: usize = 8;
+
+fn print_preview(text: &str, stderr: &mut dyn Write) -> anyhow::Result<()> {
+    let width = terminal_width();
+    stderr
+        .queue(cursor::MoveToNextLine(1))?
+        .queue(PrintStyledContent(
+            "─"
+                .repeat(width.saturating_sub(1))
+                .attribute(Attribute::Dim),
+        ))?;
+    for line in text.lines().take(PREVIEW_HEIGHT - 1) {
+        // Cut to the width so lines don't wrap and push the list up
+        let line = line.replace('\t', "    ");
+        stderr
+            .queue(cursor::MoveToNextLine(1))?
+            .queue(style::Print(fit_width(&line, width.saturating_sub(1))))?;
+    }
+    Ok(())
+}
+
+fn preview_rows(maybe_preview: Option<Preview>) -> usize {
+    if maybe_preview.is_some() {
+        PREVIEW_HEIGHT
+    } else {
+        0
+    }
+}
+
+fn print_options_and_preview(
+    state: &SelectionState,
+    options: &[(usize, &String)],
+    maybe_preview: Option<Preview>,
+    stderr: &mut dyn Write,
+) -> anyhow::Result<()> {
+    print_options(state, options, stderr)?;
+    if let Some(preview) = maybe_preview {
+        let text = if state.has_options {
+            preview(options[usize::from(state.selected)].1)
+        } else {
+            String::new()
+        };
+        print_preview(&text, stderr)?;
+    }
+    Ok(())
+}
+
 fn calculate_match_score(
     option: &str,
     filter_terms: &[&str],
@@ -520,41 +1440,159 @@ fn calculate_match_score(
     Some(score)
 }
 
-fn filter_options<'a>(filter: &str, options: &'a [String]) -> Vec<&'a String> {
+/// The options matching the filter, best first, with their index in
+/// `options`
+fn filter_options<'a>(filter: &str, options: &'a [String]) -> Vec<(usize, &'a String)> {
     if filter.is_empty() {
-        return options.iter().collect();
+        return options.iter().enumerate().collect();
     }
     let filter_terms: Vec<&str> = filter.split_whitespace().collect();
     let matcher = SkimMatcherV2::default().smart_case();
-    let mut matched: Vec<(i64, &String)> = options
+    let mut matched: Vec<(i64, usize, &String)> = options
         .iter()
-        .filter_map(|option| {
-            calculate_match_score(option, &filter_terms, &matcher).map(|score| (-score, option))
+        .enumerate()
+        .filter_map(|(index, option)| {
+            calculate_match_score(option, &filter_terms, &matcher)
+                .map(|score| (-score, index, option))
         })
         .collect();
     matched.sort();
-    matched.into_iter().map(|(_, option)| option).collect()
+    matched
+        .into_iter()
+        .map(|(_, index, option)| (index, option))
+        .collect()
+}
+
+/// How a selection prompt is shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectLayout {
+    /// Below the prompt, with up to `height` options shown at once
+    Inline { height: usize },
+    /// On the alternate screen, using the whole terminal, for lists with
+    /// hundreds of options
+    Fullscreen,
+}
+
+impl SelectLayout {
+    fn from_config(config: &SelectConfig, num_options: usize) -> Self {
+        match config.fullscreen_over {
+            Some(threshold) if num_options > threshold => SelectLayout::Fullscreen,
+            _ => SelectLayout::Inline {
+                height: config.height,
+            },
+        }
+    }
+
+    /// The layout from the config given to `configure_select`
+    pub fn configured(num_options: usize) -> Self {
+        let config = SELECT_CONFIG.get().cloned().unwrap_or_default();
+        Self::from_config(&config, num_options)
+    }
+}
+
+/// Sets the layout used by selection prompts that don't pick their own
+pub fn configure_select(config: SelectConfig) {
+    let _ = SELECT_CONFIG.set(config);
+}
+
+/// Options that fit on screen along with the prompt line and `other_rows`,
+/// eg. a preview
+fn options_that_fit(
+    num_options: usize,
+    max_shown: usize,
+    other_rows: usize,
+) -> anyhow::Result<usize> {
+    let (_, terminal_rows) = terminal::size()?;
+    let available_rows = usize::from(terminal_rows)
+        .saturating_sub(1 + other_rows)
+        .max(1);
+    Ok(num_options.min(max_shown).min(available_rows))
 }
 
 pub fn select_prompt<'a>(prompt: &str, options: &'a [String]) -> anyhow::Result<&'a str> {
+    let layout = SelectLayout::configured(options.len());
+    Ok(run_select(prompt, options, layout, None, false)?[0])
+}
+
+pub fn select_prompt_with_layout<'a>(
+    prompt: &str,
+    options: &'a [String],
+    layout: SelectLayout,
+) -> anyhow::Result<&'a str> {
+    Ok(run_select(prompt, options, layout, None, false)?[0])
+}
+
+/// `select_prompt` with details of the highlighted option shown below the
+/// list, like fzf's preview window
+pub fn select_prompt_with_preview<'a>(
+    prompt: &str,
+    options: &'a [String],
+    preview: Preview,
+) -> anyhow::Result<&'a str> {
+    let layout = SelectLayout::configured(options.len());
+    Ok(run_select(prompt, options, layout, Some(preview), false)?[0])
+}
+
+/// Pick any number of options, tab marks the highlighted one. If none are
+/// marked it's the highlighted one. Options keep their order.
+pub fn multi_select_prompt_with_layout<'a>(
+    prompt: &str,
+    options: &'a [String],
+    layout: SelectLayout,
+) -> anyhow::Result<Vec<&'a str>> {
+    run_select(prompt, options, layout, None, true)
+}
+
+fn run_select<'a>(
+    prompt: &str,
+    options: &'a [String],
+    layout: SelectLayout,
+    maybe_preview: Option<Preview>,
+    multi: bool,
+) -> anyhow::Result<Vec<&'a str>> {
+    match layout {
+        SelectLayout::Inline { height } => {
+            inline_select_prompt(prompt, options, height, maybe_preview, multi)
+        }
+        SelectLayout::Fullscreen => fullscreen_select_prompt(prompt, options, maybe_preview, multi),
+    }
+}
+
+fn inline_select_prompt<'a>(
+    prompt: &str,
+    options: &'a [String],
+    height: usize,
+    maybe_preview: Option<Preview>,
+    multi: bool,
+) -> anyhow::Result<Vec<&'a str>> {
     let mut stderr = io::stderr();
     eprint!("{} ", prompt);
     stderr.flush()?;
 
-    let items_shown = MAX_OPTIONS_SHOWN.min(options.len());
-    let input_start = u16::try_from(prompt.len() + 1)?;
+    let preview_rows = preview_rows(maybe_preview);
+    let items_shown = options_that_fit(options.len(), height.max(1), preview_rows)?;
+    let input_start = u16::try_from(prompt.width() + 1)?;
     let max_items = u16::try_from(options.len())? - 1;
     let mut state = SelectionState::new(u16::try_from(items_shown)?, input_start, 0, max_items);
+    state.marked = multi.then(BTreeSet::new);
 
     // Make room for the options to be printed and return to input line
-    eprint!("{}", "\n".repeat(items_shown));
-    stderr.queue(cursor::MoveUp(state.items_shown))?;
+    let rows_below = items_shown + preview_rows;
+    eprint!("{}", "\n".repeat(rows_below));
+    stderr.queue(cursor::MoveUp(u16::try_from(rows_below)?))?;
 
-    enable_raw_mode()?;
+    let raw_mode = RawModeGuard::new()?;
 
-    let result = select_prompt_inner(prompt, options, &mut state, &mut stderr);
+    let result = select_prompt_inner(
+        prompt,
+        options,
+        height.max(1),
+        maybe_preview,
+        &mut state,
+        &mut stderr,
+    );
 
-    disable_raw_mode()?;
+    drop(raw_mode);
 
     stderr
         // Leave the prompt line, but clear the rest
@@ -565,29 +1603,124 @@ pub fn select_prompt<'a>(prompt: &str, options: &'a [String]) -> anyhow::Result<
     result
 }
 
+fn fullscreen_select_prompt<'a>(
+    prompt: &str,
+    options: &'a [String],
+    maybe_preview: Option<Preview>,
+    multi: bool,
+) -> anyhow::Result<Vec<&'a str>> {
+    let mut stderr = io::stderr();
+    let preview_rows = preview_rows(maybe_preview);
+    let items_shown = options_that_fit(options.len(), usize::MAX, preview_rows)?;
+    let input_start = u16::try_from(prompt.width() + 1)?;
+    let max_items = u16::try_from(options.len())? - 1;
+    let mut state = SelectionState::new(u16::try_from(items_shown)?, input_start, 0, max_items);
+    state.marked = multi.then(BTreeSet::new);
+
+    // Dropping the guard also leaves the alternate screen
+    let raw_mode = RawModeGuard::new()?;
+    stderr
+        .execute(EnterAlternateScreen)?
+        .execute(cursor::MoveTo(0, 0))?;
+    IN_ALTERNATE_SCREEN.store(true, Ordering::SeqCst);
+    eprint!("{} ", prompt);
+    stderr.flush()?;
+
+    let result = select_prompt_inner(
+        prompt,
+        options,
+        usize::MAX,
+        maybe_preview,
+        &mut state,
+        &mut stderr,
+    );
+
+    drop(raw_mode);
+    // The selection was shown on the alternate screen, show it again where
+    // the prompt was run from
+    if let Ok(selected) = &result {
+        eprintln!(
+            "{}",
+            format!("{} {}", prompt, selected.join(", ")).with(Color::Cyan)
+        );
+    }
+    result
+}
+
+/// Fits the options to the resized terminal and redraws the prompt, the
+/// rest is redrawn after every event
+fn resize_select(
+    prompt: &str,
+    num_options: usize,
+    max_shown: usize,
+    maybe_preview: Option<Preview>,
+    terminal_rows: u16,
+    state: &mut SelectionState,
+    stderr: &mut Stderr,
+) -> anyhow::Result<()> {
+    let preview_rows = preview_rows(maybe_preview);
+    let items_shown = options_that_fit(num_options, max_shown, preview_rows)?;
+    state.resize(u16::try_from(items_shown)?);
+
+    // The terminal may have rewrapped lines, moving the input. If the
+    // options no longer fit below it scroll up to make room.
+    let (_, cursor_row) = cursor::position()?;
+    let rows_needed = u16::try_from(1 + items_shown + preview_rows)?;
+    let overflow = (cursor_row + rows_needed).saturating_sub(terminal_rows);
+    if overflow > 0 {
+        stderr.queue(terminal::ScrollUp(overflow))?;
+    }
+    state.prompt_state.input_row = cursor_row.saturating_sub(overflow);
+    stderr
+        .queue(cursor::MoveTo(0, state.prompt_state.input_row))?
+        .queue(Clear(ClearType::FromCursorDown))?
+        .queue(style::Print(format!("{} ", prompt)))?;
+    Ok(())
+}
+
 /// This contains all the code that prints content past the input prompt. We
 /// split this out, so that we properly clean up  and clear all the lines after
 /// the input prompt
 fn select_prompt_inner<'a>(
     prompt: &str,
     options: &'a [String],
+    max_shown: usize,
+    maybe_preview: Option<Preview>,
     state: &mut SelectionState,
     stderr: &mut Stderr,
-) -> anyhow::Result<&'a str> {
+) -> anyhow::Result<Vec<&'a str>> {
+    state.prompt_state.history = prompt_history::load(prompt);
     let (_, position_row) = cursor::position()?;
     // Move from prompt to first line of options
     stderr.queue(cursor::MoveToNextLine(1))?;
-    print_options(state, &options.iter().collect(), stderr)?;
+    print_options_and_preview(state, &filter_options("", options), maybe_preview, stderr)?;
     state.prompt_state.input_row = position_row;
     update_cursor(&state.prompt_state, stderr)?;
     stderr.flush()?;
 
-    while let Event::Key(KeyEvent {
-        code, modifiers, ..
-    }) = event::read()?
-    {
-        if select_handle_key(state, code, modifiers)? {
-            break;
+    loop {
+        match read_event()? {
+            PromptEvent::Key(KeyCode::Tab, KeyModifiers::NONE) if state.marked.is_some() => {
+                let filtered_options = filter_options(&state.prompt_state.line, options);
+                if let Some(&(index, _)) = filtered_options.get(usize::from(state.selected)) {
+                    state.toggle_mark(index);
+                    state.next_item();
+                }
+            }
+            PromptEvent::Key(code, modifiers) => {
+                if select_handle_key(state, code, modifiers)? {
+                    break;
+                }
+            }
+            PromptEvent::Resize { rows } => resize_select(
+                prompt,
+                options.len(),
+                max_shown,
+                maybe_preview,
+                rows,
+                state,
+                stderr,
+            )?,
         }
 
         let filtered_options = filter_options(&state.prompt_state.line, options);
@@ -600,18 +1733,27 @@ fn select_prompt_inner<'a>(
 
         print_prompt_input(&state.prompt_state, stderr)?;
         stderr.queue(cursor::MoveToNextLine(1))?;
-        print_options(state, &filtered_options, stderr)?;
+        print_options_and_preview(state, &filtered_options, maybe_preview, stderr)?;
         update_cursor(&state.prompt_state, stderr)?;
         stderr.flush()?;
     }
 
     let filtered_options = filter_options(&state.prompt_state.line, options);
-    let result = filtered_options[usize::from(state.selected)];
-    let result_output = format!("{} {}\n", prompt, &result);
+    let result: Vec<&str> = match &state.marked {
+        Some(marked) if !marked.is_empty() => marked
+            .iter()
+            .map(|&index| options[index].as_str())
+            .collect(),
+        _ => vec![filtered_options[usize::from(state.selected)].1],
+    };
+    let result_output = format!("{} {}\n", prompt, result.join(", "));
     stderr
         .queue(cursor::MoveTo(0, state.prompt_state.input_row))?
         .queue(PrintStyledContent(result_output.with(Color::Cyan)))?
         .flush()?;
+    if let [selected] = result[..] {
+        prompt_history::remember(prompt, selected);
+    }
     Ok(result)
 }
 
@@ -640,42 +1782,48 @@ pub fn boolean_prompt(prompt: &str, default: bool) -> anyhow::Result<bool> {
 
     eprint!("{} ", prompt);
 
-    enable_raw_mode()?;
+    let raw_mode = RawModeGuard::new()?;
     stderr.queue(cursor::SavePosition)?.queue(cursor::Hide)?;
-    print_boolean_toogle(state, &mut stderr)?;
+
+    let result = boolean_prompt_inner(&mut state, &mut stderr);
+
+    // Dropping the guard also shows the cursor again
+    drop(raw_mode);
+    eprintln!();
+    result.map(|_| state)
+}
+
+fn boolean_prompt_inner(state: &mut bool, stderr: &mut Stderr) -> anyhow::Result<()> {
+    print_boolean_toogle(*state, stderr)?;
     stderr.flush()?;
 
-    while let Event::Key(KeyEvent {
-        code, modifiers, ..
-    }) = event::read()?
-    {
+    loop {
+        let PromptEvent::Key(code, modifiers) = read_event()? else {
+            continue;
+        };
         match (code, modifiers) {
             (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                bail!("ctrl-c sent");
+                return Err(UserAbort.into());
             }
             (code, KeyModifiers::NONE | KeyModifiers::SHIFT) => match code {
                 KeyCode::Enter => {
                     break;
                 }
                 KeyCode::Char('l' | 'f' | 'n') => {
-                    state = false;
+                    *state = false;
                 }
                 KeyCode::Char('h' | 't' | 'y') => {
-                    state = true;
+                    *state = true;
                 }
                 _ => {}
             },
             _ => {}
         }
         stderr.queue(cursor::RestorePosition)?;
-        print_boolean_toogle(state, &mut stderr)?;
+        print_boolean_toogle(*state, stderr)?;
         stderr.flush()?;
     }
-
-    stderr.execute(cursor::Show)?;
-    disable_raw_mode()?;
-    eprintln!();
-    Ok(state)
+    Ok(())
 }
 
 use std::fmt;
@@ -701,3 +1849,249 @@ impl fmt::Display for Link<'_> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    use super::{
+        ellipsize, filter_options, handle_key, MultilineState, PromptState, SelectLayout,
+        SelectionState,
+    };
+    use wkfl_core::config::SelectConfig;
+
+    fn press(state: &mut PromptState, keys: &str) {
+        for c in keys.chars() {
+            let key = match c {
+                '\x1b' => KeyCode::Esc,
+                '\x7f' => KeyCode::Backspace,
+                c => KeyCode::Char(c),
+            };
+            handle_key(state, key, KeyModifiers::NONE).unwrap();
+        }
+    }
+
+    fn state_with_line(line: &str) -> PromptState {
+        let mut state = PromptState::new(0, 0);
+        state.line = line.to_string();
+        state.cursor = line.len();
+        state
+    }
+
+    #[test]
+    fn test_select_layout_from_config() {
+        let mut config = SelectConfig::default();
+        assert_eq!(
+            SelectLayout::from_config(&config, 500),
+            SelectLayout::Inline { height: 10 }
+        );
+        config.fullscreen_over = Some(100);
+        assert_eq!(
+            SelectLayout::from_config(&config, 100),
+            SelectLayout::Inline { height: 10 }
+        );
+        assert_eq!(
+            SelectLayout::from_config(&config, 101),
+            SelectLayout::Fullscreen
+        );
+    }
+
+    #[test]
+    fn test_kill_word_before_cursor() {
+        let mut state = state_with_line("git commit  ");
+        state.kill_word_before_cursor();
+        assert_eq!(state.line, "git ");
+        assert_eq!(state.cursor, 4);
+        state.kill_word_before_cursor();
+        assert_eq!(state.line, "");
+    }
+
+    #[test]
+    fn test_kill_to_start_and_yank() {
+        let mut state = state_with_line("hello world");
+        state.cursor = 6;
+        state.kill_to_start();
+        assert_eq!(state.line, "world");
+        state.move_to_end();
+        state.yank();
+        assert_eq!(state.line, "worldhello ");
+        assert_eq!(state.cursor, state.line.len());
+    }
+
+    #[test]
+    fn test_multiline_new_line_and_backspace() {
+        let mut state = MultilineState::new("ab");
+        state.move_left();
+        state.new_line();
+        assert_eq!(state.lines, vec!["a", "b"]);
+        assert_eq!((state.row, state.column), (1, 0));
+        state.backspace();
+        assert_eq!(state.text(), "ab");
+        assert_eq!((state.row, state.column), (0, 1));
+    }
+
+    #[test]
+    fn test_multiline_move_up_keeps_column() {
+        let mut state = MultilineState::new("hé\nlonger line");
+        state.move_up();
+        assert_eq!((state.row, state.column), (0, "hé".len()));
+        state.move_down();
+        assert_eq!((state.row, state.column), (1, 2));
+        state.move_left();
+        state.move_left();
+        state.move_left();
+        assert_eq!((state.row, state.column), (0, "hé".len()));
+    }
+
+    #[test]
+    fn test_undo_groups_inserts() {
+        let mut state = PromptState::new(0, 0);
+        press(&mut state, "hello world\x1b");
+        press(&mut state, "bdw");
+        assert_eq!(state.line, "hello ");
+        press(&mut state, "u");
+        assert_eq!(state.line, "hello world");
+        press(&mut state, "u");
+        assert_eq!(state.line, "");
+        handle_key(&mut state, KeyCode::Char('r'), KeyModifiers::CONTROL).unwrap();
+        assert_eq!(state.line, "hello world");
+    }
+
+    #[test]
+    fn test_paste_deleted_text() {
+        let mut state = state_with_line("abc");
+        state.normal_mode();
+        state.cursor = 0;
+        press(&mut state, "xp");
+        assert_eq!(state.line, "bac");
+        assert_eq!(state.cursor, 1);
+        state.cursor = 0;
+        press(&mut state, "P");
+        assert_eq!(state.line, "abac");
+    }
+
+    #[test]
+    fn test_repeat_change() {
+        let mut state = state_with_line("one two three");
+        state.normal_mode();
+        state.cursor = 0;
+        press(&mut state, "ceuno\x1b");
+        assert_eq!(state.line, "uno two three");
+        press(&mut state, "w.");
+        assert_eq!(state.line, "uno uno three");
+        press(&mut state, "w.");
+        assert_eq!(state.line, "uno uno uno");
+    }
+
+    #[test]
+    fn test_history_keeps_draft() {
+        let mut state = state_with_line("dra");
+        state.history = vec!["newest".to_string(), "oldest".to_string()];
+        for _ in 0..3 {
+            handle_key(&mut state, KeyCode::Up, KeyModifiers::NONE).unwrap();
+        }
+        assert_eq!(state.line, "oldest");
+        handle_key(&mut state, KeyCode::Down, KeyModifiers::NONE).unwrap();
+        assert_eq!(state.line, "newest");
+        handle_key(&mut state, KeyCode::Down, KeyModifiers::NONE).unwrap();
+        assert_eq!(state.line, "dra");
+        assert_eq!(state.cursor, 3);
+    }
+
+    #[test]
+    fn test_reverse_search() {
+        let mut state = PromptState::new(0, 0);
+        state.history = vec![
+            "feature/login".to_string(),
+            "fix/typo".to_string(),
+            "feature/logout".to_string(),
+        ];
+        handle_key(&mut state, KeyCode::Char('r'), KeyModifiers::CONTROL).unwrap();
+        press(&mut state, "log");
+        assert_eq!(state.line, "feature/login");
+        assert_eq!(state.cursor, "feature/".len());
+        handle_key(&mut state, KeyCode::Char('r'), KeyModifiers::CONTROL).unwrap();
+        assert_eq!(state.line, "feature/logout");
+        press(&mut state, "\x1b");
+        assert_eq!(state.search, None);
+        press(&mut state, "!");
+        assert_eq!(state.line, "feature/!logout");
+    }
+
+    #[test]
+    fn test_ellipsize() {
+        assert_eq!(ellipsize("short", 5), "short");
+        assert_eq!(ellipsize("much longer", 5), "much…");
+        assert_eq!(ellipsize("héllo wörld", 6), "héllo…");
+    }
+
+    #[test]
+    fn test_resize_keeps_selected_in_view() {
+        let mut state = SelectionState::new(10, 0, 0, 19);
+        for _ in 0..8 {
+            state.next_item();
+        }
+        state.resize(3);
+        assert_eq!((state.selected, state.first_item), (8, 6));
+        state.resize(10);
+        assert_eq!((state.selected, state.first_item), (8, 6));
+        state.previous_item();
+        state.resize(1);
+        assert_eq!((state.selected, state.first_item), (7, 7));
+        state.next_item();
+        assert_eq!((state.selected, state.first_item), (8, 8));
+    }
+
+    #[test]
+    fn test_marks_duplicate_options_separately() {
+        let options = vec!["main".to_string(), "dev".to_string(), "main".to_string()];
+        let filtered = filter_options("main", &options);
+        assert_eq!(
+            filtered.iter().map(|&(index, _)| index).collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        let mut state = SelectionState::new(10, 0, 0, 2);
+        state.marked = Some(Default::default());
+        state.toggle_mark(filtered[1].0);
+        assert_eq!(state.marked, Some([2].into()));
+    }
+
+    #[test]
+    fn test_edit_by_grapheme() {
+        // The é is an e and a combining accent
+        let mut state = state_with_line("cafe\u{301} 日本");
+        press(&mut state, "\x7f");
+        assert_eq!(state.line, "cafe\u{301} 日");
+        press(&mut state, "\x1bbb");
+        assert_eq!(state.cursor, 0);
+        press(&mut state, "ex");
+        assert_eq!(state.line, "caf 日");
+        press(&mut state, "P");
+        assert_eq!(state.line, "cafe\u{301} 日");
+        assert_eq!(state.cursor, 3);
+        press(&mut state, "wx");
+        assert_eq!(state.line, "cafe\u{301} ");
+    }
+
+    #[test]
+    fn test_multiline_cursor_on_wrapped_lines() {
+        // The first line wraps onto a second row at 10 columns wide
+        let mut state = MultilineState::new("0123456789abc\n日本");
+        assert_eq!(state.cursor_offset(10), (4, 2));
+        assert_eq!(state.total_rows(10), 3);
+        state.row = 0;
+        state.column = 12;
+        assert_eq!(state.cursor_offset(10), (2, 1));
+        state.column = 3;
+        assert_eq!(state.cursor_offset(10), (3, 0));
+    }
+
+    #[test]
+    fn test_kill_to_end() {
+        let mut state = state_with_line("hello world");
+        state.cursor = 5;
+        state.kill_to_end();
+        assert_eq!(state.line, "hello");
+        assert_eq!(state.kill_ring, vec![" world"]);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+
+use wkfl_core::llm;
+
+/// Lines of the diff, numbered with their line in the new file so the
+/// model can give line references without counting through hunks
+pub fn number_lines(patch: &str) -> String {
+    let mut numbered = String::new();
+    let mut new_line = 0;
+    // File headers only come before the first hunk, inside one a `+++`
+    // line is an added line starting with `++`
+    let mut in_hunk = false;
+    for line in patch.lines() {
+        if let Some(hunk) = line.strip_prefix("@@ ") {
+            new_line = hunk_new_start(hunk).unwrap_or(0);
+            in_hunk = true;
+            numbered.push_str(&format!("      {}\n", line));
+            continue;
+        }
+        if line.starts_with("diff --git ") {
+            in_hunk = false;
+        }
+        match line.chars().next() {
+            Some('+' | ' ') if in_hunk => {
+                numbered.push_str(&format!("{:>5} {}\n", new_line, line));
+                new_line += 1;
+            }
+            _ => numbered.push_str(&format!("      {}\n", line)),
+        }
+    }
+    numbered
+}
+
+/// Start line in the new file from a hunk header, eg. `-1,4 +2,5 @@`
+fn hunk_new_start(hunk: &str) -> Option<u32> {
+    let new_range = hunk.split_whitespace().find(|part| part.starts_with('+'))?;
+    new_range[1..].split(',').next()?.parse().ok()
+}
+
+/// Splits a multi-file patch into one patch per file
+pub fn split_by_file(patch: &str) -> Vec<String> {
+    let mut files: Vec<String> = vec![];
+    for line in patch.lines() {
+        if line.starts_with("diff --git ") || files.is_empty() {
+            files.push(String::new());
+        }
+        let file = files.last_mut().expect("A file was pushed above");
+        file.push_str(line);
+        file.push('\n');
+    }
+    files
+}
+
+/// Groups whole files into chunks of at most `budget` tokens. Files too
+/// big for a chunk on their own are truncated.
+pub fn chunk_files(files: Vec<String>, budget: u64) -> Vec<String> {
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+    for mut file in files {
+        if llm::estimate_tokens(&file) > budget {
+            file = llm::truncate_to_tokens(&file, budget);
+        }
+        if !chunk.is_empty() && llm::estimate_tokens(&chunk) + llm::estimate_tokens(&file) > budget
+        {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        chunk.push_str(&file);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Findings by file then line, from lines like `src/main.rs:12: comment`.
+/// Anything else in the response is ignored.
+pub fn parse_findings(response: &str, findings: &mut BTreeMap<String, Vec<(u32, String)>>) {
+    for line in response.lines() {
+        let line = line.trim().trim_start_matches("- ");
+        let mut parts = line.splitn(3, ':');
+        let (Some(path), Some(line_number), Some(comment)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let Ok(line_number) = line_number.trim().parse() else {
+            continue;
+        };
+        findings
+            .entry(path.trim().to_string())
+            .or_default()
+            .push((line_number, comment.trim().to_string()));
+    }
+    for file_findings in findings.values_mut() {
+        file_findings.sort_by_key(|(line_number, _)| *line_number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{chunk_files, number_lines, parse_findings, split_by_file};
+
+    const PATCH: &str = "diff --git a/a.rs b/a.rs
+--- a/a.rs
++++ b/a.rs
+@@ -1,2 +1,3 @@
+ fn a() {}
+-fn b() {}
++fn b() -> u8 { 1 }
++fn c() {}
+diff --git a/b.rs b/b.rs
+--- a/b.rs
++++ b/b.rs
+@@ -10 +10 @@
+-old
++new
+";
+
+    #[test]
+    fn test_number_lines() {
+        let numbered = number_lines(PATCH);
+        assert!(numbered.contains("    1  fn a() {}\n"));
+        assert!(numbered.contains("      -fn b() {}\n"));
+        assert!(numbered.contains("    2 +fn b() -> u8 { 1 }\n"));
+        assert!(numbered.contains("    3 +fn c() {}\n"));
+        assert!(numbered.contains("   10 +new\n"));
+    }
+
+    #[test]
+    fn test_number_added_lines_like_headers() {
+        let patch = "--- a/c.c\n+++ b/c.c\n@@ -1,1 +1,2 @@\n x;\n+++i;\n";
+        let numbered = number_lines(patch);
+        assert!(numbered.contains("      +++ b/c.c\n"));
+        assert!(numbered.contains("    2 +++i;\n"));
+    }
+
+    #[test]
+    fn test_split_and_chunk() {
+        let files = split_by_file(PATCH);
+        assert_eq!(files.len(), 2);
+        assert!(files[1].starts_with("diff --git a/b.rs"));
+        assert_eq!(chunk_files(files.clone(), 1000).len(), 1);
+        assert_eq!(chunk_files(files, 50).len(), 2);
+    }
+
+    #[test]
+    fn test_parse_findings() {
+        let mut findings = BTreeMap::new();
+        parse_findings(
+            "Here's what I found:\n- a.rs:3: c is unused\na.rs:2: returns a magic number\nNo other issues.",
+            &mut findings,
+        );
+        assert_eq!(
+            findings["a.rs"],
+            vec![
+                (2, "returns a magic number".to_string()),
+                (3, "c is unused".to_string())
+            ]
+        );
+        assert_eq!(findings.len(), 1);
+    }
+}
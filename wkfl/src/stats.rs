@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+
+use wkfl_core::config::wkfl_state_dir;
+
+const STATS_LIMIT: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Invocation {
+    pub command: String,
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+}
+
+fn stats_file() -> anyhow::Result<PathBuf> {
+    Ok(wkfl_state_dir()?.join("stats.jsonl"))
+}
+
+/// Full subcommand path that was run, eg. "notes today"
+pub fn command_name(matches: &ArgMatches) -> String {
+    let mut names = vec![];
+    let mut current = matches;
+    while let Some((name, sub_matches)) = current.subcommand() {
+        names.push(name);
+        current = sub_matches;
+    }
+    names.join(" ")
+}
+
+pub fn record(command: &str, duration: Duration, success: bool) -> anyhow::Result<()> {
+    let started_at = SystemTime::now()
+        .checked_sub(duration)
+        .unwrap_or(UNIX_EPOCH)
+        .duration_since(UNIX_EPOCH)?
+        .as_secs();
+    let invocation = Invocation {
+        command: command.to_string(),
+        started_at,
+        duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        success,
+    };
+    let path = stats_file()?;
+    fs::create_dir_all(path.parent().expect("Stats file is in a directory"))?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(&invocation)?)?;
+    Ok(())
+}
+
+fn read_invocations() -> anyhow::Result<Vec<Invocation>> {
+    let path = stats_file()?;
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        // Skip lines we can't parse rather than failing on a partial write
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct CommandSummary {
+    count: u64,
+    failures: u64,
+    total_ms: u64,
+    max_ms: u64,
+}
+
+impl CommandSummary {
+    fn average_ms(&self) -> u64 {
+        self.total_ms / self.count.max(1)
+    }
+}
+
+fn summarize(invocations: &[Invocation]) -> HashMap<&str, CommandSummary> {
+    let mut summaries: HashMap<&str, CommandSummary> = HashMap::new();
+    for invocation in invocations {
+        let summary = summaries.entry(&invocation.command).or_default();
+        summary.count += 1;
+        if !invocation.success {
+            summary.failures += 1;
+        }
+        summary.total_ms += invocation.duration_ms;
+        summary.max_ms = summary.max_ms.max(invocation.duration_ms);
+    }
+    summaries
+}
+
+pub fn print_stats() -> anyhow::Result<()> {
+    let invocations = read_invocations()?;
+    if invocations.is_empty() {
+        println!("No stats recorded yet. Set `record_stats = true` in your config to enable.");
+        return Ok(());
+    }
+    let summaries = summarize(&invocations);
+    let mut by_count: Vec<(&&str, &CommandSummary)> = summaries.iter().collect();
+
+    by_count.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+    println!("Most used commands:");
+    println!("{:<24} {:>6} {:>9}", "command", "runs", "failures");
+    for (command, summary) in by_count.iter().take(STATS_LIMIT) {
+        println!(
+            "{:<24} {:>6} {:>9}",
+            command, summary.count, summary.failures
+        );
+    }
+
+    by_count.sort_by(|a, b| b.1.average_ms().cmp(&a.1.average_ms()).then(a.0.cmp(b.0)));
+    println!("\nSlowest commands:");
+    println!("{:<24} {:>9} {:>9}", "command", "avg (ms)", "max (ms)");
+    for (command, summary) in by_count.iter().take(STATS_LIMIT) {
+        println!(
+            "{:<24} {:>9} {:>9}",
+            command,
+            summary.average_ms(),
+            summary.max_ms
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{summarize, CommandSummary, Invocation};
+
+    fn invocation(command: &str, duration_ms: u64, success: bool) -> Invocation {
+        Invocation {
+            command: command.to_string(),
+            started_at: 0,
+            duration_ms,
+            success,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let invocations = vec![
+            invocation("chat", 1000, true),
+            invocation("chat", 3000, false),
+            invocation("notes today", 10, true),
+        ];
+        let summaries = summarize(&invocations);
+        assert_eq!(
+            summaries["chat"],
+            CommandSummary {
+                count: 2,
+                failures: 1,
+                total_ms: 4000,
+                max_ms: 3000,
+            }
+        );
+        assert_eq!(summaries["chat"].average_ms(), 2000);
+        assert_eq!(summaries["notes today"].count, 1);
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crossterm::style::Stylize;
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER_MARKER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+/// A change proposed by an LLM, either as a unified diff or as search and
+/// replace blocks:
+///
+/// ````text
+/// src/main.rs
+/// ```rust
+/// <<<<<<< SEARCH
+/// fn old() {}
+/// =======
+/// fn new() {}
+/// >>>>>>> REPLACE
+/// ```
+/// ````
+#[derive(Debug, PartialEq)]
+pub enum Suggestion {
+    Patch(String),
+    Edits(Vec<Edit>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Edit {
+    pub path: PathBuf,
+    /// Empty to create a new file
+    pub search: String,
+    pub replace: String,
+}
+
+/// A file's contents before and after applying the edits to it
+pub struct FileChange {
+    pub path: PathBuf,
+    pub old: String,
+    pub new: String,
+}
+
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+/// Whether `line` closes the code block `opening` started. It needs the
+/// same indent and at least as many backticks with nothing after them, so
+/// a diff context line like " ```rust" doesn't end a patch early.
+fn closes_fence(opening: &str, line: &str) -> bool {
+    let indent = opening.len() - opening.trim_start().len();
+    let backticks = opening[indent..].len() - opening[indent..].trim_start_matches('`').len();
+    let (line_indent, rest) = line.split_at(line.len() - line.trim_start().len());
+    let rest = rest.trim_end();
+    line_indent == &opening[..indent] && rest.len() >= backticks && rest.chars().all(|c| c == '`')
+}
+
+/// Edits are only allowed to files inside the repo
+fn check_edit_path(path: &Path) -> anyhow::Result<()> {
+    let inside_repo = path.components().next().is_some()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !inside_repo {
+        anyhow::bail!(
+            "Refusing to edit {}, paths need to be relative and inside the repo",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+pub fn parse_suggestion(text: &str) -> anyhow::Result<Suggestion> {
+    if text.lines().any(|line| line.trim_end() == SEARCH_MARKER) {
+        return Ok(Suggestion::Edits(parse_edits(text)?));
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| line.starts_with("diff --git ") || line.starts_with("--- "))
+        .ok_or(anyhow::anyhow!(
+            "No unified diff or SEARCH/REPLACE blocks in the suggestion"
+        ))?;
+    // Only a fence before the diff starts a code block it can end with
+    let opening_fence = lines[..start]
+        .iter()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .filter(|line| is_fence(line));
+    let patch: Vec<&str> = lines[start..]
+        .iter()
+        .take_while(|line| !opening_fence.is_some_and(|opening| closes_fence(opening, line)))
+        .copied()
+        .collect();
+    Ok(Suggestion::Patch(format!("{}\n", patch.join("\n"))))
+}
+
+fn parse_edits(text: &str) -> anyhow::Result<Vec<Edit>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut edits = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].trim_end() != SEARCH_MARKER {
+            i += 1;
+            continue;
+        }
+        // The path is the closest line before the block, skipping the fence.
+        // Blocks straight after another one are for the same file.
+        let path = match lines[..i]
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty() && !is_fence(line))
+        {
+            Some(REPLACE_MARKER) => edits
+                .last()
+                .map(|edit: &Edit| edit.path.clone())
+                .ok_or_else(|| anyhow::anyhow!("SEARCH block without a file path before it"))?,
+            Some(line) => PathBuf::from(line.trim_matches('`')),
+            None => anyhow::bail!("SEARCH block without a file path before it"),
+        };
+        check_edit_path(&path)?;
+        let divider = find_marker(&lines, i + 1, DIVIDER_MARKER)?;
+        let end = find_marker(&lines, divider + 1, REPLACE_MARKER)?;
+        edits.push(Edit {
+            path,
+            search: join_lines(&lines[i + 1..divider]),
+            replace: join_lines(&lines[divider + 1..end]),
+        });
+        i = end + 1;
+    }
+    Ok(edits)
+}
+
+fn find_marker(lines: &[&str], from: usize, marker: &str) -> anyhow::Result<usize> {
+    lines[from..]
+        .iter()
+        .position(|line| line.trim_end() == marker)
+        .map(|offset| from + offset)
+        .ok_or(anyhow::anyhow!("SEARCH block is missing `{}`", marker))
+}
+
+fn join_lines(lines: &[&str]) -> String {
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Applies the edits in memory, checking each search text matches the
+/// working tree exactly once
+pub fn preview_edits(edits: &[Edit], root: &Path) -> anyhow::Result<Vec<FileChange>> {
+    let mut contents: BTreeMap<&Path, (String, String)> = BTreeMap::new();
+    for edit in edits {
+        if !contents.contains_key(edit.path.as_path()) {
+            let full_path = root.join(&edit.path);
+            let old = if full_path.exists() {
+                fs::read_to_string(&full_path)?
+            } else if edit.search.is_empty() {
+                String::new()
+            } else {
+                anyhow::bail!("{} doesn't exist", edit.path.display());
+            };
+            contents.insert(&edit.path, (old.clone(), old));
+        }
+        let (_, new) = contents
+            .get_mut(edit.path.as_path())
+            .expect("Inserted above");
+        if edit.search.is_empty() {
+            if !new.is_empty() {
+                anyhow::bail!(
+                    "Empty SEARCH block for {}, which already has contents",
+                    edit.path.display()
+                );
+            }
+            *new = edit.replace.clone();
+            continue;
+        }
+        match new.matches(&edit.search).count() {
+            1 => *new = new.replacen(&edit.search, &edit.replace, 1),
+            0 => anyhow::bail!(
+                "SEARCH text isn't in {}:\n{}",
+                edit.path.display(),
+                edit.search
+            ),
+            count => anyhow::bail!(
+                "SEARCH text matches {} places in {}, it needs to be unique:\n{}",
+                count,
+                edit.path.display(),
+                edit.search
+            ),
+        }
+    }
+    Ok(contents
+        .into_iter()
+        .map(|(path, (old, new))| FileChange {
+            path: path.to_path_buf(),
+            old,
+            new,
+        })
+        .collect())
+}
+
+/// Colours added lines green and removed lines red
+pub fn colorize_diff(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            if line.starts_with("+++") || line.starts_with("---") {
+                line.bold().to_string()
+            } else if line.starts_with('+') {
+                line.green().to_string()
+            } else if line.starts_with('-') {
+                line.red().to_string()
+            } else if line.starts_with("@@") {
+                line.cyan().to_string()
+            } else {
+                line.to_string()
+            }
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{parse_suggestion, preview_edits, Edit, Suggestion};
+
+    #[test]
+    fn test_parse_search_replace() {
+        let text = "Change the greeting:\n\nsrc/main.rs\n```rust\n<<<<<<< SEARCH\nprintln!(\"hi\");\n=======\nprintln!(\"hello\");\n>>>>>>> REPLACE\n```\n```rust\n<<<<<<< SEARCH\n=======\nfn new() {}\n>>>>>>> REPLACE\n```\n";
+        assert_eq!(
+            parse_suggestion(text).unwrap(),
+            Suggestion::Edits(vec![
+                Edit {
+                    path: PathBuf::from("src/main.rs"),
+                    search: "println!(\"hi\");\n".to_string(),
+                    replace: "println!(\"hello\");\n".to_string(),
+                },
+                Edit {
+                    path: PathBuf::from("src/main.rs"),
+                    search: String::new(),
+                    replace: "fn new() {}\n".to_string(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_fenced_patch() {
+        let text = "Try this:\n```diff\n--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-a\n+b\n```\nThanks";
+        assert_eq!(
+            parse_suggestion(text).unwrap(),
+            Suggestion::Patch("--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-a\n+b\n".to_string())
+        );
+        assert!(parse_suggestion("Just prose").is_err());
+    }
+
+    #[test]
+    fn test_patch_keeps_fence_context_lines() {
+        let text = "```diff\n--- a/README.md\n+++ b/README.md\n@@ -1,3 +1,3 @@\n ```rust\n-a\n+b\n ```\n```\n";
+        assert_eq!(
+            parse_suggestion(text).unwrap(),
+            Suggestion::Patch(
+                "--- a/README.md\n+++ b/README.md\n@@ -1,3 +1,3 @@\n ```rust\n-a\n+b\n ```\n"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_edits_outside_repo_rejected() {
+        for path in ["/etc/passwd", "../../.bashrc", "src/../../x"] {
+            let text = format!("{}\n<<<<<<< SEARCH\n=======\nx\n>>>>>>> REPLACE\n", path);
+            assert!(parse_suggestion(&text).is_err(), "{} was allowed", path);
+        }
+    }
+
+    #[test]
+    fn test_stray_replace_marker_before_edits() {
+        let text = ">>>>>>> REPLACE
+<<<<<<< SEARCH
+=======
+x
+>>>>>>> REPLACE
+";
+        assert!(parse_suggestion(text).is_err());
+    }
+
+    #[test]
+    fn test_preview_edits_needs_unique_match() {
+        let root = std::env::temp_dir().join("wkfl-test-preview-edits");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.txt"), "one\ntwo\ntwo\n").unwrap();
+        let edit = |search: &str| Edit {
+            path: PathBuf::from("a.txt"),
+            search: search.to_string(),
+            replace: "three\n".to_string(),
+        };
+
+        let changes = preview_edits(&[edit("one\n")], &root).unwrap();
+        assert_eq!(changes[0].new, "three\ntwo\ntwo\n");
+        assert!(preview_edits(&[edit("two\n")], &root).is_err());
+        assert!(preview_edits(&[edit("four\n")], &root).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
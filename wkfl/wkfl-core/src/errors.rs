@@ -0,0 +1,217 @@
+use std::{fmt, process::ExitCode};
+
+/// Broad categories of failures. Each maps to its own exit code so scripts
+/// wrapping wkfl can tell them apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Other,
+    Config,
+    Network,
+    Auth,
+    Git,
+    UserAbort,
+}
+
+impl ErrorCategory {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            ErrorCategory::Other => 1,
+            // 2 is left for clap's usage errors
+            ErrorCategory::Config => 3,
+            ErrorCategory::Network => 4,
+            ErrorCategory::Auth => 5,
+            ErrorCategory::Git => 6,
+            // 128 + SIGINT, same as being interrupted by ctrl-c
+            ErrorCategory::UserAbort => 130,
+        })
+    }
+}
+
+/// The user cancelled (eg. ctrl-c in a prompt)
+#[derive(Debug)]
+pub struct UserAbort;
+
+impl fmt::Display for UserAbort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Aborted by user")
+    }
+}
+
+impl std::error::Error for UserAbort {}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    MissingKey(String),
+    NoProvider(String),
+    Secret(String),
+    InvalidRepoConfig(String),
+    /// Config written for a newer wkfl, with its `config_version`
+    NewerVersion(i64),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingKey(key) => write!(f, "Missing {} in config", key),
+            ConfigError::NoProvider(feature) => {
+                write!(f, "No provider configured that supports {}", feature)
+            }
+            ConfigError::Secret(message) => write!(f, "Unable to resolve secret: {}", message),
+            ConfigError::InvalidRepoConfig(message) => {
+                write!(f, "Invalid repo config: {}", message)
+            }
+            ConfigError::NewerVersion(version) => {
+                write!(
+                    f,
+                    "Config is version {}, which is newer than this wkfl",
+                    version
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConfigError {
+    fn hint(&self) -> String {
+        match self {
+            ConfigError::MissingKey(key) => {
+                format!("set `{}` in ~/.config/wkfl/config.toml", key)
+            }
+            ConfigError::NoProvider(_) => {
+                "configure an API key for one of the supported providers in ~/.config/wkfl/config.toml"
+                    .to_string()
+            }
+            ConfigError::Secret(_) => {
+                "check the `cmd::`/`env::` value for the secret in ~/.config/wkfl/config.toml"
+                    .to_string()
+            }
+            ConfigError::InvalidRepoConfig(_) => {
+                "fix the repo's .git/info/wkfl.toml".to_string()
+            }
+            ConfigError::NewerVersion(_) => "upgrade wkfl to use this config".to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn categorize_ureq_error(err: &ureq::Error) -> ErrorCategory {
+    match err {
+        ureq::Error::Status(401 | 403, _) => ErrorCategory::Auth,
+        ureq::Error::Status(429 | 500..=599, _) => ErrorCategory::Network,
+        ureq::Error::Status(_, _) => ErrorCategory::Other,
+        ureq::Error::Transport(_) => ErrorCategory::Network,
+    }
+}
+
+#[cfg(feature = "http")]
+fn ureq_error_host(err: &ureq::Error) -> Option<String> {
+    let url = match err {
+        ureq::Error::Status(_, response) => url::Url::parse(response.get_url()).ok(),
+        ureq::Error::Transport(transport) => transport.url().cloned(),
+    };
+    url.and_then(|url| url.host_str().map(|host| host.to_string()))
+}
+
+pub fn categorize(err: &anyhow::Error) -> ErrorCategory {
+    for cause in err.chain() {
+        if cause.is::<UserAbort>() {
+            return ErrorCategory::UserAbort;
+        }
+        if cause.is::<ConfigError>() || cause.is::<toml::de::Error>() {
+            return ErrorCategory::Config;
+        }
+        #[cfg(feature = "git")]
+        if cause.is::<git2::Error>() {
+            return ErrorCategory::Git;
+        }
+        #[cfg(feature = "http")]
+        if let Some(ureq_err) = cause.downcast_ref::<ureq::Error>() {
+            return categorize_ureq_error(ureq_err);
+        }
+    }
+    ErrorCategory::Other
+}
+
+fn remediation_hint(err: &anyhow::Error) -> Option<String> {
+    for cause in err.chain() {
+        if let Some(config_err) = cause.downcast_ref::<ConfigError>() {
+            return Some(config_err.hint());
+        }
+        if cause.is::<toml::de::Error>() {
+            return Some("fix the syntax error in your wkfl config".to_string());
+        }
+        #[cfg(feature = "http")]
+        if let Some(ureq_err) = cause.downcast_ref::<ureq::Error>() {
+            let host = ureq_error_host(ureq_err).unwrap_or("the server".to_string());
+            return match categorize_ureq_error(ureq_err) {
+                ErrorCategory::Auth => {
+                    Some(format!("check the credentials configured for {}", host))
+                }
+                ErrorCategory::Network => Some(format!(
+                    "request to {} failed, check your connection and try again",
+                    host
+                )),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+/// Prints the error (and a hint on how to fix it if we have one) and
+/// returns the exit code for its category
+pub fn report(err: &anyhow::Error) -> ExitCode {
+    let category = categorize(err);
+    if category == ErrorCategory::UserAbort {
+        // The user knows they cancelled, no need for the full error
+        eprintln!("{}", UserAbort);
+    } else {
+        eprintln!("Error: {:?}", err);
+        if let Some(hint) = remediation_hint(err) {
+            eprintln!("hint: {}", hint);
+        }
+    }
+    category.exit_code()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::{categorize, ConfigError, ErrorCategory, UserAbort};
+
+    #[test]
+    fn test_user_abort() {
+        let err = anyhow::Error::from(UserAbort);
+        assert_eq!(categorize(&err), ErrorCategory::UserAbort);
+    }
+
+    #[test]
+    fn test_category_found_through_context() {
+        let result: anyhow::Result<()> =
+            Err(ConfigError::MissingKey("vertex_ai".to_string()).into());
+        let err = result.context("Failed to create client").unwrap_err();
+        assert_eq!(categorize(&err), ErrorCategory::Config);
+    }
+
+    #[test]
+    fn test_toml_error_is_config() {
+        let err = anyhow::Error::from(toml::from_str::<toml::Table>("a = ").unwrap_err());
+        assert_eq!(categorize(&err), ErrorCategory::Config);
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_error() {
+        let err = anyhow::Error::from(git2::Error::from_str("bad ref"));
+        assert_eq!(categorize(&err), ErrorCategory::Git);
+    }
+
+    #[test]
+    fn test_unknown_error() {
+        let err = anyhow::anyhow!("Something else");
+        assert_eq!(categorize(&err), ErrorCategory::Other);
+    }
+}
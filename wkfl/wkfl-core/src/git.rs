@@ -7,8 +7,8 @@ use std::{
 use anyhow::{self, bail};
 
 use git2::{
-    build::CheckoutBuilder, Branch, BranchType, Error, ErrorCode, Repository, RepositoryState,
-    StatusOptions, WorktreeAddOptions,
+    build::CheckoutBuilder, Branch, BranchType, Diff, DiffFormat, Error, ErrorCode, Patch,
+    Repository, RepositoryState, Sort, StatusOptions, WorktreeAddOptions,
 };
 use log::{info, warn};
 
@@ -128,6 +128,80 @@ pub fn has_changes(repo: &Repository) -> anyhow::Result<bool> {
     Ok(!repo.statuses(Some(&mut status_options))?.is_empty())
 }
 
+/// Patch of the changes staged for the next commit
+pub fn staged_diff(repo: &Repository) -> anyhow::Result<String> {
+    let head_tree = match repo.head() {
+        Ok(head) => Some(head.peel_to_tree()?),
+        // No commits yet, everything staged is new
+        Err(err) if err.code() == ErrorCode::UnbornBranch => None,
+        Err(err) => return Err(err.into()),
+    };
+    let diff = repo.diff_tree_to_index(head_tree.as_ref(), None, None)?;
+    diff_to_patch(&diff)
+}
+
+/// What the current branch adds on top of the default branch
+pub struct BranchChanges {
+    /// Commit messages, oldest first
+    pub commit_messages: Vec<String>,
+    pub diff: String,
+}
+
+/// Changes since the current branch split from origin's default branch
+pub fn branch_changes(repo: &Repository) -> anyhow::Result<BranchChanges> {
+    let default_branch_name = get_default_branch(repo)?;
+    let default_branch = repo.find_branch(
+        &format!("origin/{}", default_branch_name),
+        BranchType::Remote,
+    )?;
+    let default_oid = default_branch
+        .get()
+        .target()
+        .expect("Branch should point to a commit");
+    let head_oid = repo
+        .head()?
+        .target()
+        .ok_or(anyhow::anyhow!("HEAD doesn't point to a commit"))?;
+    let base_oid = repo.merge_base(head_oid, default_oid)?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    revwalk.push(head_oid)?;
+    revwalk.hide(base_oid)?;
+    let mut commit_messages = vec![];
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        commit_messages.push(String::from_utf8_lossy(commit.message_bytes()).to_string());
+    }
+
+    let base_tree = repo.find_commit(base_oid)?.tree()?;
+    let head_tree = repo.find_commit(head_oid)?.tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)?;
+    Ok(BranchChanges {
+        commit_messages,
+        diff: diff_to_patch(&diff)?,
+    })
+}
+
+/// Unified diff between two versions of a file
+pub fn diff_contents(path: &Path, old: &str, new: &str) -> anyhow::Result<String> {
+    let mut patch =
+        Patch::from_buffers(old.as_bytes(), Some(path), new.as_bytes(), Some(path), None)?;
+    Ok(patch.to_buf()?.as_str().unwrap_or_default().to_string())
+}
+
+fn diff_to_patch(diff: &Diff) -> anyhow::Result<String> {
+    let mut patch = String::new();
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin());
+        }
+        patch.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })?;
+    Ok(patch)
+}
+
 pub fn remove_worktree(repo: &Repository, worktree_name: &str) -> anyhow::Result<()> {
     let worktree = repo.find_worktree(worktree_name)?;
     let worktree_repo = Repository::open(worktree.path())?;
@@ -148,7 +222,7 @@ pub fn on_default_branch(repo: &Repository) -> anyhow::Result<bool> {
     Ok(current_branch.name()?.unwrap_or("") == default_branch)
 }
 
-fn get_current_branch(repo: &Repository) -> anyhow::Result<Branch> {
+fn get_current_branch(repo: &Repository) -> anyhow::Result<Branch<'_>> {
     if repo.head_detached().unwrap_or(false) {
         bail!("Currently no branch, repo head is detached");
     }
@@ -164,6 +238,14 @@ fn get_current_branch(repo: &Repository) -> anyhow::Result<Branch> {
     Ok(branch)
 }
 
+pub fn get_current_branch_name(repo: &Repository) -> anyhow::Result<String> {
+    let branch = get_current_branch(repo)?;
+    let name = branch
+        .name()?
+        .ok_or(anyhow::anyhow!("Branch name is not utf-8"))?;
+    Ok(name.to_string())
+}
+
 pub fn remove_current_branch(repo: &Repository) -> anyhow::Result<()> {
     let mut current_branch = get_current_branch(repo)?;
     let default_branch = get_default_branch(repo)?;
@@ -182,6 +264,16 @@ pub fn remove_branch(repo: &Repository, branch_name: &str) -> anyhow::Result<()>
     Ok(())
 }
 
+/// Removes the named worktree and its branch in a bare, worktree based
+/// repo, or the named branch otherwise
+pub fn remove_branch_or_worktree(repo: &Repository, name: &str) -> anyhow::Result<()> {
+    if repo.is_bare() {
+        remove_worktree(repo, name)
+    } else {
+        remove_branch(repo, name)
+    }
+}
+
 pub fn get_worktrees(repo: &Repository) -> anyhow::Result<Vec<String>> {
     Ok(repo
         .worktrees()?
@@ -191,6 +283,17 @@ pub fn get_worktrees(repo: &Repository) -> anyhow::Result<Vec<String>> {
         .collect())
 }
 
+pub fn get_local_branches(repo: &Repository) -> anyhow::Result<Vec<String>> {
+    let mut branch_names = vec![];
+    for branch_result in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch_result?;
+        if let Some(name) = branch.name()? {
+            branch_names.push(name.to_string());
+        }
+    }
+    Ok(branch_names)
+}
+
 pub fn clone_repo(repo_url: &str, repo_path: &Path) -> anyhow::Result<()> {
     info!("Cloing {} into {}...", repo_url, repo_path.display());
     // Shell out to git for clone because libgit2 doesn't take into account .ssh/config
@@ -206,3 +309,41 @@ pub fn clone_repo(repo_url: &str, repo_path: &Path) -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use git2::{Repository, Signature, WorktreeAddOptions};
+
+    use super::remove_branch_or_worktree;
+
+    #[test]
+    fn test_remove_worktree_by_name_in_bare_repo() {
+        let root = env::temp_dir().join(format!("wkfl-test-bare-{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let repo = Repository::init_bare(root.join(".git")).unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let signature = Signature::now("wkfl", "wkfl@example.com").unwrap();
+        let commit_id = repo
+            .commit(None, &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        let branch = repo
+            .branch("feature", &repo.find_commit(commit_id).unwrap(), false)
+            .unwrap();
+        let mut worktree_opts = WorktreeAddOptions::new();
+        worktree_opts.reference(Some(branch.get()));
+        repo.worktree("feature", &root.join("feature"), Some(&worktree_opts))
+            .unwrap();
+
+        remove_branch_or_worktree(&repo, "feature").unwrap();
+        assert!(!root.join("feature").exists());
+        assert!(repo.find_worktree("feature").is_err());
+        assert!(repo
+            .find_branch("feature", git2::BranchType::Local)
+            .is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use log::warn;
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer},
+    ClientConfig, RootCertStore,
+};
+use serde::Serialize;
+use time::{
+    format_description::BorrowedFormatItem, macros::format_description, OffsetDateTime,
+    PrimitiveDateTime,
+};
+use ureq::{Agent, AgentBuilder, ErrorKind, Proxy, Request, Response};
+
+use crate::config::HttpConfig;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// The IMF-fixdate form of an HTTP date, eg. `Wed, 21 Oct 2015 07:28:00 GMT`
+const HTTP_DATE_FORMAT: &[BorrowedFormatItem] = format_description!(
+    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+/// Shared client for all outbound requests, so retries are handled the same
+/// way for every API we talk to
+pub struct HttpClient {
+    agent: Agent,
+    max_attempts: u32,
+}
+
+impl HttpClient {
+    pub fn from_config(config: &HttpConfig) -> anyhow::Result<Self> {
+        let mut builder = AgentBuilder::new();
+        if let Some(secs) = config.connect_timeout_secs {
+            builder = builder.timeout_connect(Duration::from_secs(secs));
+        }
+        if let Some(secs) = config.read_timeout_secs {
+            builder = builder.timeout_read(Duration::from_secs(secs));
+        }
+        builder = match &config.proxy {
+            Some(proxy) => builder.proxy(Proxy::new(proxy).context("Invalid http.proxy")?),
+            None => builder.try_proxy_from_env(true),
+        };
+        if let Some(ca_bundle) = config.ca_bundle_path()? {
+            builder = builder.tls_config(create_tls_config(&ca_bundle)?);
+        }
+        Ok(Self {
+            agent: builder.build(),
+            max_attempts: config.max_attempts.max(1),
+        })
+    }
+
+    pub fn get(&self, url: &str) -> Request {
+        self.agent.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> Request {
+        self.agent.post(url)
+    }
+
+    /// Sends the request with a JSON body, retrying transient failures
+    pub fn send_json(&self, request: Request, data: impl Serialize) -> anyhow::Result<Response> {
+        self.send_with_retries(&request, Some(&serde_json::to_value(data)?))
+    }
+
+    /// Sends the request without a body, retrying transient failures
+    pub fn call(&self, request: Request) -> anyhow::Result<Response> {
+        self.send_with_retries(&request, None)
+    }
+
+    /// Retries transient failures (429s, 5xxs and connection problems)
+    /// with exponential backoff. Requests that aren't idempotent, like the
+    /// LLM POSTs, are only retried if they never reached the server.
+    fn send_with_retries(
+        &self,
+        request: &Request,
+        body: Option<&serde_json::Value>,
+    ) -> anyhow::Result<Response> {
+        let mut attempt = 1;
+        loop {
+            let result = match body {
+                Some(json) => request.clone().send_json(json),
+                None => request.clone().call(),
+            };
+            let err = match result {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            let maybe_delay = if attempt < self.max_attempts {
+                retry_delay(&err, request.method(), attempt)
+            } else {
+                None
+            };
+            let Some(delay) = maybe_delay else {
+                return Err(err.into());
+            };
+            warn!(
+                "Request to {} failed ({}), retrying in {:.1}s...",
+                request.url(),
+                err,
+                delay.as_secs_f32()
+            );
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+/// Builds the same TLS config ureq uses by default, but also trusting the
+/// certificates in the CA bundle
+fn create_tls_config(ca_bundle: &Path) -> anyhow::Result<Arc<ClientConfig>> {
+    let mut root_store = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let certs = CertificateDer::pem_file_iter(ca_bundle)
+        .with_context(|| format!("Failed to read CA bundle {}", ca_bundle.display()))?;
+    for cert in certs {
+        let cert = cert.with_context(|| format!("Invalid cert in {}", ca_bundle.display()))?;
+        root_store.add(cert)?;
+    }
+    let tls_config = ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    Ok(Arc::new(tls_config))
+}
+
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(MAX_BACKOFF)
+}
+
+fn is_idempotent(method: &str) -> bool {
+    matches!(method, "GET" | "HEAD" | "OPTIONS" | "PUT" | "DELETE")
+}
+
+/// Parses a Retry-After header, which is either a number of seconds or an
+/// HTTP date to wait until
+fn parse_retry_after(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let until = PrimitiveDateTime::parse(value, HTTP_DATE_FORMAT)
+        .ok()?
+        .assume_utc();
+    // A date in the past means it's fine to retry straight away
+    Some((until - now).try_into().unwrap_or(Duration::ZERO))
+}
+
+/// Returns how long to wait before retrying, or None if the error isn't
+/// worth retrying
+fn retry_delay(err: &ureq::Error, method: &str, attempt: u32) -> Option<Duration> {
+    match err {
+        ureq::Error::Status(429 | 500..=599, response) => {
+            let retry_after = response
+                .header("Retry-After")
+                .and_then(|value| parse_retry_after(value, SystemTime::now().into()));
+            Some(retry_after.unwrap_or(backoff(attempt)).min(MAX_BACKOFF))
+        }
+        ureq::Error::Transport(transport) => match transport.kind() {
+            // Nothing was sent if the connection couldn't be made
+            ErrorKind::ConnectionFailed => Some(backoff(attempt)),
+            // The server may have already acted on the request
+            ErrorKind::Io if is_idempotent(method) => Some(backoff(attempt)),
+            _ => None,
+        },
+        ureq::Error::Status(_, _) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use time::macros::datetime;
+
+    use super::{backoff, parse_retry_after, retry_delay, MAX_BACKOFF};
+
+    #[test]
+    fn test_backoff_doubles() {
+        assert_eq!(backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff(2), Duration::from_secs(1));
+        assert_eq!(backoff(3), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_retry_after_header_is_used() {
+        let response: ureq::Response = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 7\r\n\r\n"
+            .parse()
+            .unwrap();
+        let err = ureq::Error::Status(429, response);
+        assert_eq!(retry_delay(&err, "POST", 1), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn test_retry_after_http_date() {
+        let now = datetime!(2015-10-21 07:27:50 UTC);
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_io_errors_only_retried_when_idempotent() {
+        let err = || ureq::Error::from(std::io::Error::other("reset"));
+        assert_eq!(
+            retry_delay(&err(), "GET", 1),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(retry_delay(&err(), "POST", 1), None);
+    }
+
+    #[test]
+    fn test_client_errors_arent_retried() {
+        let response = ureq::Response::new(400, "Bad Request", "").unwrap();
+        let err = ureq::Error::Status(400, response);
+        assert_eq!(retry_delay(&err, "GET", 1), None);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        assert_eq!(backoff(10), MAX_BACKOFF);
+        assert_eq!(backoff(100), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,21 @@
+//! The parts of wkfl that don't need a terminal: config, git, LLM
+//! providers and notes. The `wkfl` binary is a clap layer on top of this,
+//! other frontends can depend on it directly.
+//!
+//! The integrations are behind cargo features, all on by default: `llm`
+//! for the chat providers, `embeddings` for the note index providers,
+//! `git` for libgit2 and `http` for the HTTP client the others share.
+
+pub mod config;
+pub mod errors;
+#[cfg(feature = "git")]
+pub mod git;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod llm;
+pub mod migrations;
+pub mod note_index;
+pub mod note_links;
+pub mod notes;
+pub mod snippets;
+pub mod utils;
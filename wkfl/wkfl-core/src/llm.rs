@@ -0,0 +1,470 @@
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::config::{Config, ModelIds};
+
+#[cfg(feature = "llm")]
+pub mod anthropic;
+pub mod cache;
+pub mod history;
+#[cfg(feature = "embeddings")]
+pub mod ollama;
+#[cfg(feature = "embeddings")]
+pub mod openai;
+#[cfg(feature = "llm")]
+pub mod perplexity;
+pub mod templates;
+pub mod usage;
+#[cfg(feature = "llm")]
+pub mod vertex_ai;
+
+#[derive(Debug, Serialize)]
+pub struct ChatRequest {
+    pub query: String,
+    pub model_type: ModelType,
+    /// Earlier turns of the conversation, oldest first
+    pub history: Vec<Message>,
+    pub system: Option<String>,
+    /// Model ID to use instead of the one for `model_type`
+    pub model: Option<String>,
+    /// Ask providers that support prompt caching to cache this prompt, for
+    /// when a follow up request will start the same way (eg. the next turn
+    /// of a chat with files attached)
+    pub cache_prompt: bool,
+    /// JSON schema the answer has to follow. Providers constrain their
+    /// output to it as best they can, check it with `parse_structured`.
+    pub response_schema: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroundedChatRequest {
+    pub query: String,
+    pub model_type: ModelType,
+    /// Model ID to use instead of the one for `model_type`
+    pub model: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Assistant,
+    System,
+    User,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub enum ModelType {
+    #[default]
+    Small,
+    Large,
+    Thinking,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+#[derive(Debug)]
+pub struct ChatResponse {
+    pub message: Message,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroundedChatResponse {
+    pub message: Message,
+    pub citations: CitationMetadata,
+}
+
+/// How a web chat answer shows which sources back each part of it
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CitationStyle {
+    /// Markdown footnotes (`[^1]`), for pasting into notes
+    Footnotes,
+    /// Superscript numbers (`¹˒²`), for reading in the terminal
+    #[default]
+    Inline,
+    /// Just the answer, without sources
+    None,
+}
+
+/// Superscript form of the number, eg. ¹² for 12
+pub fn superscript_number(number: usize) -> String {
+    const SUPERSCRIPT_DIGITS: [&str; 10] = ["⁰", "¹", "²", "³", "⁴", "⁵", "⁶", "⁷", "⁸", "⁹"];
+    number
+        .to_string()
+        .chars()
+        .map(|c| SUPERSCRIPT_DIGITS[c.to_digit(10).expect("Formatted as digits") as usize])
+        .collect()
+}
+
+impl GroundedChatResponse {
+    /// The answer with a citation marker after each supported part. Sources
+    /// are numbered from 1.
+    pub fn annotated_answer(&self, style: CitationStyle) -> String {
+        let content = &self.message.content;
+        if style == CitationStyle::None {
+            return content.to_string();
+        }
+        let mut answer = String::new();
+        let mut last_end = 0;
+        for support in self.citations.supports.iter() {
+            answer.push_str(&content[last_end..support.end_index]);
+            let numbers = support
+                .source_indices
+                .iter()
+                .map(|index| *index as usize + 1);
+            let markers = match style {
+                CitationStyle::Footnotes => {
+                    numbers.map(|number| format!("[^{}]", number)).collect()
+                }
+                CitationStyle::Inline => numbers
+                    .map(superscript_number)
+                    .collect::<Vec<String>>()
+                    .join("˒"),
+                CitationStyle::None => String::new(),
+            };
+            answer.push_str(&markers);
+            last_end = support.end_index;
+        }
+        answer.push_str(&content[last_end..]);
+        answer
+    }
+
+    /// Markdown of the answer with its citations, and the sources listed at
+    /// the end to match
+    pub fn render(&self, style: CitationStyle) -> String {
+        let mut markdown = self.annotated_answer(style);
+        markdown.push('\n');
+        if style == CitationStyle::None || self.citations.sources.is_empty() {
+            return markdown;
+        }
+        markdown.push('\n');
+        for (i, source) in self.citations.sources.iter().enumerate() {
+            let marker = match style {
+                CitationStyle::Footnotes => format!("[^{}]:", i + 1),
+                CitationStyle::Inline | CitationStyle::None => {
+                    format!("- {}", superscript_number(i + 1))
+                }
+            };
+            markdown.push_str(&format!("{} [{}]({})\n", marker, source.title, source.uri));
+        }
+        markdown
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CitationMetadata {
+    pub sources: Vec<Source>,
+    pub supports: Vec<Support>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Source {
+    pub title: String,
+    pub uri: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Support {
+    pub start_index: usize,
+    pub end_index: usize,
+    pub text: String,
+    pub source_indices: Vec<u8>,
+}
+
+pub trait LlmProvider: Sized {
+    fn from_config(config: Config) -> anyhow::Result<Self>;
+}
+
+pub trait GroundedChat {
+    fn create_grounded_chat_completion(
+        &self,
+        request: GroundedChatRequest,
+    ) -> anyhow::Result<GroundedChatResponse>;
+    /// The model ID and settings a request would be answered with, so the
+    /// cache doesn't mix up answers from different models or configs
+    fn model_key(&self, model: Option<String>, model_type: &ModelType) -> anyhow::Result<String>;
+}
+
+pub trait Chat {
+    fn create_message(&self, request: ChatRequest) -> anyhow::Result<ChatResponse>;
+    /// Same as `GroundedChat::model_key`
+    fn model_key(&self, model: Option<String>, model_type: &ModelType) -> anyhow::Result<String>;
+}
+
+pub trait Embeddings {
+    /// Embeddings from different models can't be compared, so anything
+    /// stored needs to know which model made it
+    fn model(&self) -> &str;
+    /// One embedding per text, in the same order
+    fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>>;
+}
+
+/// The model a request should use: its `--model` override, else the one
+/// configured in `[models]` for its model type, else the provider's default.
+/// Each provider's model enum has an untagged `Custom` variant, so IDs it
+/// doesn't know about are passed through as is.
+pub fn pick_model<T: DeserializeOwned>(
+    model: Option<String>,
+    model_type: &ModelType,
+    model_ids: &ModelIds,
+    default: impl FnOnce(&ModelType) -> Result<T>,
+) -> Result<T> {
+    match model.or_else(|| model_ids.for_type(model_type).map(|id| id.to_string())) {
+        Some(id) => serde_json::from_value(serde_json::Value::String(id.clone()))
+            .with_context(|| format!("{} isn't a valid model ID", id)),
+        None => default(model_type),
+    }
+}
+
+/// The ID a provider's model enum is sent to the API as
+pub fn model_id(model: &impl Serialize) -> Result<String> {
+    match serde_json::to_value(model)? {
+        serde_json::Value::String(id) => Ok(id),
+        value => Ok(value.to_string()),
+    }
+}
+
+/// Parses a structured answer, failing with every way it doesn't match the
+/// schema
+pub fn parse_structured(schema: &serde_json::Value, answer: &str) -> Result<serde_json::Value> {
+    let validator = jsonschema::validator_for(schema)
+        .map_err(|err| anyhow::anyhow!("Invalid JSON schema: {}", err))?;
+    let value: serde_json::Value = serde_json::from_str(answer.trim())
+        .with_context(|| format!("Answer isn't JSON:\n{}", answer))?;
+    let errors: Vec<String> = validator
+        .iter_errors(&value)
+        .map(|err| format!("  {}: {}", err.instance_path, err))
+        .collect();
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "Answer doesn't match the schema:\n{}\n\n{}",
+            errors.join("\n"),
+            answer
+        );
+    }
+    Ok(value)
+}
+
+/// Rough token estimate for when we don't want to ask the provider, errs
+/// on the side of overestimating
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.len() as u64).div_ceil(3)
+}
+
+/// Fails before sending a prompt the model would reject for being too long
+pub fn check_context_length(model: &str, prompt_tokens: u64, limit: u64) -> Result<()> {
+    if prompt_tokens > limit {
+        anyhow::bail!(
+            "Prompt is {} tokens, which is over the {} token limit for {}",
+            prompt_tokens,
+            limit,
+            model
+        );
+    }
+    Ok(())
+}
+
+/// Total size of the attachments added to a prompt, split evenly between
+/// those too big to fit
+const ATTACHMENT_TOKEN_BUDGET: u64 = 100_000;
+
+/// Appends each attachment, a name and its contents, to the query in a
+/// fenced block. Big ones are truncated to fit in the token budget.
+pub fn add_attachments(query: String, mut attachments: Vec<(String, String)>) -> String {
+    if attachments.is_empty() {
+        return query;
+    }
+    truncate_to_budget(&mut attachments, ATTACHMENT_TOKEN_BUDGET);
+    let mut prompt = query;
+    for (name, contents) in &attachments {
+        if !prompt.is_empty() {
+            prompt.push_str("\n\n");
+        }
+        prompt.push_str(&fence(name, contents));
+    }
+    prompt
+}
+
+/// Wraps the contents in a code fence longer than any backtick run inside
+/// them, so attached markdown can't close it early
+fn fence(name: &str, contents: &str) -> String {
+    let mut longest_run = 0;
+    let mut run = 0;
+    for c in contents.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest_run = longest_run.max(run);
+    }
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{}:\n{}\n{}\n{}", name, fence, contents.trim_end(), fence)
+}
+
+/// Shrinks the attachments to fit in the budget. Smaller ones are kept
+/// whole where possible, the rest share what's left evenly.
+fn truncate_to_budget(attachments: &mut [(String, String)], budget: u64) {
+    let mut by_size: Vec<usize> = (0..attachments.len()).collect();
+    by_size.sort_by_key(|&i| attachments[i].1.len());
+
+    let mut remaining = budget;
+    for (position, &i) in by_size.iter().enumerate() {
+        let share = remaining / (by_size.len() - position) as u64;
+        let (name, contents) = &mut attachments[i];
+        let tokens = estimate_tokens(contents);
+        if tokens <= share {
+            remaining -= tokens;
+            continue;
+        }
+        warn!("Truncating {} to fit in the prompt", name);
+        *contents = truncate_to_tokens(contents, share);
+        remaining -= share;
+    }
+}
+
+/// Cuts the text at the last line that fits, noting how much was dropped
+pub fn truncate_to_tokens(text: &str, tokens: u64) -> String {
+    let mut end = (tokens * 3) as usize;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    if let Some(newline) = text[..end].rfind('\n') {
+        end = newline;
+    }
+    let dropped_lines = text[end..].trim_start_matches('\n').lines().count();
+    format!("{}\n[{} more lines truncated]", &text[..end], dropped_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "llm")]
+    use super::{anthropic::AnthropicModel, pick_model, ModelType};
+    use super::{
+        fence, parse_structured, truncate_to_budget, CitationMetadata, CitationStyle,
+        GroundedChatResponse, Message, Role, Source, Support,
+    };
+    #[cfg(feature = "llm")]
+    use crate::config::ModelIds;
+
+    #[test]
+    fn test_render_citations() {
+        let response = GroundedChatResponse {
+            message: Message {
+                role: Role::Assistant,
+                content: "Rust is fast. It is safe.".to_string(),
+            },
+            citations: CitationMetadata {
+                sources: vec![
+                    Source {
+                        title: "Rust".to_string(),
+                        uri: "https://rust-lang.org".to_string(),
+                    },
+                    Source {
+                        title: "Book".to_string(),
+                        uri: "https://doc.rust-lang.org/book".to_string(),
+                    },
+                ],
+                supports: vec![Support {
+                    start_index: 0,
+                    end_index: 13,
+                    text: "Rust is fast.".to_string(),
+                    source_indices: vec![0, 1],
+                }],
+            },
+        };
+        assert_eq!(
+            response.render(CitationStyle::Footnotes),
+            "Rust is fast.[^1][^2] It is safe.\n\n\
+             [^1]: [Rust](https://rust-lang.org)\n\
+             [^2]: [Book](https://doc.rust-lang.org/book)\n"
+        );
+        assert_eq!(
+            response.render(CitationStyle::Inline),
+            "Rust is fast.¹˒² It is safe.\n\n\
+             - ¹ [Rust](https://rust-lang.org)\n\
+             - ² [Book](https://doc.rust-lang.org/book)\n"
+        );
+        assert_eq!(
+            response.render(CitationStyle::None),
+            "Rust is fast. It is safe.\n"
+        );
+    }
+
+    #[test]
+    fn test_fence_longer_than_contents() {
+        assert_eq!(fence("a.rs", "fn a() {}\n"), "a.rs:\n```\nfn a() {}\n```");
+        assert_eq!(
+            fence("README.md", "```sh\nls\n```"),
+            "README.md:\n````\n```sh\nls\n```\n````"
+        );
+    }
+
+    #[test]
+    fn test_truncate_to_budget() {
+        let small = "small\n".to_string();
+        let big = "line\n".repeat(100);
+        let mut attachments = vec![
+            ("big".to_string(), big),
+            ("small".to_string(), small.clone()),
+        ];
+        truncate_to_budget(&mut attachments, 20);
+        assert_eq!(attachments[1].1, small);
+        assert!(attachments[0].1.starts_with("line\nline\n"));
+        assert!(attachments[0].1.ends_with("more lines truncated]"));
+        assert!(attachments[0].1.len() < 20 * 3 + 30);
+    }
+
+    #[cfg(feature = "llm")]
+    #[test]
+    fn test_pick_model() {
+        let model_ids = ModelIds {
+            large: Some("claude-3-5-haiku-latest".to_string()),
+            thinking: Some("claude-3-7-sonnet-latest".to_string()),
+            ..ModelIds::default()
+        };
+        let default = |_: &ModelType| Ok(AnthropicModel::Claude35Sonnet);
+        let pick = |model: Option<&str>, model_type| {
+            pick_model(
+                model.map(|model| model.to_string()),
+                &model_type,
+                &model_ids,
+                default,
+            )
+            .unwrap()
+        };
+        assert!(matches!(
+            pick(None, ModelType::Small),
+            AnthropicModel::Claude35Sonnet
+        ));
+        assert!(matches!(
+            pick(None, ModelType::Large),
+            AnthropicModel::Claude35Haiku
+        ));
+        assert!(matches!(
+            pick(None, ModelType::Thinking),
+            AnthropicModel::Custom(id) if id == "claude-3-7-sonnet-latest"
+        ));
+        assert!(matches!(
+            pick(Some("claude-opus"), ModelType::Large),
+            AnthropicModel::Custom(id) if id == "claude-opus"
+        ));
+    }
+
+    #[test]
+    fn test_parse_structured() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"files": {"type": "array", "items": {"type": "string"}}},
+            "required": ["files"],
+        });
+        let answer = parse_structured(&schema, "{\"files\": [\"a.rs\"]}\n").unwrap();
+        assert_eq!(answer["files"][0], "a.rs");
+
+        let err = parse_structured(&schema, "{\"files\": [1]}").unwrap_err();
+        assert!(err.to_string().contains("/files/0"), "{}", err);
+        assert!(parse_structured(&schema, "{}").is_err());
+        assert!(parse_structured(&schema, "not json").is_err());
+    }
+}
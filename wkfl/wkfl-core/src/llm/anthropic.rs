@@ -0,0 +1,392 @@
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{resolve_secret, AnthropicConfig, Config, ModelIds};
+use crate::errors::ConfigError;
+use crate::http::HttpClient;
+
+use super::{usage, Message, Role};
+
+#[allow(dead_code)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub enum AnthropicModel {
+    #[serde(alias = "claude-3-5-haiku-20241022")]
+    #[serde(rename = "claude-3-5-haiku-latest")]
+    Claude35Haiku,
+    #[default]
+    #[serde(alias = "claude-3-5-sonnet-20241022")]
+    #[serde(rename = "claude-3-5-sonnet-latest")]
+    Claude35Sonnet,
+    /// Any other model ID, eg. from the `[models]` config
+    #[serde(untagged)]
+    Custom(String),
+}
+
+/// Marks the end of a prefix of the prompt for Anthropic to cache
+#[derive(Debug, Serialize)]
+pub struct CacheControl {
+    #[serde(rename = "type")]
+    pub cache_type: String,
+}
+
+impl CacheControl {
+    /// The only kind there is, cached for 5 minutes after its last use
+    pub fn ephemeral() -> Self {
+        Self {
+            cache_type: "ephemeral".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextBlock {
+    #[serde(rename = "type")]
+    pub block_type: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// Content can be plain text, or blocks when it needs a `cache_control`
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Content {
+    Text(String),
+    Blocks(Vec<TextBlock>),
+}
+
+impl Content {
+    /// The text as a block marked to be cached
+    pub fn cached(text: String) -> Self {
+        Content::Blocks(vec![TextBlock {
+            block_type: "text".to_string(),
+            text,
+            cache_control: Some(CacheControl::ephemeral()),
+        }])
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnthropicMessage {
+    pub role: Role,
+    pub content: Content,
+}
+
+impl From<Message> for AnthropicMessage {
+    fn from(message: Message) -> Self {
+        Self {
+            role: message.role,
+            content: Content::Text(message.content),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AnthropicRequest {
+    pub model: AnthropicModel,
+    pub messages: Vec<AnthropicMessage>,
+    pub max_tokens: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: serde_json::Value,
+}
+
+/// Forces the model to answer by calling the named tool
+#[derive(Debug, Serialize)]
+pub struct ToolChoice {
+    #[serde(rename = "type")]
+    pub choice_type: String,
+    pub name: String,
+}
+
+/// Anthropic doesn't have a JSON mode, so structured answers are asked for
+/// as the input to a tool it has to call
+const RESPOND_TOOL: &str = "respond";
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct ContentBlock {
+    #[serde(rename = "type")]
+    pub content_type: String,
+    /// Only text blocks have text
+    #[serde(default)]
+    pub text: String,
+    /// Only tool_use blocks have input
+    pub input: Option<serde_json::Value>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct Usage {
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+    #[serde(default)]
+    pub cache_creation_input_tokens: i32,
+    #[serde(default)]
+    pub cache_read_input_tokens: i32,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct AnthropicResponse {
+    pub id: String,
+    pub model: AnthropicModel,
+    pub role: Role,
+    pub content: Vec<ContentBlock>,
+    pub stop_reason: Option<String>,
+    pub usage: Usage,
+}
+
+pub struct AnthropicClient {
+    api_key: String,
+    max_tokens: i32,
+    temperature: Option<f32>,
+    models: ModelIds,
+    http: HttpClient,
+}
+
+impl AnthropicClient {
+    pub fn new(
+        api_key: String,
+        config: &AnthropicConfig,
+        models: ModelIds,
+        http: HttpClient,
+    ) -> Self {
+        Self {
+            api_key,
+            max_tokens: config.max_tokens,
+            temperature: config.temperature,
+            models,
+            http,
+        }
+    }
+
+    fn pick_model(
+        &self,
+        model: Option<String>,
+        model_type: &super::ModelType,
+    ) -> anyhow::Result<AnthropicModel> {
+        super::pick_model(
+            model,
+            model_type,
+            &self.models,
+            |model_type| match model_type {
+                super::ModelType::Small => Ok(AnthropicModel::Claude35Haiku),
+                super::ModelType::Large => Ok(AnthropicModel::Claude35Sonnet),
+                super::ModelType::Thinking => bail!(
+                "Anthropic doesn't have a default thinking model, set one in [models.anthropic]"
+            ),
+            },
+        )
+    }
+
+    pub fn create_chat_completion(
+        &self,
+        request: AnthropicRequest,
+    ) -> anyhow::Result<AnthropicResponse> {
+        let http_request = self
+            .http
+            .post("https://api.anthropic.com/v1/messages")
+            .set("x-api-key", &self.api_key)
+            .set("anthropic-version", "2023-06-01")
+            .set("Content-Type", "application/json");
+        let response: AnthropicResponse =
+            self.http.send_json(http_request, &request)?.into_json()?;
+        // Cached input is cheaper to read but more expensive to write, so
+        // counting it all as input is only roughly right
+        let input_tokens = response.usage.input_tokens
+            + response.usage.cache_creation_input_tokens
+            + response.usage.cache_read_input_tokens;
+        usage::record(
+            "anthropic",
+            &usage::model_name(&response.model),
+            input_tokens as u64,
+            response.usage.output_tokens as u64,
+        );
+
+        Ok(response)
+    }
+}
+
+impl super::LlmProvider for AnthropicClient {
+    fn from_config(config: Config) -> anyhow::Result<Self> {
+        let api_key_raw = config
+            .anthropic_api_key
+            .ok_or(ConfigError::MissingKey("anthropic_api_key".to_string()))?;
+        let api_key = resolve_secret(&api_key_raw)?;
+        Ok(Self::new(
+            api_key,
+            &config.anthropic,
+            config.models.anthropic,
+            HttpClient::from_config(&config.http)?,
+        ))
+    }
+}
+
+impl super::Chat for AnthropicClient {
+    fn create_message(&self, request: super::ChatRequest) -> anyhow::Result<super::ChatResponse> {
+        let mut messages: Vec<AnthropicMessage> = request
+            .history
+            .into_iter()
+            .map(AnthropicMessage::from)
+            .collect();
+        // Caching up to the new query means the next request, which has
+        // this one in its history, reads the whole prompt from the cache
+        let (system, query) = if request.cache_prompt {
+            (
+                request.system.map(Content::cached),
+                Content::cached(request.query),
+            )
+        } else {
+            (
+                request.system.map(Content::Text),
+                Content::Text(request.query),
+            )
+        };
+        messages.push(AnthropicMessage {
+            role: super::Role::User,
+            content: query,
+        });
+        let (tools, tool_choice) = match request.response_schema {
+            Some(schema) => (
+                Some(vec![Tool {
+                    name: RESPOND_TOOL.to_string(),
+                    description: "Give your answer in this structure".to_string(),
+                    input_schema: schema,
+                }]),
+                Some(ToolChoice {
+                    choice_type: "tool".to_string(),
+                    name: RESPOND_TOOL.to_string(),
+                }),
+            ),
+            None => (None, None),
+        };
+        let structured = tools.is_some();
+        let result = self.create_chat_completion(AnthropicRequest {
+            messages,
+            system,
+            tools,
+            tool_choice,
+            model: self.pick_model(request.model, &request.model_type)?,
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            ..AnthropicRequest::default()
+        })?;
+        let content = if structured {
+            result
+                .content
+                .into_iter()
+                .find_map(|block| block.input)
+                .map(|input| input.to_string())
+                .ok_or(anyhow!("Anthropic didn't give a structured answer"))?
+        } else {
+            result
+                .content
+                .into_iter()
+                .nth(0)
+                .expect("It should always return some content")
+                .text
+        };
+        Ok(super::ChatResponse {
+            message: Message {
+                content,
+                role: result.role,
+            },
+        })
+    }
+
+    fn model_key(
+        &self,
+        model: Option<String>,
+        model_type: &super::ModelType,
+    ) -> anyhow::Result<String> {
+        let model = super::model_id(&self.pick_model(model, model_type)?)?;
+        Ok(format!(
+            "{} max_tokens={} temperature={:?}",
+            model, self.max_tokens, self.temperature
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{AnthropicClient, AnthropicMessage, AnthropicModel, AnthropicRequest, Content};
+    use crate::{
+        config::{AnthropicConfig, HttpConfig, ModelIds},
+        http::HttpClient,
+        llm::{Chat, ModelType, Role},
+    };
+
+    #[test]
+    fn test_cached_content_serializes_as_blocks() {
+        let request = AnthropicRequest {
+            model: AnthropicModel::Claude35Haiku,
+            messages: vec![AnthropicMessage {
+                role: Role::User,
+                content: Content::cached("long context".to_string()),
+            }],
+            system: Some(Content::Text("be brief".to_string())),
+            max_tokens: 1024,
+            ..AnthropicRequest::default()
+        };
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["system"], json!("be brief"));
+        assert_eq!(
+            value["messages"][0]["content"],
+            json!([{
+                "type": "text",
+                "text": "long context",
+                "cache_control": {"type": "ephemeral"}
+            }])
+        );
+    }
+
+    #[test]
+    fn test_model_key_uses_resolved_model_and_settings() {
+        let client = |temperature| {
+            let config = AnthropicConfig {
+                temperature,
+                ..AnthropicConfig::default()
+            };
+            let http = HttpClient::from_config(&HttpConfig::default()).unwrap();
+            AnthropicClient::new(String::new(), &config, ModelIds::default(), http)
+        };
+        let key = client(None).model_key(None, &ModelType::Small).unwrap();
+        assert!(key.starts_with("claude-3-5-haiku-latest "));
+        assert_eq!(
+            key,
+            client(None)
+                .model_key(
+                    Some("claude-3-5-haiku-latest".to_string()),
+                    &ModelType::Large
+                )
+                .unwrap()
+        );
+        assert_ne!(
+            key,
+            client(Some(0.5))
+                .model_key(None, &ModelType::Small)
+                .unwrap()
+        );
+    }
+}
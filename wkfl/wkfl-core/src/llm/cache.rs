@@ -0,0 +1,213 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::{wkfl_cache_dir, Config};
+
+use super::{
+    Chat, ChatRequest, ChatResponse, GroundedChat, GroundedChatRequest, GroundedChatResponse,
+    Message, ModelType, Role,
+};
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    created_at: u64,
+    content: String,
+}
+
+/// Content addressed cache of LLM responses stored under
+/// ~/.cache/wkfl/llm/, one file per response
+pub struct LlmCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+impl LlmCache {
+    /// Returns None when the cache isn't enabled in the config
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        if !config.llm_cache.enabled {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            dir: wkfl_cache_dir()?.join("llm"),
+            ttl: Duration::from_secs(config.llm_cache.ttl_secs),
+        }))
+    }
+
+    pub fn key(parts: &[&str]) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part.as_bytes());
+            // Separator so ("ab", "c") and ("a", "bc") don't collide
+            hasher.update([0]);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let path = self.entry_path(key);
+        let entry: CacheEntry = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+        if now_secs().saturating_sub(entry.created_at) > self.ttl.as_secs() {
+            debug!("LLM cache entry {} expired", key);
+            let _ = fs::remove_file(path);
+            return None;
+        }
+        debug!("LLM cache hit for {}", key);
+        Some(entry.content)
+    }
+
+    pub fn put(&self, key: &str, content: &str) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = CacheEntry {
+            created_at: now_secs(),
+            content: content.to_string(),
+        };
+        fs::write(self.entry_path(key), serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// Wraps a chat client so identical requests to the same provider are
+/// answered from the cache
+pub struct CachedChat {
+    inner: Box<dyn Chat>,
+    cache: LlmCache,
+    provider: String,
+}
+
+impl CachedChat {
+    pub fn new(inner: Box<dyn Chat>, cache: LlmCache, provider: String) -> Self {
+        Self {
+            inner,
+            cache,
+            provider,
+        }
+    }
+}
+
+impl Chat for CachedChat {
+    fn create_message(&self, request: ChatRequest) -> anyhow::Result<ChatResponse> {
+        let model_key = self
+            .inner
+            .model_key(request.model.clone(), &request.model_type)?;
+        let history = serde_json::to_string(&request.history)?;
+        let system = request.system.as_deref().unwrap_or("");
+        let mut parts = vec![
+            self.provider.as_str(),
+            &model_key,
+            system,
+            &history,
+            &request.query,
+        ];
+        let schema = request
+            .response_schema
+            .as_ref()
+            .map(|schema| schema.to_string());
+        if let Some(schema) = &schema {
+            parts.push(schema);
+        }
+        let key = LlmCache::key(&parts);
+        if let Some(content) = self.cache.get(&key) {
+            return Ok(ChatResponse {
+                message: Message {
+                    role: Role::Assistant,
+                    content,
+                },
+            });
+        }
+        let response_schema = request.response_schema.clone();
+        let response = self.inner.create_message(request)?;
+        // Trying again should ask again, not get the same wrong answer
+        let matches_schema = response_schema.is_none_or(|schema| {
+            super::parse_structured(&schema, &response.message.content).is_ok()
+        });
+        if matches_schema {
+            self.cache.put(&key, &response.message.content)?;
+        }
+        Ok(response)
+    }
+
+    fn model_key(&self, model: Option<String>, model_type: &ModelType) -> anyhow::Result<String> {
+        self.inner.model_key(model, model_type)
+    }
+}
+
+/// Same as `CachedChat` for web chat. The citations are cached along with
+/// the answer, as JSON.
+pub struct CachedGroundedChat {
+    inner: Box<dyn GroundedChat>,
+    cache: LlmCache,
+    provider: String,
+}
+
+impl CachedGroundedChat {
+    pub fn new(inner: Box<dyn GroundedChat>, cache: LlmCache, provider: String) -> Self {
+        Self {
+            inner,
+            cache,
+            provider,
+        }
+    }
+}
+
+impl GroundedChat for CachedGroundedChat {
+    fn create_grounded_chat_completion(
+        &self,
+        request: GroundedChatRequest,
+    ) -> anyhow::Result<GroundedChatResponse> {
+        let model_key = self
+            .inner
+            .model_key(request.model.clone(), &request.model_type)?;
+        // Kept apart from chat responses, which are just the answer
+        let key = LlmCache::key(&["grounded", &self.provider, &model_key, &request.query]);
+        if let Some(response) = self
+            .cache
+            .get(&key)
+            .and_then(|content| serde_json::from_str(&content).ok())
+        {
+            return Ok(response);
+        }
+        let response = self.inner.create_grounded_chat_completion(request)?;
+        self.cache.put(&key, &serde_json::to_string(&response)?)?;
+        Ok(response)
+    }
+
+    fn model_key(&self, model: Option<String>, model_type: &ModelType) -> anyhow::Result<String> {
+        self.inner.model_key(model, model_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LlmCache;
+
+    #[test]
+    fn test_key_is_stable() {
+        assert_eq!(
+            LlmCache::key(&["Anthropic", "Small", "hello"]),
+            LlmCache::key(&["Anthropic", "Small", "hello"])
+        );
+    }
+
+    #[test]
+    fn test_key_parts_are_separated() {
+        assert_ne!(LlmCache::key(&["ab", "c"]), LlmCache::key(&["a", "bc"]));
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fs, io::Write, path::PathBuf, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+use time::{format_description::BorrowedFormatItem, macros::format_description, OffsetDateTime};
+
+use crate::config::wkfl_data_dir;
+
+use super::{Message, Role};
+
+const ID_FORMAT: &[BorrowedFormatItem] =
+    format_description!("[year][month][day]-[hour][minute][second]-[subsecond digits:3]");
+const TITLE_LENGTH: usize = 60;
+
+/// A chat conversation saved to ~/.local/share/wkfl/chats/<id>.json so it
+/// can be continued later
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub system: Option<String>,
+    pub messages: Vec<Message>,
+    /// Whether the file exists yet, a new conversation's first save
+    /// mustn't replace another one's
+    #[serde(skip)]
+    saved: bool,
+}
+
+fn chats_dir() -> anyhow::Result<PathBuf> {
+    Ok(wkfl_data_dir()?.join("chats"))
+}
+
+fn now() -> OffsetDateTime {
+    SystemTime::now().into()
+}
+
+impl Conversation {
+    pub fn new(system: Option<String>) -> Self {
+        let now = now();
+        Self {
+            id: now.format(ID_FORMAT).expect("Format is valid"),
+            title: String::new(),
+            created_at: now.unix_timestamp(),
+            updated_at: now.unix_timestamp(),
+            system,
+            messages: vec![],
+            saved: false,
+        }
+    }
+
+    /// Records a turn, the first question becomes the title
+    pub fn push_turn(&mut self, query: String, response: Message) {
+        if self.title.is_empty() {
+            let first_line = query.lines().next().unwrap_or_default();
+            self.title = first_line.chars().take(TITLE_LENGTH).collect();
+        }
+        self.messages.push(Message {
+            role: Role::User,
+            content: query,
+        });
+        self.messages.push(response);
+        self.updated_at = now().unix_timestamp();
+    }
+
+    fn path(id: &str) -> anyhow::Result<PathBuf> {
+        Ok(chats_dir()?.join(format!("{}.json", id)))
+    }
+
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        fs::create_dir_all(chats_dir()?)?;
+        let path = Self::path(&self.id)?;
+        let contents = serde_json::to_string(self)?;
+        if self.saved {
+            fs::write(path, contents)?;
+        } else {
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .map_err(|err| {
+                    anyhow::anyhow!("Failed to create chat {}: {}", path.display(), err)
+                })?
+                .write_all(contents.as_bytes())?;
+            self.saved = true;
+        }
+        Ok(())
+    }
+
+    pub fn load(id: &str) -> anyhow::Result<Self> {
+        let path = Self::path(id)?;
+        if !path.exists() {
+            anyhow::bail!("No chat with id {}", id);
+        }
+        let mut conversation: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        conversation.saved = true;
+        Ok(conversation)
+    }
+
+    /// The most recently updated conversation
+    pub fn latest() -> anyhow::Result<Option<Self>> {
+        Ok(Self::list()?.into_iter().next())
+    }
+
+    /// All saved conversations, most recently updated first
+    pub fn list() -> anyhow::Result<Vec<Self>> {
+        let dir = chats_dir()?;
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut conversations: Vec<Self> = fs::read_dir(dir)?
+            .map_while(Result::ok)
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|contents| serde_json::from_str::<Self>(&contents).ok())
+            .map(|conversation| Self {
+                saved: true,
+                ..conversation
+            })
+            .collect();
+        conversations.sort_by_key(|conversation| std::cmp::Reverse(conversation.updated_at));
+        Ok(conversations)
+    }
+
+    pub fn delete(id: &str) -> anyhow::Result<()> {
+        let path = Self::path(id)?;
+        if !path.exists() {
+            anyhow::bail!("No chat with id {}", id);
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut transcript = String::new();
+        if let Some(system) = &self.system {
+            transcript.push_str(&format!("## System\n\n{}\n\n", system));
+        }
+        for message in &self.messages {
+            let heading = match message.role {
+                Role::User => "You",
+                Role::Assistant => "Assistant",
+                Role::System => "System",
+            };
+            transcript.push_str(&format!("## {}\n\n{}\n\n", heading, message.content));
+        }
+        transcript
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Conversation, Message, Role};
+
+    #[test]
+    fn test_title_from_first_question() {
+        let mut conversation = Conversation::new(None);
+        let response = || Message {
+            role: Role::Assistant,
+            content: "answer".to_string(),
+        };
+        conversation.push_turn("first question\nmore detail".to_string(), response());
+        conversation.push_turn("second question".to_string(), response());
+        assert_eq!(conversation.title, "first question");
+        assert_eq!(conversation.messages.len(), 4);
+    }
+}
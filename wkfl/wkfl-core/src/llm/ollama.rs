@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::errors::ConfigError;
+use crate::http::HttpClient;
+
+const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+#[derive(Debug, Serialize)]
+pub struct EmbedRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbedResponse {
+    pub embeddings: Vec<Vec<f32>>,
+}
+
+/// Client for a local Ollama server. Only used for embeddings, local
+/// models aren't recorded in the usage ledger since they're free.
+pub struct OllamaClient {
+    base_url: String,
+    model: String,
+    http: HttpClient,
+}
+
+impl OllamaClient {
+    pub fn new(base_url: String, model: String, http: HttpClient) -> Self {
+        Self {
+            base_url,
+            model,
+            http,
+        }
+    }
+
+    pub fn create_embeddings(&self, request: EmbedRequest) -> anyhow::Result<EmbedResponse> {
+        let http_request = self
+            .http
+            .post(&format!(
+                "{}/api/embed",
+                self.base_url.trim_end_matches('/')
+            ))
+            .set("Content-Type", "application/json");
+        let response = self.http.send_json(http_request, &request)?.into_json()?;
+        Ok(response)
+    }
+}
+
+impl super::LlmProvider for OllamaClient {
+    fn from_config(config: Config) -> anyhow::Result<Self> {
+        let embeddings_config = config
+            .embeddings
+            .ok_or(ConfigError::MissingKey("embeddings".to_string()))?;
+        Ok(Self::new(
+            embeddings_config
+                .base_url
+                .unwrap_or(DEFAULT_BASE_URL.to_string()),
+            embeddings_config
+                .model
+                .unwrap_or(DEFAULT_EMBEDDING_MODEL.to_string()),
+            HttpClient::from_config(&config.http)?,
+        ))
+    }
+}
+
+impl super::Embeddings for OllamaClient {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let response = self.create_embeddings(EmbedRequest {
+            model: &self.model,
+            input: texts,
+        })?;
+        Ok(response.embeddings)
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{resolve_secret, Config};
+use crate::errors::ConfigError;
+use crate::http::HttpClient;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+#[derive(Debug, Serialize)]
+pub struct EmbeddingsRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Embedding {
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub total_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingsResponse {
+    pub data: Vec<Embedding>,
+    pub usage: Usage,
+}
+
+/// Client for OpenAI's API, or any other with the same /embeddings
+/// endpoint (eg. llama.cpp's server or LM Studio)
+pub struct OpenAiClient {
+    base_url: String,
+    /// Local servers often don't need one
+    api_key: Option<String>,
+    model: String,
+    http: HttpClient,
+}
+
+impl OpenAiClient {
+    pub fn new(base_url: String, api_key: Option<String>, model: String, http: HttpClient) -> Self {
+        Self {
+            base_url,
+            api_key,
+            model,
+            http,
+        }
+    }
+
+    pub fn create_embeddings(
+        &self,
+        request: EmbeddingsRequest,
+    ) -> anyhow::Result<EmbeddingsResponse> {
+        let mut http_request = self
+            .http
+            .post(&format!(
+                "{}/embeddings",
+                self.base_url.trim_end_matches('/')
+            ))
+            .set("Content-Type", "application/json");
+        if let Some(api_key) = &self.api_key {
+            http_request = http_request.set("Authorization", &format!("Bearer {}", api_key));
+        }
+        let response: EmbeddingsResponse =
+            self.http.send_json(http_request, &request)?.into_json()?;
+        super::usage::record("openai", request.model, response.usage.prompt_tokens, 0);
+        Ok(response)
+    }
+}
+
+impl super::LlmProvider for OpenAiClient {
+    fn from_config(config: Config) -> anyhow::Result<Self> {
+        let embeddings_config = config
+            .embeddings
+            .ok_or(ConfigError::MissingKey("embeddings".to_string()))?;
+        let api_key = embeddings_config
+            .api_key
+            .map(|api_key| resolve_secret(&api_key))
+            .transpose()?;
+        Ok(Self::new(
+            embeddings_config
+                .base_url
+                .unwrap_or(DEFAULT_BASE_URL.to_string()),
+            api_key,
+            embeddings_config
+                .model
+                .unwrap_or(DEFAULT_EMBEDDING_MODEL.to_string()),
+            HttpClient::from_config(&config.http)?,
+        ))
+    }
+}
+
+impl super::Embeddings for OpenAiClient {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut response = self.create_embeddings(EmbeddingsRequest {
+            model: &self.model,
+            input: texts,
+        })?;
+        response.data.sort_by_key(|embedding| embedding.index);
+        Ok(response
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+}
@@ -1,10 +1,11 @@
 use std::str::FromStr;
 
-use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use crate::config::{resolve_secret, Config};
+use crate::config::{resolve_secret, Config, ModelIds};
+use crate::errors::ConfigError;
+use crate::http::HttpClient;
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -14,6 +15,9 @@ pub enum PerplexityModel {
     SonarPro,
     SonarReasoning,
     SonarReasoningPro,
+    /// Any other model ID, eg. from the `[models]` config
+    #[serde(untagged)]
+    Custom(String),
 }
 
 #[derive(Debug, Default, Serialize)]
@@ -67,23 +71,51 @@ pub struct PerplexityResponse {
 
 pub struct PerplexityClient {
     api_key: String,
+    models: ModelIds,
+    http: HttpClient,
 }
 
 impl PerplexityClient {
-    pub fn new(api_key: String) -> Self {
-        Self { api_key }
+    pub fn new(api_key: String, models: ModelIds, http: HttpClient) -> Self {
+        Self {
+            api_key,
+            models,
+            http,
+        }
+    }
+
+    fn pick_model(
+        &self,
+        model: Option<String>,
+        model_type: &super::ModelType,
+    ) -> anyhow::Result<PerplexityModel> {
+        super::pick_model(model, model_type, &self.models, |model_type| {
+            Ok(match model_type {
+                super::ModelType::Small => PerplexityModel::Sonar,
+                super::ModelType::Large => PerplexityModel::SonarPro,
+                super::ModelType::Thinking => PerplexityModel::SonarReasoningPro,
+            })
+        })
     }
 
     pub fn create_chat_completion(
         &self,
         request: PerplexityRequest,
     ) -> anyhow::Result<PerplexityResponse> {
-        let response = ureq::post("https://api.perplexity.ai/chat/completions")
+        let http_request = self
+            .http
+            .post("https://api.perplexity.ai/chat/completions")
             .set("Authorization", &format!("Bearer {}", self.api_key))
-            .set("Content-Type", "application/json")
-            .send_json(&request)?;
+            .set("Content-Type", "application/json");
+        let response = self.http.send_json(http_request, &request)?;
 
         let completion = response.into_json::<PerplexityResponse>()?;
+        super::usage::record(
+            "perplexity",
+            &super::usage::model_name(&completion.model),
+            completion.usage.prompt_tokens as u64,
+            completion.usage.completion_tokens as u64,
+        );
         Ok(completion)
     }
 }
@@ -201,9 +233,13 @@ impl super::LlmProvider for PerplexityClient {
     fn from_config(config: Config) -> anyhow::Result<Self> {
         let api_key_raw = config
             .perplexity_api_key
-            .ok_or(anyhow!("Missing perplexity_api_key in config"))?;
+            .ok_or(ConfigError::MissingKey("perplexity_api_key".to_string()))?;
         let api_key = resolve_secret(&api_key_raw)?;
-        Ok(Self::new(api_key))
+        Ok(Self::new(
+            api_key,
+            config.models.perplexity,
+            HttpClient::from_config(&config.http)?,
+        ))
     }
 }
 
@@ -212,11 +248,7 @@ impl super::GroundedChat for PerplexityClient {
         &self,
         request: super::GroundedChatRequest,
     ) -> anyhow::Result<super::GroundedChatResponse> {
-        let model = match request.model_type {
-            super::ModelType::Small => PerplexityModel::Sonar,
-            super::ModelType::Large => PerplexityModel::SonarPro,
-            super::ModelType::Thinking => PerplexityModel::SonarReasoningPro,
-        };
+        let model = self.pick_model(request.model, &request.model_type)?;
         let request = PerplexityRequest {
             messages: vec![super::Message {
                 role: super::Role::User,
@@ -252,6 +284,14 @@ impl super::GroundedChat for PerplexityClient {
             citations: super::CitationMetadata { sources, supports },
         })
     }
+
+    fn model_key(
+        &self,
+        model: Option<String>,
+        model_type: &super::ModelType,
+    ) -> anyhow::Result<String> {
+        super::model_id(&self.pick_model(model, model_type)?)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,153 @@
+use std::{fs, io, path::PathBuf, process::Command};
+
+use crate::config::{wkfl_config_dir, PromptsConfig};
+
+/// Commands to read the clipboard and primary selection, tried in order
+/// until one is installed. macOS doesn't have a primary selection.
+const CLIPBOARD_COMMANDS: &[(&str, &[&str], &[&str])] = &[
+    ("pbpaste", &[], &[]),
+    (
+        "wl-paste",
+        &["--no-newline"],
+        &["--no-newline", "--primary"],
+    ),
+    (
+        "xclip",
+        &["-out", "-selection", "clipboard"],
+        &["-out", "-selection", "primary"],
+    ),
+    (
+        "xsel",
+        &["--output", "--clipboard"],
+        &["--output", "--primary"],
+    ),
+];
+
+fn prompts_dir() -> anyhow::Result<PathBuf> {
+    Ok(wkfl_config_dir()?.join("prompts"))
+}
+
+/// Template named `name`, from ~/.config/wkfl/prompts/<name>.md or else
+/// the `[prompts.templates]` config section
+pub fn load_template(name: &str, config: &PromptsConfig) -> anyhow::Result<String> {
+    let path = prompts_dir()?.join(format!("{}.md", name));
+    if path.exists() {
+        return Ok(fs::read_to_string(path)?);
+    }
+    config.templates.get(name).cloned().ok_or_else(|| {
+        anyhow::anyhow!(
+            "No prompt template named {}, add {} or a [prompts.templates] entry",
+            name,
+            path.display()
+        )
+    })
+}
+
+/// Fills in the template's placeholders:
+///
+/// - `{query}`: the query given on the command line, appended to the end
+///   if the template doesn't use it
+/// - `{selection}`: the primary selection (the clipboard on macOS)
+/// - `{clipboard}`: the clipboard
+/// - `{file:path}`: contents of the file
+///
+/// Anything else in braces is left alone, templates often contain code.
+pub fn render_template(template: &str, maybe_query: Option<&str>) -> anyhow::Result<String> {
+    let mut rendered = substitute(template, |placeholder| match placeholder {
+        "query" => Some(Ok(maybe_query.unwrap_or_default().to_string())),
+        "selection" => Some(read_clipboard(true)),
+        "clipboard" => Some(read_clipboard(false)),
+        _ => {
+            let path = placeholder.strip_prefix("file:")?;
+            Some(
+                fs::read_to_string(path)
+                    .map_err(|err| anyhow::anyhow!("Can't read {} for template: {}", path, err)),
+            )
+        }
+    })?;
+    if let Some(query) = maybe_query {
+        if !template.contains("{query}") {
+            rendered = format!("{}\n\n{}", rendered.trim_end(), query);
+        }
+    }
+    Ok(rendered)
+}
+
+/// Replaces each `{placeholder}` that `value_for` knows about
+fn substitute(
+    template: &str,
+    mut value_for: impl FnMut(&str) -> Option<anyhow::Result<String>>,
+) -> anyhow::Result<String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after_start = &rest[start + 1..];
+        let value = after_start.find('}').and_then(|end| {
+            let value = value_for(&after_start[..end])?;
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                rendered.push_str(&value?);
+                rest = &after_start[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = after_start;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+fn read_clipboard(primary: bool) -> anyhow::Result<String> {
+    for (program, clipboard_args, primary_args) in CLIPBOARD_COMMANDS {
+        let args = if primary {
+            primary_args
+        } else {
+            clipboard_args
+        };
+        let output = match Command::new(program).args(*args).output() {
+            Ok(output) => output,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} failed: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        return Ok(String::from_utf8(output.stdout)?);
+    }
+    anyhow::bail!("Can't read the clipboard, install wl-clipboard, xclip or xsel")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_template, substitute};
+
+    #[test]
+    fn test_substitute_leaves_unknown_braces() {
+        let rendered = substitute("fn main() { {name} }", |placeholder| {
+            (placeholder == "name").then(|| Ok("wkfl".to_string()))
+        })
+        .unwrap();
+        assert_eq!(rendered, "fn main() { wkfl }");
+    }
+
+    #[test]
+    fn test_render_template_query() {
+        assert_eq!(
+            render_template("Review {query} carefully", Some("this")).unwrap(),
+            "Review this carefully"
+        );
+        assert_eq!(
+            render_template("Review this:\n", Some("code")).unwrap(),
+            "Review this:\n\ncode"
+        );
+    }
+}
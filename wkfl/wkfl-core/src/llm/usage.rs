@@ -0,0 +1,292 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::config::{get_config, wkfl_state_dir, ModelPrice};
+
+/// Prices in USD per million tokens, for models without one in the
+/// config's `[llm_prices]`
+const DEFAULT_PRICES: &[(&str, f64, f64)] = &[
+    ("claude-3-5-haiku-latest", 0.8, 4.0),
+    ("claude-3-5-sonnet-latest", 3.0, 15.0),
+    ("sonar", 1.0, 1.0),
+    ("sonar-pro", 3.0, 15.0),
+    ("sonar-reasoning", 1.0, 5.0),
+    ("sonar-reasoning-pro", 2.0, 8.0),
+    ("gemini-1.5-flash-002", 0.075, 0.3),
+    ("gemini-1.5-pro-002", 1.25, 5.0),
+    // Experimental models are free while in preview
+    ("gemini-2.0-flash-exp", 0.0, 0.0),
+    ("gemini-exp-1206", 0.0, 0.0),
+    ("gemini-2.0-flash-thinking-exp-01-21", 0.0, 0.0),
+    ("text-embedding-3-small", 0.02, 0.0),
+    ("text-embedding-3-large", 0.13, 0.0),
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub recorded_at: u64,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Usage recorded by this run of wkfl, for the cost estimate at the end
+static SESSION_USAGE: Mutex<Vec<UsageRecord>> = Mutex::new(Vec::new());
+
+fn usage_file() -> anyhow::Result<PathBuf> {
+    Ok(wkfl_state_dir()?.join("llm_usage.jsonl"))
+}
+
+/// Model name as sent to the provider, for the ledger
+pub fn model_name(model: &impl Serialize) -> String {
+    super::model_id(model).unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Records the tokens a provider reported using. Failing to write the
+/// ledger only warns, it shouldn't lose the response.
+pub fn record(provider: &str, model: &str, input_tokens: u64, output_tokens: u64) {
+    let usage = UsageRecord {
+        recorded_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0),
+        provider: provider.to_string(),
+        model: model.to_string(),
+        input_tokens,
+        output_tokens,
+    };
+    if let Err(err) = append_to_ledger(&usage) {
+        warn!("Failed to record LLM usage: {}", err);
+    }
+    SESSION_USAGE
+        .lock()
+        .expect("Nothing panics while holding the lock")
+        .push(usage);
+}
+
+fn append_to_ledger(usage: &UsageRecord) -> anyhow::Result<()> {
+    let path = usage_file()?;
+    fs::create_dir_all(path.parent().expect("Usage file is in a directory"))?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(usage)?)?;
+    Ok(())
+}
+
+fn read_ledger() -> anyhow::Result<Vec<UsageRecord>> {
+    let path = usage_file()?;
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        // Skip lines we can't parse rather than failing on a partial write
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+fn price_for(model: &str, prices: &BTreeMap<String, ModelPrice>) -> Option<ModelPrice> {
+    prices.get(model).cloned().or_else(|| {
+        DEFAULT_PRICES
+            .iter()
+            .find(|(name, _, _)| *name == model)
+            .map(|(_, input, output)| ModelPrice {
+                input: *input,
+                output: *output,
+            })
+    })
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct UsageSummary {
+    requests: u64,
+    input_tokens: u64,
+    output_tokens: u64,
+    /// None if any of the requests were to a model without a price
+    cost: Option<f64>,
+}
+
+fn summarize<'a>(
+    records: impl IntoIterator<Item = &'a UsageRecord>,
+    prices: &BTreeMap<String, ModelPrice>,
+) -> UsageSummary {
+    let mut summary = UsageSummary {
+        cost: Some(0.0),
+        ..UsageSummary::default()
+    };
+    for record in records {
+        summary.requests += 1;
+        summary.input_tokens += record.input_tokens;
+        summary.output_tokens += record.output_tokens;
+        summary.cost = match (summary.cost, price_for(&record.model, prices)) {
+            (Some(cost), Some(price)) => Some(
+                cost + (record.input_tokens as f64 * price.input
+                    + record.output_tokens as f64 * price.output)
+                    / 1_000_000.0,
+            ),
+            _ => None,
+        };
+    }
+    summary
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    match cost {
+        Some(cost) => format!("${:.4}", cost),
+        None => "unknown".to_string(),
+    }
+}
+
+/// One line estimate of what this run's LLM requests cost, if it made any
+pub fn print_session_cost() {
+    let session_usage = SESSION_USAGE
+        .lock()
+        .expect("Nothing panics while holding the lock");
+    if session_usage.is_empty() {
+        return;
+    }
+    let prices = get_config()
+        .map(|config| config.llm_prices)
+        .unwrap_or_default();
+    let summary = summarize(session_usage.iter(), &prices);
+    eprintln!(
+        "LLM usage: {} input + {} output tokens, estimated cost {}",
+        summary.input_tokens,
+        summary.output_tokens,
+        format_cost(summary.cost)
+    );
+}
+
+fn record_day(record: &UsageRecord) -> String {
+    OffsetDateTime::from_unix_timestamp(record.recorded_at as i64)
+        .map(|time| time.date().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Usage from the ledger over the last `days` days, per provider and model,
+/// and per day as well when `daily` is set
+pub fn print_usage(
+    days: u64,
+    daily: bool,
+    prices: &BTreeMap<String, ModelPrice>,
+) -> anyhow::Result<()> {
+    let since = SystemTime::now()
+        .checked_sub(Duration::from_secs(days * 24 * 60 * 60))
+        .unwrap_or(UNIX_EPOCH)
+        .duration_since(UNIX_EPOCH)?
+        .as_secs();
+    let records: Vec<UsageRecord> = read_ledger()?
+        .into_iter()
+        .filter(|record| record.recorded_at >= since)
+        .collect();
+    if records.is_empty() {
+        println!("No LLM usage recorded in the last {} days", days);
+        return Ok(());
+    }
+
+    let mut groups: BTreeMap<(String, String, String), Vec<&UsageRecord>> = BTreeMap::new();
+    for record in &records {
+        let day = if daily {
+            record_day(record)
+        } else {
+            String::new()
+        };
+        groups
+            .entry((day, record.provider.clone(), record.model.clone()))
+            .or_default()
+            .push(record);
+    }
+    let day_column = if daily {
+        format!("{:<11}", "day")
+    } else {
+        String::new()
+    };
+    println!(
+        "{}{:<12} {:<36} {:>8} {:>12} {:>12} {:>10}",
+        day_column, "provider", "model", "requests", "input", "output", "cost"
+    );
+    for ((day, provider, model), group) in &groups {
+        let summary = summarize(group.iter().copied(), prices);
+        let day_column = if daily {
+            format!("{:<11}", day)
+        } else {
+            String::new()
+        };
+        println!(
+            "{}{:<12} {:<36} {:>8} {:>12} {:>12} {:>10}",
+            day_column,
+            provider,
+            model,
+            summary.requests,
+            summary.input_tokens,
+            summary.output_tokens,
+            format_cost(summary.cost)
+        );
+    }
+    let total = summarize(&records, prices);
+    println!(
+        "\nTotal: {} requests, {} input + {} output tokens, estimated cost {}",
+        total.requests,
+        total.input_tokens,
+        total.output_tokens,
+        format_cost(total.cost)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{summarize, UsageRecord};
+    use crate::config::ModelPrice;
+
+    fn usage(model: &str, input_tokens: u64, output_tokens: u64) -> UsageRecord {
+        UsageRecord {
+            recorded_at: 0,
+            provider: "anthropic".to_string(),
+            model: model.to_string(),
+            input_tokens,
+            output_tokens,
+        }
+    }
+
+    #[test]
+    fn test_summarize_prices_models() {
+        let mut prices = BTreeMap::new();
+        prices.insert(
+            "claude-3-5-haiku-latest".to_string(),
+            ModelPrice {
+                input: 1.0,
+                output: 5.0,
+            },
+        );
+        let records = vec![
+            usage("claude-3-5-haiku-latest", 1_000_000, 100_000),
+            usage("claude-3-5-sonnet-latest", 100_000, 0),
+        ];
+        let summary = summarize(&records, &prices);
+        assert_eq!(summary.requests, 2);
+        assert_eq!(summary.input_tokens, 1_100_000);
+        assert_eq!(summary.output_tokens, 100_000);
+        // 1.0 + 0.5 from the config's haiku price, 0.3 from the default
+        // sonnet price
+        assert!((summary.cost.unwrap() - 1.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_unpriced_model_has_unknown_cost() {
+        let records = vec![usage("claude-3-5-haiku-latest", 10, 10), usage("new", 1, 1)];
+        assert_eq!(summarize(&records, &BTreeMap::new()).cost, None);
+    }
+}
@@ -0,0 +1,577 @@
+use anyhow::Ok;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::fmt;
+
+use crate::config::{resolve_secret, Config, ModelIds};
+use crate::errors::ConfigError;
+use crate::http::HttpClient;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub enum VertexAiModel {
+    #[default]
+    #[serde(rename = "gemini-2.0-flash-exp")]
+    Gemini20Flash,
+    #[serde(rename = "gemini-exp-1206")]
+    GeminiExp,
+    #[serde(rename = "gemini-2.0-flash-thinking-exp-01-21")]
+    Gemini20FlashThinking,
+    #[serde(rename = "gemini-1.5-flash-002")]
+    Gemini15Flash,
+    #[serde(rename = "gemini-1.5-pro-002")]
+    Gemini15Pro,
+    /// Any other model ID, eg. from the `[models]` config
+    #[serde(untagged)]
+    Custom(String),
+}
+
+impl fmt::Display for VertexAiModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // This is jank, but didn't require a new crate and respects
+        // the renames that I need for Deserialize. I already indirectly
+        // require serde_json
+        let json_repr = serde_json::to_string(self).unwrap();
+        write!(f, "{}", json_repr.trim_matches('"'))
+    }
+}
+
+impl VertexAiModel {
+    pub fn input_token_limit(&self) -> u64 {
+        match self {
+            VertexAiModel::Gemini20Flash
+            | VertexAiModel::Gemini20FlashThinking
+            | VertexAiModel::Gemini15Flash => 1_048_576,
+            VertexAiModel::GeminiExp | VertexAiModel::Gemini15Pro => 2_097_152,
+            // Every Gemini model takes at least this many
+            VertexAiModel::Custom(_) => 1_048_576,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexAiRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_content: Option<String>,
+    pub contents: Vec<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<GoogleSearchTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<GenerationConfig>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoogleSearchTool {
+    google_search: GoogleSearchOptions,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoogleSearchOptions {}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    pub parts: Vec<Part>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Model,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Part {
+    pub text: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+    /// Needs `response_mime_type` to be application/json
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_timestamp: Option<bool>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexAiResponse {
+    pub candidates: Vec<Candidate>,
+    pub usage_metadata: UsageMetadata,
+    pub model_version: VertexAiModel,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    pub content: Content,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grounding_metadata: Option<GroundingMetadata>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingMetadata {
+    pub grounding_chunks: Vec<GroundingChunk>,
+    pub grounding_supports: Vec<GroundingSupport>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingChunk {
+    pub web: super::Source,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingSupport {
+    pub segment: GroundingSupportSegment,
+    pub grounding_chunk_indices: Vec<u8>,
+    pub confidence_scores: Vec<f32>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingSupportSegment {
+    pub start_index: usize,
+    pub end_index: usize,
+    pub text: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    pub prompt_token_count: i32,
+    pub candidates_token_count: i32,
+    pub total_token_count: i32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CountTokensRequest<'a> {
+    contents: &'a [Content],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<&'a Content>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountTokensResponse {
+    pub total_tokens: u64,
+    #[serde(default)]
+    pub total_billable_characters: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmbeddingInstance<'a> {
+    pub content: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmbeddingsRequest<'a> {
+    pub instances: Vec<EmbeddingInstance<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingsResponse {
+    pub predictions: Vec<EmbeddingPrediction>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingPrediction {
+    pub embeddings: EmbeddingValues,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingValues {
+    pub values: Vec<f32>,
+    pub statistics: EmbeddingStatistics,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingStatistics {
+    pub token_count: f64,
+}
+
+pub struct VertexAiClient {
+    api_key: String,
+    project_id: String,
+    models: ModelIds,
+    http: HttpClient,
+}
+
+impl VertexAiClient {
+    pub fn new(api_key: String, project_id: String, models: ModelIds, http: HttpClient) -> Self {
+        Self {
+            api_key,
+            project_id,
+            models,
+            http,
+        }
+    }
+
+    fn model_url(&self, model: &impl fmt::Display, method: &str) -> String {
+        format!("https://us-central1-aiplatform.googleapis.com/v1/projects/{}/locations/us-central1/publishers/google/models/{}:{}", self.project_id, model, method)
+    }
+
+    pub fn create_chat_completion(
+        &self,
+        request: VertexAiRequest,
+        model: VertexAiModel,
+    ) -> anyhow::Result<VertexAiResponse> {
+        self.check_context_length(&request, &model)?;
+        let http_request = self
+            .http
+            .post(&self.model_url(&model, "generateContent"))
+            .set("Authorization", &format!("Bearer {}", self.api_key))
+            .set("Content-Type", "application/json");
+        let response = self.http.send_json(http_request, &request)?;
+        let completion = response.into_json::<VertexAiResponse>()?;
+        super::usage::record(
+            "vertex-ai",
+            &completion.model_version.to_string(),
+            completion.usage_metadata.prompt_token_count as u64,
+            completion.usage_metadata.candidates_token_count as u64,
+        );
+        Ok(completion)
+    }
+
+    pub fn create_embeddings(
+        &self,
+        request: EmbeddingsRequest,
+        model: &str,
+    ) -> anyhow::Result<EmbeddingsResponse> {
+        let http_request = self
+            .http
+            .post(&self.model_url(&model, "predict"))
+            .set("Authorization", &format!("Bearer {}", self.api_key))
+            .set("Content-Type", "application/json");
+        let response = self.http.send_json(http_request, &request)?;
+        let embeddings = response.into_json::<EmbeddingsResponse>()?;
+        let tokens: f64 = embeddings
+            .predictions
+            .iter()
+            .map(|prediction| prediction.embeddings.statistics.token_count)
+            .sum();
+        super::usage::record("vertex-ai", model, tokens as u64, 0);
+        Ok(embeddings)
+    }
+
+    pub fn count_tokens(
+        &self,
+        request: &VertexAiRequest,
+        model: &VertexAiModel,
+    ) -> anyhow::Result<CountTokensResponse> {
+        let count_request = CountTokensRequest {
+            contents: &request.contents,
+            system_instruction: request.system_instruction.as_ref(),
+        };
+        let http_request = self
+            .http
+            .post(&self.model_url(model, "countTokens"))
+            .set("Authorization", &format!("Bearer {}", self.api_key))
+            .set("Content-Type", "application/json");
+        let response = self.http.send_json(http_request, &count_request)?;
+        Ok(response.into_json::<CountTokensResponse>()?)
+    }
+
+    /// Only asks the API for an exact count when the prompt is big enough
+    /// that it might not fit, to avoid an extra request for every chat
+    fn check_context_length(
+        &self,
+        request: &VertexAiRequest,
+        model: &VertexAiModel,
+    ) -> anyhow::Result<()> {
+        let limit = model.input_token_limit();
+        let estimate: u64 = request
+            .contents
+            .iter()
+            .chain(request.system_instruction.iter())
+            .flat_map(|content| content.parts.iter())
+            .map(|part| super::estimate_tokens(&part.text))
+            .sum();
+        if estimate < limit / 2 {
+            return Ok(());
+        }
+        let count = self.count_tokens(request, model)?;
+        super::check_context_length(&model.to_string(), count.total_tokens, limit)
+    }
+
+    fn convert_to_standard_role(role: Option<Role>) -> super::Role {
+        match role {
+            Some(Role::User) => super::Role::User,
+            Some(Role::Model) => super::Role::Assistant,
+            None => super::Role::Assistant,
+        }
+    }
+
+    fn convert_from_standard_role(role: super::Role) -> Role {
+        match role {
+            super::Role::Assistant => Role::Model,
+            // System prompts go in system_instruction, not the contents
+            super::Role::User | super::Role::System => Role::User,
+        }
+    }
+
+    fn pick_model(
+        &self,
+        model: Option<String>,
+        model_type: &super::ModelType,
+    ) -> anyhow::Result<VertexAiModel> {
+        super::pick_model(model, model_type, &self.models, |model_type| {
+            Ok(match model_type {
+                super::ModelType::Small => VertexAiModel::Gemini20Flash,
+                super::ModelType::Large => VertexAiModel::GeminiExp,
+                super::ModelType::Thinking => VertexAiModel::Gemini20FlashThinking,
+            })
+        })
+    }
+}
+
+impl super::LlmProvider for VertexAiClient {
+    fn from_config(config: Config) -> anyhow::Result<Self> {
+        let vertex_ai_config = config
+            .vertex_ai
+            .ok_or(ConfigError::MissingKey("vertex_ai".to_string()))?;
+        let api_key = resolve_secret(&vertex_ai_config.api_key)?;
+        Ok(Self::new(
+            api_key,
+            vertex_ai_config.project_id,
+            config.models.vertex_ai,
+            HttpClient::from_config(&config.http)?,
+        ))
+    }
+}
+
+impl super::GroundedChat for VertexAiClient {
+    fn create_grounded_chat_completion(
+        &self,
+        request: super::GroundedChatRequest,
+    ) -> anyhow::Result<super::GroundedChatResponse> {
+        let vertex_request = VertexAiRequest {
+            contents: vec![Content {
+                role: Some(Role::User),
+                parts: vec![Part {
+                    text: request.query,
+                }],
+            }],
+            ..VertexAiRequest::default()
+        };
+        let model = self.pick_model(request.model, &request.model_type)?;
+        let response = self.create_chat_completion(vertex_request, model)?;
+        let candidate = response
+            .candidates
+            .into_iter()
+            .nth(0)
+            .expect("It should always return a canidate");
+        let grounding_metadata = candidate
+            .grounding_metadata
+            .unwrap_or(GroundingMetadata::default());
+        let mut supports: Vec<super::Support> = grounding_metadata
+            .grounding_supports
+            .into_iter()
+            .map(|support| super::Support {
+                start_index: support.segment.start_index,
+                end_index: support.segment.end_index,
+                text: support.segment.text,
+                source_indices: support.grounding_chunk_indices,
+            })
+            .collect();
+        supports.sort_by_key(|support| support.end_index);
+        let content = candidate
+            .content
+            .parts
+            .into_iter()
+            .nth(0)
+            .expect("There should always be one candidate")
+            .text;
+        Ok(super::GroundedChatResponse {
+            message: super::Message {
+                role: Self::convert_to_standard_role(candidate.content.role),
+                content,
+            },
+            citations: super::CitationMetadata {
+                sources: grounding_metadata
+                    .grounding_chunks
+                    .into_iter()
+                    .map(|chunk| chunk.web)
+                    .collect(),
+                supports,
+            },
+        })
+    }
+
+    fn model_key(
+        &self,
+        model: Option<String>,
+        model_type: &super::ModelType,
+    ) -> anyhow::Result<String> {
+        Ok(self.pick_model(model, model_type)?.to_string())
+    }
+}
+
+impl super::Chat for VertexAiClient {
+    fn create_message(&self, request: super::ChatRequest) -> anyhow::Result<super::ChatResponse> {
+        let mut contents: Vec<Content> = request
+            .history
+            .into_iter()
+            .map(|message| Content {
+                role: Some(Self::convert_from_standard_role(message.role)),
+                parts: vec![Part {
+                    text: message.content,
+                }],
+            })
+            .collect();
+        contents.push(Content {
+            role: Some(Role::User),
+            parts: vec![Part {
+                text: request.query,
+            }],
+        });
+        let vertex_request = VertexAiRequest {
+            contents,
+            system_instruction: request.system.map(|system| Content {
+                role: None,
+                parts: vec![Part { text: system }],
+            }),
+            generation_config: request.response_schema.map(|schema| GenerationConfig {
+                response_mime_type: Some("application/json".to_string()),
+                response_schema: Some(to_openapi_schema(schema)),
+                ..GenerationConfig::default()
+            }),
+            ..VertexAiRequest::default()
+        };
+        let model = self.pick_model(request.model, &request.model_type)?;
+        let response = self.create_chat_completion(vertex_request, model)?;
+        let candidate = response
+            .candidates
+            .into_iter()
+            .nth(0)
+            .expect("It should always return a canidate");
+        let content = candidate
+            .content
+            .parts
+            .into_iter()
+            .nth(0)
+            .expect("There should always be one candidate")
+            .text;
+        Ok(super::ChatResponse {
+            message: super::Message {
+                content,
+                role: Self::convert_to_standard_role(candidate.content.role),
+            },
+        })
+    }
+
+    fn model_key(
+        &self,
+        model: Option<String>,
+        model_type: &super::ModelType,
+    ) -> anyhow::Result<String> {
+        Ok(self.pick_model(model, model_type)?.to_string())
+    }
+}
+
+/// Vertex takes the OpenAPI subset of JSON schema and rejects the rest, so
+/// drop the keys it doesn't know. The answer is still checked against the
+/// whole schema afterwards.
+fn to_openapi_schema(schema: serde_json::Value) -> serde_json::Value {
+    match schema {
+        serde_json::Value::Object(object) => object
+            .into_iter()
+            .filter(|(key, _)| !matches!(key.as_str(), "$schema" | "$id" | "additionalProperties"))
+            .map(|(key, value)| (key, to_openapi_schema(value)))
+            .collect(),
+        serde_json::Value::Array(array) => array.into_iter().map(to_openapi_schema).collect(),
+        value => value,
+    }
+}
+
+#[cfg(feature = "embeddings")]
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-005";
+
+/// Vertex AI client for embeddings, which need a model picked in the
+/// config rather than by `ModelType`
+#[cfg(feature = "embeddings")]
+pub struct VertexAiEmbeddings {
+    client: VertexAiClient,
+    model: String,
+}
+
+#[cfg(feature = "embeddings")]
+impl super::LlmProvider for VertexAiEmbeddings {
+    fn from_config(mut config: Config) -> anyhow::Result<Self> {
+        let model = config
+            .embeddings
+            .take()
+            .and_then(|embeddings_config| embeddings_config.model)
+            .unwrap_or(DEFAULT_EMBEDDING_MODEL.to_string());
+        Ok(Self {
+            client: VertexAiClient::from_config(config)?,
+            model,
+        })
+    }
+}
+
+#[cfg(feature = "embeddings")]
+impl super::Embeddings for VertexAiEmbeddings {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let request = EmbeddingsRequest {
+            instances: texts
+                .iter()
+                .map(|text| EmbeddingInstance { content: text })
+                .collect(),
+        };
+        let response = self.client.create_embeddings(request, &self.model)?;
+        Ok(response
+            .predictions
+            .into_iter()
+            .map(|prediction| prediction.embeddings.values)
+            .collect())
+    }
+}
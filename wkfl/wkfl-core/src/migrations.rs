@@ -0,0 +1,82 @@
+use toml_edit::{value, DocumentMut};
+
+use crate::errors::ConfigError;
+
+/// Version written by `wkfl migrate-config`, bump it when adding a migration
+pub const CURRENT_CONFIG_VERSION: i64 = 1;
+
+/// Migration `i` upgrades the config from version `i` to `i + 1`. They edit
+/// the document rather than the parsed config so comments and layout are
+/// kept.
+const MIGRATIONS: &[fn(&mut DocumentMut)] = &[cli_provider_names];
+
+/// v0 -> v1: providers are named the same as on the command line, eg.
+/// `vertex-ai` instead of `VertexAI`
+fn cli_provider_names(config: &mut DocumentMut) {
+    for key in ["web_chat_provider", "chat_provider"] {
+        let Some(provider) = config.get(key).and_then(|item| item.as_str()) else {
+            continue;
+        };
+        let renamed = match provider {
+            "VertexAI" => "vertex-ai".to_string(),
+            other => other.to_lowercase(),
+        };
+        config[key] = value(renamed);
+    }
+}
+
+/// Configs from before versioning don't have a `config_version`
+pub fn config_version(config: &DocumentMut) -> i64 {
+    config
+        .get("config_version")
+        .and_then(|item| item.as_integer())
+        .unwrap_or(0)
+}
+
+/// Upgrades the config to the current version. Returns whether anything
+/// needed to change.
+pub fn migrate(config: &mut DocumentMut) -> anyhow::Result<bool> {
+    let version = config_version(config);
+    if version > CURRENT_CONFIG_VERSION {
+        return Err(ConfigError::NewerVersion(version).into());
+    }
+    if version == CURRENT_CONFIG_VERSION {
+        return Ok(false);
+    }
+    for migration in &MIGRATIONS[version.max(0) as usize..] {
+        migration(config);
+    }
+    config["config_version"] = value(CURRENT_CONFIG_VERSION);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use toml_edit::DocumentMut;
+
+    use super::{migrate, CURRENT_CONFIG_VERSION};
+    use crate::config::Config;
+
+    #[test]
+    fn test_migrate_unversioned_config() {
+        let mut config: DocumentMut =
+            "# My config\nweb_chat_provider = \"VertexAI\"\nchat_provider = \"Anthropic\"\n"
+                .parse()
+                .unwrap();
+        assert!(migrate(&mut config).unwrap());
+        let migrated = config.to_string();
+        assert!(migrated.starts_with("# My config\n"));
+        assert!(migrated.contains("web_chat_provider = \"vertex-ai\""));
+        assert!(migrated.contains("chat_provider = \"anthropic\""));
+        assert!(migrated.contains(&format!("config_version = {}", CURRENT_CONFIG_VERSION)));
+        toml::from_str::<Config>(&migrated).unwrap();
+
+        assert!(!migrate(&mut config).unwrap());
+    }
+
+    #[test]
+    fn test_newer_config_is_an_error() {
+        let mut config: DocumentMut = "config_version = 999\n".parse().unwrap();
+        assert!(migrate(&mut config).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::config::wkfl_cache_dir;
+use crate::llm::{self, Embeddings};
+use crate::notes::{self, ARCHIVE_DIR};
+
+/// Small enough for any embedding model's input limit
+const CHUNK_TOKENS: u64 = 1000;
+const BATCH_SIZE: usize = 16;
+
+/// Embedding of part of a note
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Relative to the notes directory
+    pub path: PathBuf,
+    /// Line the chunk starts on, counting from 1
+    pub line: usize,
+    /// When the note was modified, so changed notes are embedded again
+    pub modified: u64,
+    pub embedding: Vec<f32>,
+}
+
+/// Embeddings of every note, kept in one JSON file under ~/.cache/wkfl/.
+/// Searching compares the query with every entry, which is plenty fast
+/// for a personal notes directory.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoteIndex {
+    model: String,
+    notes_dir: PathBuf,
+    entries: Vec<IndexEntry>,
+}
+
+fn index_path() -> anyhow::Result<PathBuf> {
+    Ok(wkfl_cache_dir()?.join("notes_index.json"))
+}
+
+/// Splits a note into chunks of whole paragraphs, along with the line
+/// each one starts on
+fn chunk_note(contents: &str) -> Vec<(usize, String)> {
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+    let mut chunk_line = 1;
+    let mut paragraph = String::new();
+    let mut paragraph_line = 1;
+    let lines = contents.lines().chain([""]);
+    for (i, line) in lines.enumerate() {
+        if !line.trim().is_empty() {
+            if paragraph.is_empty() {
+                paragraph_line = i + 1;
+            }
+            paragraph.push_str(line);
+            paragraph.push('\n');
+            continue;
+        }
+        if paragraph.is_empty() {
+            continue;
+        }
+        let paragraph = std::mem::take(&mut paragraph);
+        if !chunk.is_empty()
+            && llm::estimate_tokens(&chunk) + llm::estimate_tokens(&paragraph) > CHUNK_TOKENS
+        {
+            chunks.push((chunk_line, std::mem::take(&mut chunk)));
+        }
+        if chunk.is_empty() {
+            chunk_line = paragraph_line;
+        } else {
+            chunk.push('\n');
+        }
+        chunk.push_str(&paragraph);
+    }
+    if !chunk.is_empty() {
+        chunks.push((chunk_line, chunk));
+    }
+    for (_, chunk) in chunks.iter_mut() {
+        // Only a single paragraph can be this long
+        if llm::estimate_tokens(chunk) > CHUNK_TOKENS {
+            *chunk = llm::truncate_to_tokens(chunk, CHUNK_TOKENS);
+        }
+    }
+    chunks
+}
+
+fn embed_batch(
+    client: &dyn Embeddings,
+    batch: &[(&PathBuf, u64, usize, String)],
+) -> anyhow::Result<Vec<IndexEntry>> {
+    let texts: Vec<String> = batch.iter().map(|(_, _, _, text)| text.clone()).collect();
+    let embeddings = client.embed(&texts)?;
+    if embeddings.len() != batch.len() {
+        anyhow::bail!(
+            "Asked for {} embeddings but got {}",
+            batch.len(),
+            embeddings.len()
+        );
+    }
+    Ok(batch
+        .iter()
+        .zip(embeddings)
+        .map(
+            |((relative_path, modified, line, _), embedding)| IndexEntry {
+                path: relative_path.to_path_buf(),
+                line: *line,
+                modified: *modified,
+                embedding,
+            },
+        )
+        .collect())
+}
+
+impl NoteIndex {
+    fn load(path: &Path) -> Option<Self> {
+        notes::load_notes_cache(path, "notes index")
+    }
+
+    /// Loads the index, embedding any notes that are new or changed since
+    /// it was last updated. Archived notes are indexed as well so they can
+    /// be searched with `--archived`.
+    pub fn update(notes_dir: &Path, client: &dyn Embeddings) -> anyhow::Result<Self> {
+        let path = index_path()?;
+        let mut index = Self::load(&path)
+            .filter(|index| index.model == client.model() && index.notes_dir == notes_dir)
+            .unwrap_or(Self {
+                model: client.model().to_string(),
+                notes_dir: notes_dir.to_path_buf(),
+                entries: vec![],
+            });
+
+        let mut notes: HashMap<PathBuf, u64> = HashMap::new();
+        for note_path in notes::find_notes(notes_dir, true)? {
+            let relative_path = note_path.strip_prefix(notes_dir)?.to_path_buf();
+            notes.insert(relative_path, notes::modified_secs(&note_path)?);
+        }
+        let entries_before = index.entries.len();
+        index
+            .entries
+            .retain(|entry| notes.get(&entry.path) == Some(&entry.modified));
+        let indexed: HashSet<&PathBuf> = index.entries.iter().map(|entry| &entry.path).collect();
+        let mut chunks: Vec<(&PathBuf, u64, usize, String)> = vec![];
+        for (relative_path, modified) in &notes {
+            if indexed.contains(relative_path) {
+                continue;
+            }
+            let contents = fs::read_to_string(notes_dir.join(relative_path))?;
+            for (line, chunk) in chunk_note(&contents) {
+                // The path says what kind of note it is, eg. a person or a day
+                let text = format!("{}\n\n{}", relative_path.display(), chunk);
+                chunks.push((relative_path, *modified, line, text));
+            }
+        }
+        if chunks.is_empty() && index.entries.len() == entries_before {
+            return Ok(index);
+        }
+
+        if !chunks.is_empty() {
+            info!("Embedding {} chunks of new or changed notes", chunks.len());
+        }
+        let mut new_entries = vec![];
+        let mut result = Ok(());
+        for batch in chunks.chunks(BATCH_SIZE) {
+            match embed_batch(client, batch) {
+                Ok(entries) => new_entries.extend(entries),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        // Batches that finished are kept so they aren't paid for again.
+        // A note split across the failed batch is dropped, it only counts
+        // as indexed once all of its chunks are.
+        if let Some((partial, _, _, _)) = chunks.get(new_entries.len()) {
+            new_entries.retain(|entry| entry.path != **partial);
+        }
+        index.entries.extend(new_entries);
+        notes::save_notes_cache(&path, &index)?;
+        result.map(|()| index)
+    }
+
+    /// Updates the paths of notes that were moved, eg. into the archive, so
+    /// they don't need embedding again
+    pub fn move_notes(notes_dir: &Path, moves: &[(PathBuf, PathBuf)]) -> anyhow::Result<()> {
+        let path = index_path()?;
+        let Some(mut index) = Self::load(&path).filter(|index| index.notes_dir == notes_dir) else {
+            return Ok(());
+        };
+        for entry in index.entries.iter_mut() {
+            if let Some(to) = notes::moved_to(moves, &entry.path) {
+                entry.path = to.clone();
+            }
+        }
+        notes::save_notes_cache(&path, &index)
+    }
+
+    /// The notes most similar to the query, best first, with the score and
+    /// line of each one's closest chunk
+    pub fn search(
+        &self,
+        query_embedding: &[f32],
+        include_archived: bool,
+        limit: usize,
+    ) -> Vec<(f32, &IndexEntry)> {
+        let mut scored: Vec<(f32, &IndexEntry)> = self
+            .entries
+            .iter()
+            .filter(|entry| include_archived || !entry.path.starts_with(ARCHIVE_DIR))
+            .map(|entry| (cosine_similarity(query_embedding, &entry.embedding), entry))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut seen = HashSet::new();
+        scored
+            .into_iter()
+            .filter(|(_, entry)| seen.insert(&entry.path))
+            .take(limit)
+            .collect()
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{chunk_note, cosine_similarity, IndexEntry, NoteIndex};
+
+    #[test]
+    fn test_chunk_note() {
+        let chunks = chunk_note("# Title\n\nFirst para\nstill first\n\n\nSecond\n");
+        assert_eq!(
+            chunks,
+            vec![(
+                1,
+                "# Title\n\nFirst para\nstill first\n\nSecond\n".to_string()
+            )]
+        );
+
+        let long_paragraph = "word ".repeat(500);
+        let contents = format!("{}\n\n{}\n", long_paragraph, long_paragraph);
+        let chunks = chunk_note(&contents);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].0, 3);
+        assert!(chunk_note("\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_search_ranks_notes_by_best_chunk() {
+        let entry = |path: &str, line: usize, embedding: Vec<f32>| IndexEntry {
+            path: PathBuf::from(path),
+            line,
+            modified: 0,
+            embedding,
+        };
+        let index = NoteIndex {
+            model: "test".to_string(),
+            notes_dir: PathBuf::from("/notes"),
+            entries: vec![
+                entry("topics/rust.md", 1, vec![0.5, 0.5]),
+                entry("topics/rust.md", 10, vec![1.0, 0.1]),
+                entry("topics/go.md", 1, vec![0.0, 1.0]),
+                entry("archive/2023/Mon_Jan_02.md", 1, vec![1.0, 0.0]),
+            ],
+        };
+        let results = index.search(&[1.0, 0.0], false, 10);
+        let found: Vec<(&str, usize)> = results
+            .iter()
+            .map(|(_, entry)| (entry.path.to_str().unwrap(), entry.line))
+            .collect();
+        assert_eq!(found, vec![("topics/rust.md", 10), ("topics/go.md", 1)]);
+
+        let results = index.search(&[1.0, 0.0], true, 1);
+        assert_eq!(
+            results[0].1.path,
+            PathBuf::from("archive/2023/Mon_Jan_02.md")
+        );
+    }
+}
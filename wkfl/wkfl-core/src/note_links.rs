@@ -0,0 +1,445 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::wkfl_cache_dir;
+use crate::notes::{self, LINKED_FROM_SECTION};
+
+/// A `[[wiki link]]` in a note
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct WikiLink {
+    /// What the link points at, without any `|alias` or `#heading`
+    pub target: String,
+    /// Line the link is on, counting from 1
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LinkedNote {
+    /// Relative to the notes directory
+    path: PathBuf,
+    modified: u64,
+    title: Option<String>,
+    links: Vec<WikiLink>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// The wiki links and tags in every note, kept in one JSON file under
+/// ~/.cache/wkfl/ and updated for the notes that changed since the last use
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkIndex {
+    notes_dir: PathBuf,
+    notes: Vec<LinkedNote>,
+    /// Built on the first lookup, so resolving every link in the notes
+    /// doesn't search all of them each time
+    #[serde(skip)]
+    targets: OnceLock<LinkTargets>,
+}
+
+/// Which note each normalized name refers to, as an index into the notes.
+/// The first note with a name wins.
+#[derive(Debug, Default)]
+struct LinkTargets {
+    by_path: HashMap<String, usize>,
+    by_stem: HashMap<String, usize>,
+    by_title: HashMap<String, usize>,
+}
+
+impl LinkTargets {
+    fn new(notes: &[LinkedNote]) -> Self {
+        let mut targets = Self::default();
+        for (i, note) in notes.iter().enumerate() {
+            targets
+                .by_path
+                .entry(normalize(&link_name(&note.path)))
+                .or_insert(i);
+            if let Some(stem) = note.path.file_stem() {
+                targets
+                    .by_stem
+                    .entry(normalize(&stem.to_string_lossy()))
+                    .or_insert(i);
+            }
+            if let Some(title) = &note.title {
+                targets.by_title.entry(normalize(title)).or_insert(i);
+            }
+        }
+        targets
+    }
+}
+
+fn index_path() -> anyhow::Result<PathBuf> {
+    Ok(wkfl_cache_dir()?.join("note_links.json"))
+}
+
+/// Finds the `[[wiki links]]` in a note. Links in code blocks and in the
+/// generated `## Linked from` section don't count.
+pub fn parse_wiki_links(contents: &str) -> Vec<WikiLink> {
+    let linked_from_heading = format!("## {}", LINKED_FROM_SECTION);
+    let mut links = vec![];
+    let mut in_code_block = false;
+    let mut in_linked_from = false;
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if line.starts_with("#") {
+            in_linked_from = line.trim_end() == linked_from_heading;
+        }
+        if in_code_block || in_linked_from {
+            continue;
+        }
+        let mut rest = line;
+        while let Some(start) = rest.find("[[") {
+            let Some(end) = rest[start + 2..].find("]]") else {
+                break;
+            };
+            let inner = &rest[start + 2..start + 2 + end];
+            let target = inner.split(['|', '#']).next().unwrap_or("").trim();
+            if !target.is_empty() {
+                links.push(WikiLink {
+                    target: target.to_string(),
+                    line: i + 1,
+                });
+            }
+            rest = &rest[start + 2 + end + 2..];
+        }
+    }
+    links
+}
+
+/// The note's YAML front matter, between the `---` lines at the top
+fn front_matter(contents: &str) -> Option<String> {
+    let mut lines = contents.lines();
+    if lines.next().map(str::trim_end) != Some("---") {
+        return None;
+    }
+    let front_matter: Vec<&str> = lines.take_while(|line| line.trim_end() != "---").collect();
+    Some(front_matter.join("\n"))
+}
+
+/// Finds the tags in a note, from its front matter and from `#tags` in the
+/// text. Tags need a letter in them so `#123` issue numbers don't count,
+/// and are lower cased.
+pub fn parse_tags(contents: &str) -> Vec<String> {
+    let mut tags = front_matter(contents)
+        .map(|front_matter| notes::note_metadata_list(&front_matter, "tags"))
+        .unwrap_or_default();
+    let mut in_code_block = false;
+    for line in contents.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+        for word in line.split_whitespace() {
+            let Some(tag) = word.strip_prefix('#') else {
+                continue;
+            };
+            let tag: String = tag
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                .collect();
+            if tag.chars().any(char::is_alphabetic) {
+                tags.push(tag);
+            }
+        }
+    }
+    let mut tags: Vec<String> = tags
+        .into_iter()
+        .map(|tag| tag.trim().trim_matches(['"', '\'', '#']).to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Lower case with spaces and dashes as underscores, the same way note
+/// names become paths
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// The name to link to a note by, its path without the extension
+pub fn link_name(relative_path: &Path) -> String {
+    relative_path
+        .with_extension("")
+        .to_string_lossy()
+        .to_string()
+}
+
+impl LinkIndex {
+    fn load(path: &Path) -> Option<Self> {
+        notes::load_notes_cache(path, "note links")
+    }
+
+    /// Loads the index, parsing the links and tags of any notes that are new or
+    /// changed since it was last updated
+    pub fn update(notes_dir: &Path) -> anyhow::Result<Self> {
+        let path = index_path()?;
+        let mut previous: HashMap<PathBuf, LinkedNote> = Self::load(&path)
+            .filter(|index| index.notes_dir == notes_dir)
+            .map(|index| {
+                index
+                    .notes
+                    .into_iter()
+                    .map(|note| (note.path.clone(), note))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut changed = false;
+        let mut linked_notes = vec![];
+        for note_path in notes::find_notes(notes_dir, true)? {
+            let relative_path = note_path.strip_prefix(notes_dir)?.to_path_buf();
+            let modified = notes::modified_secs(&note_path)?;
+            match previous.remove(&relative_path) {
+                Some(note) if note.modified == modified => linked_notes.push(note),
+                _ => {
+                    changed = true;
+                    let contents = fs::read_to_string(&note_path)?;
+                    linked_notes.push(LinkedNote {
+                        path: relative_path,
+                        modified,
+                        title: notes::note_title(&note_path),
+                        links: parse_wiki_links(&contents),
+                        tags: parse_tags(&contents),
+                    });
+                }
+            }
+        }
+        // Anything left over was deleted
+        changed |= !previous.is_empty();
+
+        let index = Self {
+            notes_dir: notes_dir.to_path_buf(),
+            notes: linked_notes,
+            targets: OnceLock::new(),
+        };
+        if changed {
+            notes::save_notes_cache(&path, &index)?;
+        }
+        Ok(index)
+    }
+
+    /// Updates the paths of notes that were moved, eg. into the archive, so
+    /// they don't need parsing again
+    pub fn move_notes(notes_dir: &Path, moves: &[(PathBuf, PathBuf)]) -> anyhow::Result<()> {
+        let path = index_path()?;
+        let Some(mut index) = Self::load(&path).filter(|index| index.notes_dir == notes_dir) else {
+            return Ok(());
+        };
+        for note in index.notes.iter_mut() {
+            if let Some(to) = notes::moved_to(moves, &note.path) {
+                note.path = to.clone();
+            }
+        }
+        index.notes.sort_by(|a, b| a.path.cmp(&b.path));
+        notes::save_notes_cache(&path, &index)
+    }
+
+    /// The note a link target is for, matched by its path without the
+    /// extension, then file name, then title. Case, spaces and dashes
+    /// don't matter.
+    pub fn resolve(&self, target: &str) -> Option<&Path> {
+        let target = normalize(target);
+        let targets = self.targets.get_or_init(|| LinkTargets::new(&self.notes));
+        targets
+            .by_path
+            .get(&target)
+            .or_else(|| targets.by_stem.get(&target))
+            .or_else(|| targets.by_title.get(&target))
+            .map(|&i| self.notes[i].path.as_path())
+    }
+
+    /// Every link to the note from another one, as the note it's in and
+    /// the line it's on
+    pub fn backlinks(&self, relative_path: &Path) -> Vec<(&Path, usize)> {
+        self.notes
+            .iter()
+            .filter(|note| note.path != relative_path)
+            .flat_map(|note| {
+                note.links
+                    .iter()
+                    .filter(|link| self.resolve(&link.target) == Some(relative_path))
+                    .map(|link| (note.path.as_path(), link.line))
+            })
+            .collect()
+    }
+
+    fn unarchived_notes(&self) -> impl Iterator<Item = &LinkedNote> {
+        self.notes
+            .iter()
+            .filter(|note| !note.path.starts_with(notes::ARCHIVE_DIR))
+    }
+
+    /// Every tag used outside of the archive with how many notes have it,
+    /// most used first
+    pub fn tag_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for tag in self.unarchived_notes().flat_map(|note| &note.tags) {
+            *counts.entry(tag).or_default() += 1;
+        }
+        let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// The notes outside of the archive with the tag, which can have a
+    /// leading `#`
+    pub fn tagged(&self, tag: &str) -> Vec<&Path> {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+        self.unarchived_notes()
+            .filter(|note| note.tags.contains(&tag))
+            .map(|note| note.path.as_path())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        sync::OnceLock,
+    };
+
+    use super::{parse_tags, parse_wiki_links, LinkIndex, LinkedNote, WikiLink};
+
+    #[test]
+    fn test_parse_wiki_links() {
+        let contents = "# Standup\n\nTalked to [[Alice Smith|Alice]] about [[topics/rust#Errors]]\n\n```\n[[not a link]]\n```\n[[]] [[unclosed\n\n## Linked from\n\n- [[people/bob]]\n";
+        assert_eq!(
+            parse_wiki_links(contents),
+            vec![
+                WikiLink {
+                    target: "Alice Smith".to_string(),
+                    line: 3
+                },
+                WikiLink {
+                    target: "topics/rust".to_string(),
+                    line: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let contents = "---\ntags: [Project-X, \"infra\"]\n---\n# Alice\n\nWorking on #project-x and #oncall, see #123 and a#b\n```\n#not-a-tag\n```\n";
+        assert_eq!(parse_tags(contents), vec!["infra", "oncall", "project-x"]);
+        assert_eq!(
+            parse_tags("---\ntitle: x\ntags:\n  - a\n  - b\nauthor: me\n---\n"),
+            vec!["a", "b"]
+        );
+        assert_eq!(parse_tags("tags: a\n## Heading"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_tags() {
+        let note = |path: &str, tags: &[&str]| LinkedNote {
+            path: PathBuf::from(path),
+            modified: 0,
+            title: None,
+            links: vec![],
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        };
+        let index = LinkIndex {
+            notes_dir: PathBuf::from("/notes"),
+            targets: OnceLock::new(),
+            notes: vec![
+                note("archive/2023/Mon_Jan_02.md", &["infra"]),
+                note("people/alice.md", &["infra", "project-x"]),
+                note("topics/deploys.md", &["project-x"]),
+                note("topics/ci.md", &["infra"]),
+            ],
+        };
+        assert_eq!(index.tag_counts(), vec![("infra", 2), ("project-x", 2)]);
+        assert_eq!(
+            index.tagged("#Project-X"),
+            vec![Path::new("people/alice.md"), Path::new("topics/deploys.md")]
+        );
+    }
+
+    #[test]
+    fn test_load_index_without_tags() {
+        // Indexes saved before tags were added
+        let index: LinkIndex = serde_json::from_str(
+            r#"{"notes_dir":"/notes","notes":[{"path":"inbox.md","modified":0,"title":null,"links":[]}]}"#,
+        )
+        .unwrap();
+        assert!(index.notes[0].tags.is_empty());
+    }
+
+    fn index() -> LinkIndex {
+        let note = |path: &str, title: &str, targets: &[&str]| LinkedNote {
+            path: PathBuf::from(path),
+            modified: 0,
+            title: Some(title.to_string()),
+            links: targets
+                .iter()
+                .map(|target| WikiLink {
+                    target: target.to_string(),
+                    line: 1,
+                })
+                .collect(),
+            tags: vec![],
+        };
+        LinkIndex {
+            notes_dir: PathBuf::from("/notes"),
+            targets: OnceLock::new(),
+            notes: vec![
+                note(
+                    "daily/2024/17/Wed_May_01.md",
+                    "Wednesday May 1st",
+                    &["Alice Smith"],
+                ),
+                note("people/alice_smith.md", "Alice Smith", &["release-process"]),
+                note(
+                    "topics/release_process.md",
+                    "Release Process",
+                    &["Release Process"],
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let index = index();
+        assert_eq!(
+            index.resolve("people/alice_smith"),
+            Some(Path::new("people/alice_smith.md"))
+        );
+        assert_eq!(
+            index.resolve("Release-Process"),
+            Some(Path::new("topics/release_process.md"))
+        );
+        assert_eq!(
+            index.resolve("wednesday may 1st"),
+            Some(Path::new("daily/2024/17/Wed_May_01.md"))
+        );
+        assert_eq!(index.resolve("Bob"), None);
+    }
+
+    #[test]
+    fn test_backlinks_skip_links_to_self() {
+        let index = index();
+        assert_eq!(
+            index.backlinks(Path::new("people/alice_smith.md")),
+            vec![(Path::new("daily/2024/17/Wed_May_01.md"), 1)]
+        );
+        assert_eq!(
+            index.backlinks(Path::new("topics/release_process.md")),
+            vec![(Path::new("people/alice_smith.md"), 1)]
+        );
+    }
+}
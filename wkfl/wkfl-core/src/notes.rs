@@ -0,0 +1,1031 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use time::format_description::BorrowedFormatItem;
+use time::macros::format_description;
+use time::Date;
+use time::Duration;
+use time::OffsetDateTime;
+use time::Weekday;
+
+use crate::config::wkfl_config_dir;
+use crate::utils::to_title_case;
+
+#[derive(Clone, Debug)]
+pub enum DailyNoteSpecifier {
+    Yesterday,
+    Today,
+    Tomorrow,
+    Date(Date),
+}
+
+impl FromStr for DailyNoteSpecifier {
+    type Err = anyhow::Error;
+
+    /// Parses the day names, a date (2024-05-01), an offset in days or
+    /// weeks (-3d, +1w) or a weekday (fri, friday), which is the latest one
+    /// up to and including today
+    fn from_str(day: &str) -> anyhow::Result<Self> {
+        let day = day.trim().to_lowercase();
+        match day.as_str() {
+            "yesterday" => return Ok(DailyNoteSpecifier::Yesterday),
+            "today" => return Ok(DailyNoteSpecifier::Today),
+            "tomorrow" => return Ok(DailyNoteSpecifier::Tomorrow),
+            _ => {}
+        }
+        parse_day(&day, date_from_note_specifier(&DailyNoteSpecifier::Today))
+            .map(DailyNoteSpecifier::Date)
+    }
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Monday,
+    Weekday::Tuesday,
+    Weekday::Wednesday,
+    Weekday::Thursday,
+    Weekday::Friday,
+    Weekday::Saturday,
+    Weekday::Sunday,
+];
+
+fn parse_day(day: &str, today: Date) -> anyhow::Result<Date> {
+    if let Ok(date) = Date::parse(day, format_description!("[year]-[month]-[day]")) {
+        return Ok(date);
+    }
+    if let Some(sign) = day.chars().next().filter(|c| *c == '-' || *c == '+') {
+        let days = parse_days(&day[1..])
+            .with_context(|| format!("Offsets are in days (-3d) or weeks (+1w), not {}", day))?;
+        let days = if sign == '-' { -days } else { days };
+        return today
+            .checked_add(checked_days(days)?)
+            .ok_or(anyhow::anyhow!("{} is out of range", day));
+    }
+    // At least three letters, so "t" isn't both Tuesday and Thursday
+    if day.len() >= 3 {
+        if let Some(weekday) = WEEKDAYS
+            .iter()
+            .find(|weekday| weekday.to_string().to_lowercase().starts_with(day))
+        {
+            let days_back = (today.weekday().number_days_from_monday() + 7
+                - weekday.number_days_from_monday())
+                % 7;
+            return Ok(today - Duration::days(days_back.into()));
+        }
+    }
+    anyhow::bail!(
+        "Can't tell which day {} is, use a date (2024-05-01), an offset (-3d, +1w) or a weekday (fri)",
+        day
+    )
+}
+
+/// Number of days in a length of time like 90d or 12w, which can't be
+/// negative
+pub fn parse_days(length: &str) -> anyhow::Result<i64> {
+    let (amount, days_per_unit) = if let Some(amount) = length.strip_suffix('d') {
+        (amount, 1)
+    } else if let Some(amount) = length.strip_suffix('w') {
+        (amount, 7)
+    } else {
+        anyhow::bail!("{} isn't in days (90d) or weeks (12w)", length);
+    };
+    let amount: i64 = amount
+        .parse()
+        .with_context(|| format!("Can't read the number in {}", length))?;
+    if amount < 0 {
+        anyhow::bail!("{} is negative", length);
+    }
+    amount
+        .checked_mul(days_per_unit)
+        .ok_or(anyhow::anyhow!("{} is too long", length))
+}
+
+/// A duration of some days, which unlike `Duration::days` doesn't panic
+/// when there are too many
+pub fn checked_days(days: i64) -> anyhow::Result<Duration> {
+    days.checked_mul(Duration::DAY.whole_seconds())
+        .map(Duration::seconds)
+        .ok_or(anyhow::anyhow!("{} days is too long", days))
+}
+
+pub enum NoteSpecifier {
+    Daily {
+        day: DailyNoteSpecifier,
+    },
+    Topic {
+        name: String,
+    },
+    Person {
+        who: String,
+    },
+    /// Today's meeting with the people it was with
+    Meeting {
+        title: String,
+        with: Vec<String>,
+    },
+}
+
+pub const TOPICS_DIR: &str = "topics";
+pub const PEOPLE_DIR: &str = "people";
+pub const MEETINGS_DIR: &str = "meetings";
+pub const ARCHIVE_DIR: &str = "archive";
+pub const TEMPLATES_DIR: &str = "templates";
+pub const SNIPPETS_DIR: &str = "snippets";
+/// Section listing the notes that link to a note, kept up to date by wkfl
+pub const LINKED_FROM_SECTION: &str = "Linked from";
+const DAILY_DIR: &str = "daily";
+
+const DAILY_NOTE_FORMAT: &[BorrowedFormatItem] = format_description!("daily/[year repr:full]/[week_number repr:sunday]/[weekday repr:short]_[month repr:short]_[day].md");
+/// The week directory in `DAILY_NOTE_FORMAT`, weeks start on Sunday
+const DAILY_NOTE_WEEK_FORMAT: &[BorrowedFormatItem] =
+    format_description!("[week_number repr:sunday]");
+const DAILY_NOTE_TITLE_FORMAT: &[BorrowedFormatItem] =
+    format_description!("[weekday repr:long] [month repr:long] [day padding:none]");
+
+fn get_day_suffix<'a>(day: u8) -> &'a str {
+    match day {
+        1 | 21 | 31 => "st",
+        2 | 22 => "nd",
+        3 | 23 => "rd",
+        _ => "th",
+    }
+}
+
+fn get_path_for_topic(topic_name: &str) -> String {
+    let name_in_path = topic_name
+        .to_lowercase()
+        .replace(" ", "_")
+        .replace("-", "_");
+    format!("{}/{}.md", TOPICS_DIR, name_in_path)
+}
+
+fn get_path_for_person(topic_name: &str) -> String {
+    let name_in_path = topic_name
+        .to_lowercase()
+        .replace(" ", "_")
+        .replace("-", "_");
+    format!("{}/{}.md", PEOPLE_DIR, name_in_path)
+}
+
+fn get_path_for_meeting(date: Date, title: &str) -> String {
+    let name_in_path = title.to_lowercase().replace(" ", "_").replace("-", "_");
+    format!("{}/{}_{}.md", MEETINGS_DIR, date, name_in_path)
+}
+
+/// `[[wiki link]]` to a note at a path from `format_note_path`
+fn wiki_link(note_path: &str, text: &str) -> String {
+    format!(
+        "[[{}|{}]]",
+        note_path.strip_suffix(".md").unwrap_or(note_path),
+        text
+    )
+}
+
+/// Links to the person note of each person at a meeting
+fn attendee_links(with: &[String]) -> String {
+    with.iter()
+        .map(|who| wiki_link(&get_path_for_person(who), who))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn date_from_note_specifier(note_specifier: &DailyNoteSpecifier) -> Date {
+    let cur_time: OffsetDateTime = SystemTime::now().into();
+    let cur_date: Date = cur_time.date();
+    match note_specifier {
+        // Current date isn't going to be min date
+        DailyNoteSpecifier::Yesterday => cur_date.previous_day().unwrap(),
+        DailyNoteSpecifier::Today => cur_date,
+        // Current date isn't going to be max date
+        DailyNoteSpecifier::Tomorrow => cur_date.next_day().unwrap(),
+        DailyNoteSpecifier::Date(date) => *date,
+    }
+}
+
+pub fn format_note_path(note_specifier: &NoteSpecifier) -> String {
+    match note_specifier {
+        NoteSpecifier::Topic { name } => get_path_for_topic(name),
+        NoteSpecifier::Daily { day } => date_from_note_specifier(day)
+            .format(DAILY_NOTE_FORMAT)
+            .unwrap(),
+        NoteSpecifier::Person { who } => get_path_for_person(who),
+        NoteSpecifier::Meeting { title, .. } => {
+            get_path_for_meeting(date_from_note_specifier(&DailyNoteSpecifier::Today), title)
+        }
+    }
+}
+
+pub fn note_template(note_specifier: &NoteSpecifier) -> String {
+    match note_specifier {
+        NoteSpecifier::Daily { day } => {
+            let date = date_from_note_specifier(day);
+            let date_str = date.format(DAILY_NOTE_TITLE_FORMAT).unwrap();
+            let day_suffix = get_day_suffix(date.day());
+            format!("# {}{}\n\n## ", date_str, day_suffix)
+        }
+        NoteSpecifier::Topic { name } => format!("# {}", to_title_case(name)),
+        NoteSpecifier::Person { who } => format!("# {}", who),
+        NoteSpecifier::Meeting { title, with } => format!(
+            "# {}\n\nDate: {}\nWith: {}\n\n## Notes\n\n",
+            title,
+            date_from_note_specifier(&DailyNoteSpecifier::Today),
+            attendee_links(with)
+        ),
+    }
+}
+
+/// Placeholder values for a new note's template that come from the note
+/// itself. Topic, person and meeting notes use today's date.
+pub fn note_template_vars(note_specifier: &NoteSpecifier) -> Vec<(&'static str, Option<String>)> {
+    let date = match note_specifier {
+        NoteSpecifier::Daily { day } => date_from_note_specifier(day),
+        _ => date_from_note_specifier(&DailyNoteSpecifier::Today),
+    };
+    let title = note_template(note_specifier)
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("# "))
+        .map(|title| title.to_string());
+    let name = match note_specifier {
+        NoteSpecifier::Daily { .. } => None,
+        NoteSpecifier::Topic { name } => Some(name.clone()),
+        NoteSpecifier::Person { who } => Some(who.clone()),
+        NoteSpecifier::Meeting { title, .. } => Some(title.clone()),
+    };
+    let with = match note_specifier {
+        NoteSpecifier::Meeting { with, .. } => Some(attendee_links(with)),
+        _ => None,
+    };
+    vec![
+        ("title", title),
+        ("name", name),
+        ("date", Some(date.to_string())),
+        ("weekday", Some(date.weekday().to_string())),
+        // The same week as the daily note's directory
+        ("week", date.format(DAILY_NOTE_WEEK_FORMAT).ok()),
+        ("with", with),
+    ]
+}
+
+/// User provided template for new notes of this kind, named
+/// <daily|topic|person|meeting>.md. One in the notes directory's templates/ wins
+/// over one in ~/.config/wkfl/templates/, so a shared notes repo can set
+/// the structure for everyone using it.
+pub fn user_note_template(notes_dir: &Path, note_specifier: &NoteSpecifier) -> Option<String> {
+    let kind = match note_specifier {
+        NoteSpecifier::Daily { .. } => "daily",
+        NoteSpecifier::Topic { .. } => "topic",
+        NoteSpecifier::Person { .. } => "person",
+        NoteSpecifier::Meeting { .. } => "meeting",
+    };
+    let file_name = format!("{}.md", kind);
+    let config_templates_dir = wkfl_config_dir().ok().map(|dir| dir.join(TEMPLATES_DIR));
+    [Some(notes_dir.join(TEMPLATES_DIR)), config_templates_dir]
+        .into_iter()
+        .flatten()
+        .find_map(|dir| fs::read_to_string(dir.join(&file_name)).ok())
+}
+
+/// Contents for a new note, from the user's template if they have one.
+/// Templates can use `{{name}}` or `{name}` placeholders.
+pub fn new_note_contents(
+    notes_dir: &Path,
+    note_specifier: &NoteSpecifier,
+    template_vars: &[(&str, Option<String>)],
+) -> String {
+    match user_note_template(notes_dir, note_specifier) {
+        Some(user_template) => render_note_template(&user_template, template_vars),
+        None => note_template(note_specifier),
+    }
+}
+
+/// Adds `text` to the end of the `## <section>` in today's daily note, for
+/// commands that log to the daily note. The note and section are created if
+/// they don't exist yet.
+pub fn append_to_daily(
+    notes_dir: &Path,
+    section: &str,
+    text: &str,
+    template_vars: &[(&str, Option<String>)],
+) -> anyhow::Result<PathBuf> {
+    let today = NoteSpecifier::Daily {
+        day: DailyNoteSpecifier::Today,
+    };
+    append_to_note(notes_dir, &today, section, text, template_vars)
+}
+
+/// Same as `append_to_daily` for any note
+pub fn append_to_note(
+    notes_dir: &Path,
+    note_specifier: &NoteSpecifier,
+    section: &str,
+    text: &str,
+    template_vars: &[(&str, Option<String>)],
+) -> anyhow::Result<PathBuf> {
+    let note_path = notes_dir.join(format_note_path(note_specifier));
+    let contents = if note_path.exists() {
+        fs::read_to_string(&note_path)?
+    } else {
+        fs::create_dir_all(note_path.parent().unwrap())?;
+        new_note_contents(notes_dir, note_specifier, template_vars)
+    };
+    fs::write(&note_path, append_to_section(&contents, section, text))?;
+    Ok(note_path)
+}
+
+/// The line added to each attendee's person note for today's meeting
+pub fn meeting_backlink(title: &str) -> String {
+    let today = date_from_note_specifier(&DailyNoteSpecifier::Today);
+    format!(
+        "- {} {}",
+        today,
+        wiki_link(&get_path_for_meeting(today, title), title)
+    )
+}
+
+fn is_heading(line: &str) -> bool {
+    line.starts_with("# ") || line.starts_with("## ") || line.trim_end() == "##"
+}
+
+/// Whether each line is in a fenced code block, fences included, so a
+/// `# comment` in a shell snippet isn't taken for a heading
+fn in_code_block(lines: &[&str]) -> Vec<bool> {
+    let mut in_block = false;
+    lines
+        .iter()
+        .map(|line| {
+            let fence = line.trim_start().starts_with("```");
+            let inside = in_block || fence;
+            if fence {
+                in_block = !in_block;
+            }
+            inside
+        })
+        .collect()
+}
+
+/// Start and end of the `heading`'s section, which runs up to the next
+/// heading outside a code block
+fn find_section(lines: &[&str], heading: &str) -> Option<(usize, usize)> {
+    let code = in_code_block(lines);
+    let start = (0..lines.len()).find(|i| !code[*i] && lines[*i].trim_end() == heading)?;
+    let end = (start + 1..lines.len())
+        .find(|i| !code[*i] && is_heading(lines[*i]))
+        .unwrap_or(lines.len());
+    Some((start, end))
+}
+
+/// Moves headings in text added to a section below the section's level, so
+/// headings in eg. a saved LLM answer don't end the section early
+fn demote_headings(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let code = in_code_block(&lines);
+    lines
+        .iter()
+        .zip(code)
+        .map(|(line, code)| {
+            if !code && is_heading(line) {
+                format!("##{}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Appends `text` after the last line of the `## <section>` heading's
+/// section. A missing section is added at the end of the note, taking over
+/// an empty `## ` heading left by the daily note template if there is one.
+fn append_to_section(contents: &str, section: &str, text: &str) -> String {
+    let heading = format!("## {}", section);
+    let text = demote_headings(text.trim_matches('\n'));
+    let mut lines: Vec<&str> = contents.lines().collect();
+    let Some((start, end)) = find_section(&lines, &heading) else {
+        let trim_blank_lines = |lines: &mut Vec<&str>| {
+            while lines.last().is_some_and(|line| line.trim().is_empty()) {
+                lines.pop();
+            }
+        };
+        trim_blank_lines(&mut lines);
+        if lines.last().is_some_and(|line| line.trim_end() == "##") {
+            lines.pop();
+            trim_blank_lines(&mut lines);
+        }
+        let mut appended = lines.join("\n");
+        if !appended.is_empty() {
+            appended.push_str("\n\n");
+        }
+        return format!("{}{}\n\n{}\n", appended, heading, text);
+    };
+    let last = (start..end)
+        .rev()
+        .find(|i| !lines[*i].trim().is_empty())
+        .expect("The heading isn't blank");
+    // List items go straight after each other, anything else gets a
+    // paragraph break
+    let separator = if last == start || !(lines[last].starts_with("- ") && text.starts_with("- ")) {
+        "\n\n"
+    } else {
+        "\n"
+    };
+    let mut appended = lines[..=last].join("\n");
+    appended.push_str(separator);
+    appended.push_str(&text);
+    appended.push('\n');
+    if end < lines.len() {
+        appended.push('\n');
+        appended.push_str(&lines[end..].join("\n"));
+        appended.push('\n');
+    }
+    appended
+}
+
+/// The text in the `## <section>`, without the heading and surrounding
+/// blank lines. None if the note doesn't have the section.
+pub fn section_text(contents: &str, section: &str) -> Option<String> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let (start, end) = find_section(&lines, &format!("## {}", section))?;
+    Some(
+        lines[start + 1..end]
+            .join("\n")
+            .trim_matches('\n')
+            .to_string(),
+    )
+}
+
+/// Replaces everything in the `## <section>` with `text`, adding the
+/// section at the end of the note if it's missing. Empty `text` removes
+/// the section.
+pub fn replace_section(contents: &str, section: &str, text: &str) -> String {
+    let heading = format!("## {}", section);
+    let text = demote_headings(text.trim_matches('\n'));
+    let lines: Vec<&str> = contents.lines().collect();
+    let (before, after) = match find_section(&lines, &heading) {
+        Some((start, end)) => (&lines[..start], &lines[end..]),
+        None if text.is_empty() => return contents.to_string(),
+        None => (&lines[..], &lines[lines.len()..]),
+    };
+    let before = before.join("\n");
+    let after = after.join("\n");
+    let mut parts = vec![];
+    if !before.trim().is_empty() {
+        parts.push(before.trim_end_matches('\n').to_string());
+    }
+    if !text.is_empty() {
+        parts.push(format!("{}\n\n{}", heading, text));
+    }
+    if !after.trim().is_empty() {
+        parts.push(after.trim_end_matches('\n').to_string());
+    }
+    let mut replaced = parts.join("\n\n");
+    replaced.push('\n');
+    replaced
+}
+
+/// Replaces `{{name}}` placeholders with their values. Placeholders
+/// without a value are left empty rather than in the note.
+pub fn render_template(template: &str, vars: &[(&str, Option<String>)]) -> String {
+    let mut rendered = template.to_string();
+    for (name, value) in vars {
+        rendered = rendered.replace(&format!("{{{{{}}}}}", name), value.as_deref().unwrap_or(""));
+    }
+    rendered
+}
+
+/// `render_template` plus single brace placeholders, which snippets can't
+/// have since they'd clash with shell syntax like `${branch}`
+fn render_note_template(template: &str, vars: &[(&str, Option<String>)]) -> String {
+    let mut rendered = render_template(template, vars);
+    for (name, value) in vars {
+        rendered = rendered.replace(&format!("{{{}}}", name), value.as_deref().unwrap_or(""));
+    }
+    rendered
+}
+
+/// Lists the names of the notes in a sub directory of the notes directory.
+/// The names are the file stems, which map back to the same note path.
+pub fn list_note_names(notes_dir: &Path, sub_dir: &str) -> anyhow::Result<Vec<String>> {
+    let dir = notes_dir.join(sub_dir);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .map_while(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .filter_map(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Recursively finds all the markdown notes in the notes directory. The
+/// archive is skipped unless `include_archived` is set, templates always are.
+pub fn find_notes(notes_dir: &Path, include_archived: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let mut notes = vec![];
+    let mut dirs_to_check = vec![notes_dir.to_path_buf()];
+    while let Some(current_dir) = dirs_to_check.pop() {
+        if !current_dir.exists() {
+            continue;
+        }
+        for entry in fs::read_dir(&current_dir)?.map_while(Result::ok) {
+            let path = entry.path();
+            let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let is_skipped_dir = path == notes_dir.join(TEMPLATES_DIR)
+                || (!include_archived && path == notes_dir.join(ARCHIVE_DIR));
+            if is_hidden || is_skipped_dir {
+                continue;
+            }
+            if path.is_dir() {
+                dirs_to_check.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "md") {
+                notes.push(path);
+            }
+        }
+    }
+    notes.sort();
+    Ok(notes)
+}
+
+/// Same as `find_notes` but the most recently modified notes come first
+pub fn find_notes_by_recency(
+    notes_dir: &Path,
+    include_archived: bool,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut notes: Vec<(PathBuf, SystemTime)> = find_notes(notes_dir, include_archived)?
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (path, modified)
+        })
+        .collect();
+    notes.sort_by(|(_, a), (_, b)| b.cmp(a));
+    Ok(notes.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Title of the note, taken from its first heading
+pub fn note_title(note_path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(note_path).ok()?;
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+}
+
+/// Value of a `key: value` line in the note, eg. `email: a@example.com`.
+/// Keys are matched case insensitively and can be in a list item. Only
+/// the first line with the key counts, an empty value there is `None`.
+pub fn note_metadata(contents: &str, key: &str) -> Option<String> {
+    let (_, value) = find_metadata(contents, key)?;
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Values of a list in the note, which can be a flow list (`tags: [a, b]`),
+/// comma separated or a block list of `- a` lines after an empty `tags:`
+pub fn note_metadata_list(contents: &str, key: &str) -> Vec<String> {
+    let Some((line, value)) = find_metadata(contents, key) else {
+        return vec![];
+    };
+    if !value.is_empty() {
+        return value
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect();
+    }
+    contents
+        .lines()
+        .skip(line + 1)
+        .map_while(|line| line.trim_start().strip_prefix("- "))
+        .map(|item| item.trim().to_string())
+        .collect()
+}
+
+/// Index and trimmed value of the first line with the key
+fn find_metadata<'a>(contents: &'a str, key: &str) -> Option<(usize, &'a str)> {
+    contents.lines().enumerate().find_map(|(i, line)| {
+        let line = line
+            .trim_start()
+            .trim_start_matches(['-', '*'])
+            .trim_start();
+        let (line_key, value) = line.split_once(':')?;
+        line_key
+            .trim()
+            .eq_ignore_ascii_case(key)
+            .then(|| (i, value.trim()))
+    })
+}
+
+/// Short label for the type of note based on the directory it is in
+pub fn note_kind(relative_path: &Path) -> &'static str {
+    match relative_path
+        .components()
+        .next()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .as_deref()
+    {
+        Some(DAILY_DIR) => "daily",
+        Some(TOPICS_DIR) => "topic",
+        Some(PEOPLE_DIR) => "person",
+        Some(MEETINGS_DIR) => "meeting",
+        Some(SNIPPETS_DIR) => "snippet",
+        _ => "note",
+    }
+}
+
+/// Finds all the daily notes (outside of the archive) along with the date
+/// each one is for
+pub fn find_daily_notes(notes_dir: &Path) -> anyhow::Result<Vec<(PathBuf, Date)>> {
+    let daily_notes = find_notes(&notes_dir.join(DAILY_DIR), false)?
+        .into_iter()
+        .filter_map(|path| {
+            let relative_path = path.strip_prefix(notes_dir).ok()?;
+            let date = Date::parse(&relative_path.to_string_lossy(), DAILY_NOTE_FORMAT).ok()?;
+            Some((path, date))
+        })
+        .collect();
+    Ok(daily_notes)
+}
+
+const CARRIED_OVER_SECTION: &str = "Carried over";
+
+fn is_unfinished_task(line: &str) -> bool {
+    let line = line.trim_start();
+    ["- [ ]", "* [ ]"]
+        .iter()
+        .any(|prefix| line == *prefix || line.starts_with(&format!("{} ", prefix)))
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Takes the unchecked task list items out of a note, along with anything
+/// nested under them. Returns the note with those items marked as migrated
+/// (`- [>]`) and the items, dedented so each one is a top level item.
+fn take_unfinished_tasks(contents: &str) -> (String, Vec<String>) {
+    let mut lines: Vec<String> = contents.lines().map(|line| line.to_string()).collect();
+    let mut tasks = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        if !is_unfinished_task(&lines[i]) {
+            i += 1;
+            continue;
+        }
+        let task_indent = indent(&lines[i]);
+        let end = lines[i + 1..]
+            .iter()
+            .position(|line| line.trim().is_empty() || indent(line) <= task_indent)
+            .map_or(lines.len(), |offset| i + 1 + offset);
+        // Dedent by the task's own whitespace rather than a byte count, which
+        // could land inside a multi-byte space in a nested line
+        let prefix = &lines[i][..task_indent];
+        let task: Vec<&str> = lines[i..end]
+            .iter()
+            .map(|line| line.strip_prefix(prefix).unwrap_or(line.trim_start()))
+            .collect();
+        tasks.push(task.join("\n"));
+        lines[i] = lines[i].replacen("[ ]", "[>]", 1);
+        i = end;
+    }
+    let mut updated = lines.join("\n");
+    if contents.ends_with('\n') {
+        updated.push('\n');
+    }
+    (updated, tasks)
+}
+
+/// Copies the unfinished tasks in the latest daily note before today into
+/// today's, under `## Carried over`, and marks them as migrated in the old
+/// note so they only get carried over once. Returns the note they came from
+/// and how many tasks there were, or None if there's no earlier note.
+pub fn rollover_tasks(
+    notes_dir: &Path,
+    template_vars: &[(&str, Option<String>)],
+) -> anyhow::Result<Option<(PathBuf, usize)>> {
+    let today = date_from_note_specifier(&DailyNoteSpecifier::Today);
+    let Some((previous_note, _)) = find_daily_notes(notes_dir)?
+        .into_iter()
+        .filter(|(_, date)| *date < today)
+        .max_by_key(|(_, date)| *date)
+    else {
+        return Ok(None);
+    };
+    let (migrated, tasks) = take_unfinished_tasks(&fs::read_to_string(&previous_note)?);
+    if !tasks.is_empty() {
+        // Today's note first so the tasks can't be lost if this fails
+        append_to_daily(
+            notes_dir,
+            CARRIED_OVER_SECTION,
+            &tasks.join("\n"),
+            template_vars,
+        )?;
+        fs::write(&previous_note, migrated)?;
+    }
+    Ok(Some((previous_note, tasks.len())))
+}
+
+/// Where a daily note is moved to when archived, archive/<year>/<file name>
+pub fn archived_daily_note_path(notes_dir: &Path, note_path: &Path, date: Date) -> PathBuf {
+    let mut archive_path = notes_dir.join(ARCHIVE_DIR);
+    archive_path.push(date.year().to_string());
+    if let Some(file_name) = note_path.file_name() {
+        archive_path.push(file_name);
+    }
+    archive_path
+}
+
+/// When a note was last modified, in seconds, so the caches built from
+/// notes can tell which ones changed
+pub fn modified_secs(path: &Path) -> anyhow::Result<u64> {
+    Ok(fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)?
+        .as_secs())
+}
+
+/// Loads a cache built from the notes, eg. the note index. None if there
+/// isn't one yet, or it can't be read and has to be rebuilt.
+pub fn load_notes_cache<T: DeserializeOwned>(path: &Path, name: &str) -> Option<T> {
+    let contents = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(cache) => Some(cache),
+        Err(err) => {
+            warn!("Rebuilding the {}, it couldn't be read: {}", name, err);
+            None
+        }
+    }
+}
+
+pub fn save_notes_cache(path: &Path, cache: &impl Serialize) -> anyhow::Result<()> {
+    fs::create_dir_all(path.parent().expect("Cache is in a directory"))?;
+    fs::write(path, serde_json::to_string(cache)?)?;
+    Ok(())
+}
+
+/// Where a note was moved to, if it's one of the `(from, to)` moves
+pub fn moved_to<'a>(moves: &'a [(PathBuf, PathBuf)], path: &Path) -> Option<&'a PathBuf> {
+    moves
+        .iter()
+        .find(|(from, _)| from == path)
+        .map(|(_, to)| to)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use time::macros::date;
+    use time::Date;
+
+    use super::{
+        append_to_section, archived_daily_note_path, attendee_links, format_note_path,
+        get_path_for_meeting, note_kind, note_metadata, note_metadata_list, note_template_vars,
+        parse_day, parse_days, render_note_template, render_template, replace_section,
+        section_text, take_unfinished_tasks, DailyNoteSpecifier, NoteSpecifier, DAILY_NOTE_FORMAT,
+    };
+
+    #[test]
+    fn test_daily_note_path_round_trips() {
+        let date = date!(2024 - 05 - 01);
+        let path = date.format(DAILY_NOTE_FORMAT).unwrap();
+        assert_eq!(path, "daily/2024/17/Wed_May_01.md");
+        assert_eq!(Date::parse(&path, DAILY_NOTE_FORMAT).unwrap(), date);
+    }
+
+    #[test]
+    fn test_week_placeholder_matches_daily_note_path() {
+        let note = NoteSpecifier::Daily {
+            day: DailyNoteSpecifier::Date(date!(2024 - 05 - 01)),
+        };
+        let week = note_template_vars(&note)
+            .into_iter()
+            .find(|(key, _)| *key == "week")
+            .and_then(|(_, value)| value);
+        assert_eq!(week.as_deref(), Some("17"));
+        assert_eq!(format_note_path(&note), "daily/2024/17/Wed_May_01.md");
+    }
+
+    #[test]
+    fn test_parse_day() {
+        // A Wednesday
+        let today = date!(2024 - 05 - 01);
+        assert_eq!(
+            parse_day("2024-04-12", today).unwrap(),
+            date!(2024 - 04 - 12)
+        );
+        assert_eq!(parse_day("-3d", today).unwrap(), date!(2024 - 04 - 28));
+        assert_eq!(parse_day("+1w", today).unwrap(), date!(2024 - 05 - 08));
+        assert_eq!(parse_day("fri", today).unwrap(), date!(2024 - 04 - 26));
+        assert_eq!(parse_day("wednesday", today).unwrap(), today);
+        assert!(parse_day("t", today).is_err());
+        assert!(parse_day("-3m", today).is_err());
+        assert!(parse_day("-", today).is_err());
+        assert!(parse_day("--3d", today).is_err());
+        assert!(parse_day(&format!("-{}d", i64::MAX), today).is_err());
+        assert!(parse_day("+99999999w", today).is_err());
+        assert!(parse_day("someday", today).is_err());
+    }
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("90d").unwrap(), 90);
+        assert_eq!(parse_days("12w").unwrap(), 84);
+        assert!(parse_days("90").is_err());
+        assert!(parse_days("xd").is_err());
+        assert!(parse_days("-3d").is_err());
+        assert!(parse_days(&format!("{}w", i64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_note_kind() {
+        assert_eq!(note_kind(Path::new("daily/2024/17/Wed_May_01.md")), "daily");
+        assert_eq!(note_kind(Path::new("topics/rust.md")), "topic");
+        assert_eq!(note_kind(Path::new("people/alice.md")), "person");
+        assert_eq!(
+            note_kind(Path::new("meetings/2024-05-01_planning.md")),
+            "meeting"
+        );
+        assert_eq!(note_kind(Path::new("snippets/cleanup.md")), "snippet");
+        assert_eq!(note_kind(Path::new("inbox.md")), "note");
+    }
+
+    #[test]
+    fn test_meeting_paths_and_links() {
+        assert_eq!(
+            get_path_for_meeting(date!(2024 - 05 - 01), "Sprint Planning"),
+            "meetings/2024-05-01_sprint_planning.md"
+        );
+        assert_eq!(
+            attendee_links(&["alice".to_string(), "Bob Jones".to_string()]),
+            "[[people/alice|alice]], [[people/bob_jones|Bob Jones]]"
+        );
+    }
+
+    #[test]
+    fn test_note_metadata() {
+        let contents = "# Alice Smith\n\n- Email: alice@example.com\nteam: infra\n";
+        assert_eq!(
+            note_metadata(contents, "email").as_deref(),
+            Some("alice@example.com")
+        );
+        assert_eq!(note_metadata(contents, "team").as_deref(), Some("infra"));
+        assert_eq!(note_metadata(contents, "phone"), None);
+        let contents = "# Alice Smith\nemail:\n\n```\nemail: bob@example.com\n```\n";
+        assert_eq!(note_metadata(contents, "email"), None);
+    }
+
+    #[test]
+    fn test_note_metadata_list() {
+        assert_eq!(note_metadata_list("tags: [a, b]\n", "tags"), vec!["a", "b"]);
+        assert_eq!(note_metadata_list("tags: a, b,\n", "tags"), vec!["a", "b"]);
+        assert_eq!(
+            note_metadata_list("tags:\n  - a\n  - b\nauthor: me\n", "tags"),
+            vec!["a", "b"]
+        );
+        assert!(note_metadata_list("tags:\n\n- a\n", "tags").is_empty());
+        assert!(note_metadata_list("# Title\n", "tags").is_empty());
+    }
+
+    #[test]
+    fn test_render_template() {
+        let vars = [("repo", Some("wkfl".to_string())), ("ticket", None)];
+        assert_eq!(
+            render_template("# {{repo}} {{ticket}}\n{{repo}}", &vars),
+            "# wkfl \nwkfl"
+        );
+    }
+
+    #[test]
+    fn test_render_note_template() {
+        let vars = [
+            ("name", Some("Alice".to_string())),
+            ("weekday", Some("Monday".to_string())),
+        ];
+        assert_eq!(
+            render_note_template("# {name}\n\n{{weekday}} {unknown}", &vars),
+            "# Alice\n\nMonday {unknown}"
+        );
+    }
+
+    #[test]
+    fn test_append_to_section() {
+        let note = append_to_section("# May 1st\n\n## ", "Chats", "- first");
+        assert_eq!(note, "# May 1st\n\n## Chats\n\n- first\n");
+        let note = append_to_section(&note, "Chats", "- second");
+        assert_eq!(note, "# May 1st\n\n## Chats\n\n- first\n- second\n");
+        let note = append_to_section(&note, "Focus", "Wrote the parser\n");
+        assert_eq!(
+            note,
+            "# May 1st\n\n## Chats\n\n- first\n- second\n\n## Focus\n\nWrote the parser\n"
+        );
+        assert_eq!(
+            append_to_section(&note, "Chats", "A paragraph"),
+            "# May 1st\n\n## Chats\n\n- first\n- second\n\nA paragraph\n\n## Focus\n\nWrote the parser\n"
+        );
+    }
+
+    #[test]
+    fn test_append_to_section_skips_code_blocks() {
+        let answer = "- Q: How?\n\n# Steps\n\n```sh\n# comment\nls\n```";
+        let note = append_to_section("# May 1st\n\n## Chats\n", "Chats", answer);
+        assert_eq!(
+            note,
+            "# May 1st\n\n## Chats\n\n- Q: How?\n\n### Steps\n\n```sh\n# comment\nls\n```\n"
+        );
+        assert_eq!(
+            append_to_section(&note, "Chats", "- Q: Again?"),
+            "# May 1st\n\n## Chats\n\n- Q: How?\n\n### Steps\n\n```sh\n# comment\nls\n```\n\n- Q: Again?\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_section() {
+        let note = "# Alice\n\n## Linked from\n\n- [[old]]\n\n## Notes\n\nHi\n";
+        assert_eq!(
+            replace_section(note, "Linked from", "- [[new]]"),
+            "# Alice\n\n## Linked from\n\n- [[new]]\n\n## Notes\n\nHi\n"
+        );
+        assert_eq!(
+            replace_section(note, "Linked from", ""),
+            "# Alice\n\n## Notes\n\nHi\n"
+        );
+        assert_eq!(
+            replace_section("# May 1st\n\n## ", "Linked from", "- [[a]]"),
+            "# May 1st\n\n## \n\n## Linked from\n\n- [[a]]\n"
+        );
+        assert_eq!(replace_section("# Bob", "Linked from", ""), "# Bob");
+    }
+
+    #[test]
+    fn test_section_text() {
+        let note = "# Alice
+
+## Linked from
+
+- [[a]]
+- [[b]]
+
+## Notes
+
+Hi
+";
+        assert_eq!(
+            section_text(note, "Linked from").as_deref(),
+            Some("- [[a]]\n- [[b]]")
+        );
+        assert_eq!(section_text(note, "Todo"), None);
+    }
+
+    #[test]
+    fn test_take_unfinished_tasks() {
+        let note = "# May 1st\n\n## Todo\n\n- [x] Done\n- [ ] Review PR\n  - ask about retries\n- [ ]\n- [ ] Write docs\n\n* [ ] Book room\n";
+        let (migrated, tasks) = take_unfinished_tasks(note);
+        assert_eq!(
+            migrated,
+            "# May 1st\n\n## Todo\n\n- [x] Done\n- [>] Review PR\n  - ask about retries\n- [>]\n- [>] Write docs\n\n* [>] Book room\n"
+        );
+        assert_eq!(
+            tasks,
+            vec![
+                "- [ ] Review PR\n  - ask about retries",
+                "- [ ]",
+                "- [ ] Write docs",
+                "* [ ] Book room"
+            ]
+        );
+        let (_, tasks) = take_unfinished_tasks(&migrated);
+        assert!(tasks.is_empty());
+    }
+
+    #[test]
+    fn test_take_nested_unfinished_task() {
+        let (migrated, tasks) = take_unfinished_tasks("- Project\n  - [ ] Nested\n    more\n");
+        assert_eq!(migrated, "- Project\n  - [>] Nested\n    more\n");
+        assert_eq!(tasks, vec!["- [ ] Nested\n  more"]);
+        let (_, tasks) = take_unfinished_tasks("  - [ ] Nested\n\u{3000}more\n");
+        assert_eq!(tasks, vec!["- [ ] Nested\nmore"]);
+    }
+
+    #[test]
+    fn test_archived_daily_note_path() {
+        let notes_dir = Path::new("/notes");
+        let note_path = notes_dir.join("daily/2024/17/Wed_May_01.md");
+        assert_eq!(
+            archived_daily_note_path(notes_dir, &note_path, date!(2024 - 05 - 01)),
+            Path::new("/notes/archive/2024/Wed_May_01.md")
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use base64::Engine;
+
+use crate::notes::{self, SNIPPETS_DIR};
+
+/// A named snippet, stored as snippets/<name>.md in the notes directory:
+///
+/// ````markdown
+/// # name
+/// tags: git, cleanup
+///
+/// ```sh
+/// git branch -d {{branch}}
+/// ```
+/// ````
+pub struct Snippet {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub body: String,
+}
+
+impl Snippet {
+    pub fn parse(name: &str, contents: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            tags: notes::note_metadata_list(contents, "tags"),
+            body: code_block(contents).unwrap_or_default(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        format!(
+            "# {}\ntags: {}\n\n```sh\n{}\n```\n",
+            self.name,
+            self.tags.join(", "),
+            self.body.trim_end()
+        )
+    }
+
+    /// Names of the `{{placeholders}}` in the body, in order of first use
+    pub fn placeholders(&self) -> Vec<String> {
+        let mut placeholders = vec![];
+        let mut rest = self.body.as_str();
+        while let Some(start) = rest.find("{{") {
+            let after_start = &rest[start + 2..];
+            let Some(end) = after_start.find("}}") else {
+                break;
+            };
+            let name = after_start[..end].to_string();
+            if !name.is_empty() && !placeholders.contains(&name) {
+                placeholders.push(name);
+            }
+            rest = &after_start[end + 2..];
+        }
+        placeholders
+    }
+}
+
+/// Contents of the first fenced code block
+fn code_block(contents: &str) -> Option<String> {
+    let mut lines = contents.lines();
+    lines.find(|line| line.trim_start().starts_with("```"))?;
+    let body: Vec<&str> = lines
+        .take_while(|line| !line.trim_start().starts_with("```"))
+        .collect();
+    Some(body.join("\n"))
+}
+
+/// Where the named snippet is kept. Names that are paths are rejected, so
+/// a snippet can't be read or written outside the snippets directory.
+pub fn snippet_path(notes_dir: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    let is_file_name = matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+        && !name.contains(['/', '\\']);
+    if !is_file_name {
+        anyhow::bail!("{} can't be a snippet name, names can't be paths", name);
+    }
+    Ok(notes_dir.join(SNIPPETS_DIR).join(format!("{}.md", name)))
+}
+
+pub fn load_snippets(notes_dir: &Path) -> anyhow::Result<Vec<Snippet>> {
+    notes::list_note_names(notes_dir, SNIPPETS_DIR)?
+        .into_iter()
+        .map(|name| {
+            let contents = fs::read_to_string(snippet_path(notes_dir, &name)?)?;
+            Ok(Snippet::parse(&name, &contents))
+        })
+        .collect()
+}
+
+/// Escape sequence asking the terminal to set the clipboard (OSC 52).
+/// Works over ssh, unlike shelling out to pbcopy/xclip.
+pub fn osc52_copy(text: &str) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    format!("\u{1b}]52;c;{}\u{7}", encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{snippet_path, Snippet};
+
+    #[test]
+    fn test_parse_round_trips() {
+        let snippet = Snippet {
+            name: "cleanup".to_string(),
+            tags: vec!["git".to_string(), "branches".to_string()],
+            body: "git branch -d {{branch}}".to_string(),
+        };
+        let parsed = Snippet::parse("cleanup", &snippet.to_markdown());
+        assert_eq!(parsed.tags, snippet.tags);
+        assert_eq!(parsed.body, snippet.body);
+    }
+
+    #[test]
+    fn test_parse_empty_tags() {
+        let snippet = Snippet {
+            name: "tag".to_string(),
+            tags: vec![],
+            body: "cat <<EOF\ntags: release\nEOF".to_string(),
+        };
+        let parsed = Snippet::parse("tag", &snippet.to_markdown());
+        assert!(parsed.tags.is_empty());
+        assert_eq!(parsed.body, snippet.body);
+    }
+
+    #[test]
+    fn test_snippet_path_rejects_paths() {
+        let notes_dir = Path::new("/notes");
+        assert_eq!(
+            snippet_path(notes_dir, "cleanup").unwrap(),
+            Path::new("/notes/snippets/cleanup.md")
+        );
+        for name in ["../../x", "..", "a/b", "/etc/passwd", "a\\b", "", "a/"] {
+            assert!(
+                snippet_path(notes_dir, name).is_err(),
+                "{} was allowed",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let snippet = Snippet {
+            name: "deploy".to_string(),
+            tags: vec![],
+            body: "deploy {{env}} {{version}} && echo {{env}}".to_string(),
+        };
+        assert_eq!(snippet.placeholders(), vec!["env", "version"]);
+    }
+}
@@ -17,6 +17,30 @@ pub fn run_commands(commands: &Vec<String>) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Ticket key (eg. ABC-123) at the start of one of the branch's path
+/// segments. Handles `wkfl start` branches (user/ABC-123_name) as well as
+/// the common ABC-123-name and feature/ABC-123 styles.
+pub fn extract_ticket_from_branch(branch: &str) -> Option<String> {
+    branch.split('/').find_map(|segment| {
+        let project_len = segment
+            .find(|c: char| !(c.is_ascii_uppercase() || c.is_ascii_digit()))
+            .unwrap_or(segment.len());
+        let (project, rest) = segment.split_at(project_len);
+        if !project.starts_with(|c: char| c.is_ascii_uppercase()) {
+            return None;
+        }
+        let rest = rest.strip_prefix('-')?;
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, after) = rest.split_at(number_len);
+        if number.is_empty() || after.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(format!("{}-{}", project, number))
+    })
+}
+
 const LOWERCASE_WORDS: &[&str] = &[
     "a", "an", "and", "as", "at", "but", "by", "for", "if", "in", "of", "on", "or", "the", "to",
     "up", "yet", "nor", "via",
@@ -69,7 +93,27 @@ pub fn to_title_case(input: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::to_title_case;
+    use super::{extract_ticket_from_branch, to_title_case};
+
+    #[test]
+    fn test_extract_ticket_from_branch() {
+        assert_eq!(
+            extract_ticket_from_branch("kdeal/ABC-123_some_feature").as_deref(),
+            Some("ABC-123")
+        );
+        assert_eq!(
+            extract_ticket_from_branch("feature/PROJ2-7").as_deref(),
+            Some("PROJ2-7")
+        );
+        assert_eq!(
+            extract_ticket_from_branch("AB-1-fix-thing").as_deref(),
+            Some("AB-1")
+        );
+        assert_eq!(extract_ticket_from_branch("kdeal/some_feature"), None);
+        assert_eq!(extract_ticket_from_branch("main"), None);
+        assert_eq!(extract_ticket_from_branch("kdeal/UTF-8x_fix"), None);
+    }
+
     #[test]
     fn test_empty_string() {
         assert_eq!(to_title_case(""), "");